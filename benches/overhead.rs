@@ -0,0 +1,94 @@
+//! Compares the helper against hand-rolled `ManuallyDrop` code doing the same thing.
+//!
+//! Both variants of each case should report the same time, within noise.
+
+use core::hint::black_box;
+use core::mem::ManuallyDrop;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use into_inner_drop::{IntoInnerHelper, DetachedDrop, InPlace};
+
+struct Consume<const N: usize>;
+
+impl<const N: usize> DetachedDrop for Consume<N> {
+    type Implementor = [u8; N];
+
+    fn drop(value: Self::Implementor) {
+        black_box(value);
+    }
+}
+
+struct ConsumeInPlace<const N: usize>;
+
+impl<const N: usize> DetachedDrop for ConsumeInPlace<N> {
+    type Implementor = [u8; N];
+
+    fn drop(value: Self::Implementor) {
+        black_box(value);
+    }
+
+    fn drop_in_place(mut value: InPlace<'_, Self::Implementor>) {
+        black_box(&mut *value);
+    }
+}
+
+struct Manual<const N: usize>(ManuallyDrop<[u8; N]>);
+
+impl<const N: usize> Manual<N> {
+    fn new(value: [u8; N]) -> Self {
+        Manual(ManuallyDrop::new(value))
+    }
+
+    fn into_inner(self) -> [u8; N] {
+        unsafe {
+            let value = core::ptr::read(&*self.0);
+            core::mem::forget(self);
+            value
+        }
+    }
+}
+
+impl<const N: usize> Drop for Manual<N> {
+    fn drop(&mut self) {
+        black_box(unsafe { core::ptr::read(&*self.0) });
+    }
+}
+
+fn bench_size<const N: usize>(c: &mut Criterion) {
+    let mut group = c.benchmark_group("drop");
+    group.bench_function(BenchmarkId::new("helper", N), |b| b.iter(|| {
+        core::mem::drop(<IntoInnerHelper<_, Consume<N>>>::new(black_box([42; N])))
+    }));
+    group.bench_function(BenchmarkId::new("manual", N), |b| b.iter(|| {
+        core::mem::drop(Manual::new(black_box([42; N])))
+    }));
+    group.finish();
+
+    let mut group = c.benchmark_group("into_inner");
+    group.bench_function(BenchmarkId::new("helper", N), |b| b.iter(|| {
+        black_box(<IntoInnerHelper<_, Consume<N>>>::new(black_box([42; N])).into_inner())
+    }));
+    group.bench_function(BenchmarkId::new("manual", N), |b| b.iter(|| {
+        black_box(Manual::new(black_box([42; N])).into_inner())
+    }));
+    group.finish();
+
+    // Unlike the cases above this is expected to differ: the in-place drop avoids copying the
+    // value out of the helper, which is noticeable for large values.
+    let mut group = c.benchmark_group("drop_in_place");
+    group.bench_function(BenchmarkId::new("moved", N), |b| b.iter(|| {
+        core::mem::drop(black_box(<IntoInnerHelper<_, Consume<N>>>::new([42; N])))
+    }));
+    group.bench_function(BenchmarkId::new("in_place", N), |b| b.iter(|| {
+        core::mem::drop(black_box(<IntoInnerHelper<_, ConsumeInPlace<N>>>::new([42; N])))
+    }));
+    group.finish();
+}
+
+fn overhead(c: &mut Criterion) {
+    bench_size::<8>(c);
+    bench_size::<64>(c);
+    bench_size::<1024>(c);
+}
+
+criterion_group!(benches, overhead);
+criterion_main!(benches);
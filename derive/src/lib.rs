@@ -0,0 +1,180 @@
+//! Derive macros for `into_inner_drop`.
+//!
+//! Use them through the `derive` feature of `into_inner_drop` rather than depending on this crate
+//! directly.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::{TokenStream as TokenStream2, Span};
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, GenericArgument, Ident, Member, PathArguments, Type};
+use syn::punctuated::Punctuated;
+
+/// Forwards traits of a newtype to the inner value of the `IntoInnerHelper` it holds.
+///
+/// The struct must have exactly one field, the helper. By default `Deref`, `DerefMut`, `AsRef`
+/// and `Debug` are implemented, `Deref`, `DerefMut` and `AsRef` targeting the guarded value and
+/// `Debug` forwarding to it transparently. Use `#[guard_wrapper(...)]` to pick only some of them:
+///
+/// ```
+/// use into_inner_drop::{IntoInnerHelper, DetachedDrop, GuardWrapper};
+///
+/// enum Flush {}
+///
+/// impl DetachedDrop for Flush {
+///     type Implementor = Vec<u8>;
+///
+///     fn drop(_: Self::Implementor) {}
+/// }
+///
+/// #[derive(GuardWrapper)]
+/// #[guard_wrapper(Deref, AsRef)]
+/// pub struct Buffer(IntoInnerHelper<Vec<u8>, Flush>);
+///
+/// let buffer = Buffer(IntoInnerHelper::new(vec![1, 2, 3]));
+/// assert_eq!(buffer.len(), 3);
+/// assert_eq!(AsRef::<Vec<u8>>::as_ref(&buffer), &[1, 2, 3]);
+/// ```
+///
+/// Traits that were not selected are not implemented:
+///
+/// ```compile_fail
+/// use into_inner_drop::{IntoInnerHelper, DetachedDrop, GuardWrapper};
+///
+/// enum Flush {}
+///
+/// impl DetachedDrop for Flush {
+///     type Implementor = Vec<u8>;
+///
+///     fn drop(_: Self::Implementor) {}
+/// }
+///
+/// #[derive(GuardWrapper)]
+/// #[guard_wrapper(Deref)]
+/// pub struct Buffer(IntoInnerHelper<Vec<u8>, Flush>);
+///
+/// let mut buffer = Buffer(IntoInnerHelper::new(vec![1, 2, 3]));
+/// buffer.push(4);
+/// ```
+///
+/// Unknown traits are rejected:
+///
+/// ```compile_fail
+/// use into_inner_drop::{IntoInnerHelper, DetachedDrop, GuardWrapper};
+///
+/// enum Flush {}
+///
+/// impl DetachedDrop for Flush {
+///     type Implementor = Vec<u8>;
+///
+///     fn drop(_: Self::Implementor) {}
+/// }
+///
+/// #[derive(GuardWrapper)]
+/// #[guard_wrapper(Display)]
+/// pub struct Buffer(IntoInnerHelper<Vec<u8>, Flush>);
+/// ```
+#[proc_macro_derive(GuardWrapper, attributes(guard_wrapper))]
+pub fn derive_guard_wrapper(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    guard_wrapper(input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+#[derive(Copy, Clone)]
+enum Forward {
+    Deref,
+    DerefMut,
+    AsRef,
+    Debug,
+}
+
+impl Forward {
+    const ALL: [Forward; 4] = [Forward::Deref, Forward::DerefMut, Forward::AsRef, Forward::Debug];
+
+    fn parse(ident: &Ident) -> Result<Self, Error> {
+        match &*ident.to_string() {
+            "Deref" => Ok(Forward::Deref),
+            "DerefMut" => Ok(Forward::DerefMut),
+            "AsRef" => Ok(Forward::AsRef),
+            "Debug" => Ok(Forward::Debug),
+            _ => Err(Error::new(ident.span(), "unsupported trait, expected one of `Deref`, `DerefMut`, `AsRef`, `Debug`")),
+        }
+    }
+}
+
+fn selected_traits(input: &DeriveInput) -> Result<Vec<Forward>, Error> {
+    let mut selected = None::<Vec<Forward>>;
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("guard_wrapper")) {
+        let idents = attr.parse_args_with(Punctuated::<Ident, syn::Token![,]>::parse_terminated)?;
+        let forwards = idents.iter().map(Forward::parse).collect::<Result<Vec<_>, _>>()?;
+        selected.get_or_insert_with(Vec::new).extend(forwards);
+    }
+    Ok(selected.unwrap_or_else(|| Forward::ALL.to_vec()))
+}
+
+/// Returns the guarded type, the first generic argument of the helper.
+fn guarded_type(field_type: &Type) -> TokenStream2 {
+    if let Type::Path(path) = field_type {
+        if let Some(PathArguments::AngleBracketed(arguments)) = path.path.segments.last().map(|segment| &segment.arguments) {
+            if let Some(GenericArgument::Type(guarded)) = arguments.args.first() {
+                return quote!(#guarded);
+            }
+        }
+    }
+    quote!(<#field_type as ::core::ops::Deref>::Target)
+}
+
+fn guard_wrapper(input: DeriveInput) -> Result<TokenStream2, Error> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => return Err(Error::new(Span::call_site(), "`GuardWrapper` can only be derived for structs")),
+    };
+    let field = match fields {
+        Fields::Named(fields) if fields.named.len() == 1 => &fields.named[0],
+        Fields::Unnamed(fields) if fields.unnamed.len() == 1 => &fields.unnamed[0],
+        _ => return Err(Error::new_spanned(fields, "`GuardWrapper` requires a struct with exactly one field holding the helper")),
+    };
+    let member = match &field.ident {
+        Some(ident) => Member::Named(ident.clone()),
+        None => Member::Unnamed(0.into()),
+    };
+    let name = &input.ident;
+    let guarded = guarded_type(&field.ty);
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    let impls = selected_traits(&input)?.into_iter().map(|forward| match forward {
+        Forward::Deref => quote! {
+            impl #impl_generics ::core::ops::Deref for #name #type_generics #where_clause {
+                type Target = #guarded;
+
+                fn deref(&self) -> &Self::Target {
+                    &self.#member
+                }
+            }
+        },
+        Forward::DerefMut => quote! {
+            impl #impl_generics ::core::ops::DerefMut for #name #type_generics #where_clause {
+                fn deref_mut(&mut self) -> &mut Self::Target {
+                    &mut self.#member
+                }
+            }
+        },
+        Forward::AsRef => quote! {
+            impl #impl_generics ::core::convert::AsRef<#guarded> for #name #type_generics #where_clause {
+                fn as_ref(&self) -> &#guarded {
+                    &self.#member
+                }
+            }
+        },
+        Forward::Debug => quote! {
+            impl #impl_generics ::core::fmt::Debug for #name #type_generics #where_clause {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    ::core::fmt::Debug::fmt(&self.#member, f)
+                }
+            }
+        },
+    });
+
+    Ok(quote!(#(#impls)*))
+}
@@ -0,0 +1,163 @@
+//! A collection-like guard: a buffer that is flushed when dropped.
+//!
+//! This can be used as a template for guards that should feel like the collection they wrap.
+//! Indexing, slicing and iteration are forwarded through the helper to the inner `Vec`.
+
+use std::cell::RefCell;
+use std::ops::{Index, IndexMut};
+use into_inner_drop::IntoInnerHelper;
+
+thread_local! {
+    // Stands in for a file or a socket.
+    static SINK: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+mod inner {
+    pub(super) enum Flush {}
+
+    impl into_inner_drop::DetachedDrop for Flush {
+        type Implementor = Vec<u8>;
+
+        fn drop(data: Self::Implementor) {
+            super::SINK.with(|sink| sink.borrow_mut().extend_from_slice(&data));
+        }
+    }
+}
+
+/// A buffer which flushes its contents to the sink when dropped.
+pub struct GuardedBuffer(IntoInnerHelper<Vec<u8>, inner::Flush>);
+
+impl GuardedBuffer {
+    /// Creates an empty buffer.
+    pub fn new() -> Self {
+        GuardedBuffer(IntoInnerHelper::new(Vec::new()))
+    }
+
+    /// Appends bytes to the buffer.
+    pub fn extend_from_slice(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes)
+    }
+
+    /// Returns the number of buffered bytes.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if the buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterates over the buffered bytes.
+    pub fn iter(&self) -> std::slice::Iter<'_, u8> {
+        self.into_iter()
+    }
+
+    /// Takes out the contents without flushing.
+    pub fn into_inner(self) -> Vec<u8> {
+        self.0.into_inner()
+    }
+}
+
+impl Default for GuardedBuffer {
+    fn default() -> Self {
+        GuardedBuffer::new()
+    }
+}
+
+impl<I> Index<I> for GuardedBuffer where Vec<u8>: Index<I> {
+    type Output = <Vec<u8> as Index<I>>::Output;
+
+    fn index(&self, index: I) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl<I> IndexMut<I> for GuardedBuffer where Vec<u8>: IndexMut<I> {
+    fn index_mut(&mut self, index: I) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+impl<'a> IntoIterator for &'a GuardedBuffer {
+    type Item = &'a u8;
+    type IntoIter = std::slice::Iter<'a, u8>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        (&self.0).into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut GuardedBuffer {
+    type Item = &'a mut u8;
+    type IntoIter = std::slice::IterMut<'a, u8>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter_mut()
+    }
+}
+
+fn main() {
+    let mut buffer = GuardedBuffer::new();
+    buffer.extend_from_slice(b"hello");
+    buffer[0] = b'H';
+    println!("buffered {:?}", &buffer[..]);
+    drop(buffer);
+    SINK.with(|sink| println!("flushed {:?}", sink.borrow()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GuardedBuffer, SINK};
+
+    fn flushed() -> Vec<u8> {
+        SINK.with(|sink| sink.take())
+    }
+
+    #[test]
+    fn index() {
+        let mut buffer = GuardedBuffer::new();
+        buffer.extend_from_slice(b"hello");
+        buffer[0] = b'H';
+        assert_eq!(buffer[0], b'H');
+        assert_eq!(buffer[4], b'o');
+    }
+
+    #[test]
+    fn slice() {
+        let mut buffer = GuardedBuffer::new();
+        buffer.extend_from_slice(b"hello");
+        assert_eq!(&buffer[1..3], b"el");
+        buffer[..2].copy_from_slice(b"HE");
+        assert_eq!(&buffer[..], b"HEllo");
+    }
+
+    #[test]
+    fn iterate() {
+        let mut buffer = GuardedBuffer::new();
+        buffer.extend_from_slice(b"abc");
+        for byte in &mut buffer {
+            *byte = byte.to_ascii_uppercase();
+        }
+        assert_eq!(buffer.iter().copied().collect::<Vec<_>>(), b"ABC");
+        assert_eq!((&buffer).into_iter().len(), buffer.len());
+    }
+
+    #[test]
+    fn drop_flushes() {
+        let mut buffer = GuardedBuffer::new();
+        buffer.extend_from_slice(b"hello");
+        assert!(flushed().is_empty());
+        drop(buffer);
+        assert_eq!(flushed(), b"hello");
+    }
+
+    #[test]
+    fn into_inner_doesnt_flush() {
+        let mut buffer = GuardedBuffer::new();
+        buffer.extend_from_slice(b"hello");
+        assert!(!buffer.is_empty());
+        assert_eq!(buffer.into_inner(), b"hello");
+        assert!(flushed().is_empty());
+    }
+}
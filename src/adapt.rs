@@ -0,0 +1,157 @@
+use core::mem::ManuallyDrop;
+use crate::{IntoInnerHelper, DetachedDrop};
+use crate::unwind::detached_drop;
+
+/// A helper holding the inner value temporarily converted into a different type.
+///
+/// Created by `IntoInnerHelper::adapt`. When dropped, the value is converted back using the stored
+/// conversion function and the original detached drop runs on the result.
+pub struct AdaptedHelper<U, G, D> where D: DetachedDrop, G: FnOnce(U) -> D::Implementor {
+    adapted: ManuallyDrop<U>,
+    from: ManuallyDrop<G>,
+    _phantom: core::marker::PhantomData<fn() -> D>,
+}
+
+impl<T, D> IntoInnerHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    /// Converts the inner value into a different type while keeping the drop behavior.
+    ///
+    /// `into` is called immediately, `from` is called to get the original type back either in
+    /// `AdaptedHelper::unadapt` or before running the detached drop.
+    ///
+    /// This is useful for e.g. wrapping a `File` in a `BufWriter` and unwrapping it when done.
+    /// If `into` panics the value is dropped without running the detached drop.
+    pub fn adapt<U, F, G>(self, into: F, from: G) -> AdaptedHelper<U, G, D> where F: FnOnce(T) -> U, G: FnOnce(U) -> T {
+        AdaptedHelper {
+            adapted: ManuallyDrop::new(into(self.into_inner())),
+            from: ManuallyDrop::new(from),
+            _phantom: Default::default(),
+        }
+    }
+}
+
+impl<U, G, D> AdaptedHelper<U, G, D> where D: DetachedDrop, G: FnOnce(U) -> D::Implementor {
+    /// Accesses the adapted value.
+    pub fn inner(&self) -> &U {
+        &self.adapted
+    }
+
+    /// Accesses the adapted value mutably.
+    pub fn inner_mut(&mut self) -> &mut U {
+        &mut self.adapted
+    }
+
+    /// Converts the value back to the original type, restoring the original helper.
+    pub fn unadapt(self) -> IntoInnerHelper<D::Implementor, D> {
+        let (adapted, from) = self.into_parts();
+        IntoInnerHelper::new(from(adapted))
+    }
+
+    /// Moves out the adapted value, preventing the detached drop.
+    pub fn into_inner(self) -> U {
+        self.into_parts().0
+    }
+
+    fn into_parts(self) -> (U, G) {
+        unsafe {
+            let adapted = core::ptr::read(&*self.adapted);
+            let from = core::ptr::read(&*self.from);
+            core::mem::forget(self);
+            (adapted, from)
+        }
+    }
+}
+
+impl<U, G, D> Drop for AdaptedHelper<U, G, D> where D: DetachedDrop, G: FnOnce(U) -> D::Implementor {
+    fn drop(&mut self) {
+        unsafe {
+            let adapted = core::ptr::read(&*self.adapted);
+            let from = core::ptr::read(&*self.from);
+            detached_drop::<D>(from(adapted));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use crate::{IntoInnerHelper, DetachedDrop};
+
+    std::thread_local! {
+        static DROPPED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    fn dropped() -> usize {
+        DROPPED.with(Cell::get)
+    }
+
+    enum CountDrop {}
+
+    impl DetachedDrop for CountDrop {
+        type Implementor = dropcheck::DropToken;
+
+        fn drop(_: Self::Implementor) {
+            DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+        }
+    }
+
+    struct Buffered {
+        token: dropcheck::DropToken,
+        buffer: u32,
+    }
+
+    fn buffer(token: dropcheck::DropToken) -> Buffered {
+        Buffered { token, buffer: 0 }
+    }
+
+    fn unbuffer(buffered: Buffered) -> dropcheck::DropToken {
+        buffered.token
+    }
+
+    #[test]
+    fn drop_runs_on_original() {
+        let check = dropcheck::DropCheck::new();
+        let (drop_token, drop_state) = check.pair();
+        let helper = <IntoInnerHelper<_, CountDrop>>::new(drop_token);
+        let mut adapted = helper.adapt(buffer, unbuffer);
+        adapted.inner_mut().buffer = 42;
+        assert_eq!(adapted.inner().buffer, 42);
+        core::mem::drop(adapted);
+        assert_eq!(dropped(), 1);
+        assert!(drop_state.is_dropped());
+    }
+
+    #[test]
+    fn unadapt() {
+        let check = dropcheck::DropCheck::new();
+        let (drop_token, drop_state) = check.pair();
+        let helper = <IntoInnerHelper<_, CountDrop>>::new(drop_token);
+        let helper = helper.adapt(buffer, unbuffer).unadapt();
+        assert_eq!(dropped(), 0);
+        assert!(drop_state.is_not_dropped());
+        core::mem::drop(helper);
+        assert_eq!(dropped(), 1);
+    }
+
+    #[test]
+    fn into_inner() {
+        let check = dropcheck::DropCheck::new();
+        let (drop_token, drop_state) = check.pair();
+        let helper = <IntoInnerHelper<_, CountDrop>>::new(drop_token);
+        let buffered = helper.adapt(buffer, unbuffer).into_inner();
+        assert!(drop_state.is_not_dropped());
+        core::mem::drop(buffered);
+        assert_eq!(dropped(), 0);
+        assert!(drop_state.is_dropped());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unwinding() {
+        use crate::unwind::tests::{Record, SkipOnPanic, drop_while_panicking};
+
+        let adapted = <IntoInnerHelper<_, Record>>::new(42).adapt(u64::from, |value| value as u32);
+        assert_eq!(drop_while_panicking(adapted), ["drop_panicking"]);
+        let adapted = <IntoInnerHelper<_, SkipOnPanic>>::new(42).adapt(u64::from, |value| value as u32);
+        assert!(drop_while_panicking(adapted).is_empty());
+    }
+}
@@ -0,0 +1,224 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use crate::DetachedDrop;
+use crate::unwind::detached_drop;
+
+/// A replacement trait for providing asynchronous Drop implementation.
+///
+/// Rust can't await in `Drop`, so the asynchronous cleanup only runs when the helper is finalized
+/// explicitly. If a helper is dropped without finalizing, the synchronous `DetachedDrop::drop`
+/// runs instead. If it's dropped while the asynchronous cleanup is in progress, the cleanup future
+/// is dropped, cancelling it.
+pub trait AsyncDetachedDrop: DetachedDrop {
+    /// The future performing the cleanup.
+    type Future: Future<Output=()>;
+
+    /// The asynchronous drop implementation called by `AsyncHelper::poll_finalize`.
+    fn drop_async(value: Self::Implementor) -> Self::Future;
+}
+
+/// The helper running asynchronous cleanup when finalized.
+pub struct AsyncHelper<T, D> where D: AsyncDetachedDrop<Implementor=T> {
+    state: State<T, D::Future>,
+}
+
+enum State<T, F> {
+    Holding(T),
+    Running(F),
+    Done,
+}
+
+impl<T, D> AsyncHelper<T, D> where D: AsyncDetachedDrop<Implementor=T> {
+    /// Creates the helper.
+    pub fn new(inner: T) -> Self {
+        AsyncHelper {
+            state: State::Holding(inner),
+        }
+    }
+
+    /// Accesses the inner value if the finalization didn't start yet.
+    pub fn inner(&self) -> Option<&T> {
+        match &self.state {
+            State::Holding(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if the asynchronous cleanup completed.
+    pub fn is_finalized(&self) -> bool {
+        matches!(self.state, State::Done)
+    }
+
+    /// Moves out the inner value if the finalization didn't start yet.
+    ///
+    /// If the cleanup is running it's cancelled.
+    pub fn into_inner(mut self) -> Option<T> {
+        match core::mem::replace(&mut self.state, State::Done) {
+            State::Holding(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Drives the asynchronous cleanup.
+    ///
+    /// This is intended for embedding the helper in hand-written futures. The first call starts
+    /// the cleanup, the following ones poll it. Polling after the cleanup completed returns
+    /// `Poll::Ready` immediately.
+    pub fn poll_finalize(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // SAFETY: the future is never moved once stored in `state`, only dropped in place. The
+        // inner value is not pinned so it's fine to move it out.
+        let this = unsafe { self.get_unchecked_mut() };
+        if let State::Holding(_) = this.state {
+            if let State::Holding(value) = core::mem::replace(&mut this.state, State::Done) {
+                this.state = State::Running(D::drop_async(value));
+            }
+        }
+        match &mut this.state {
+            State::Running(future) => {
+                // SAFETY: see above.
+                let future = unsafe { Pin::new_unchecked(future) };
+                if future.poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                this.state = State::Done;
+                Poll::Ready(())
+            },
+            _ => Poll::Ready(()),
+        }
+    }
+
+    /// Runs the asynchronous cleanup to completion.
+    pub async fn finalize(self) {
+        let mut this = core::pin::pin!(self);
+        core::future::poll_fn(|cx| this.as_mut().poll_finalize(cx)).await
+    }
+}
+
+impl<T, D> Drop for AsyncHelper<T, D> where D: AsyncDetachedDrop<Implementor=T> {
+    fn drop(&mut self) {
+        // The running future must not be moved, so only the inner value is taken out. The future
+        // is dropped in place afterwards.
+        if let State::Holding(_) = self.state {
+            if let State::Holding(value) = core::mem::replace(&mut self.state, State::Done) {
+                detached_drop::<D>(value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+    use core::cell::RefCell;
+    use core::future::Future;
+    use core::pin::{Pin, pin};
+    use core::task::{Context, Poll, Waker};
+    use super::{AsyncHelper, AsyncDetachedDrop};
+    use crate::DetachedDrop;
+
+    std::thread_local! {
+        static EVENTS: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn record(event: &'static str) {
+        EVENTS.with(|events| events.borrow_mut().push(event));
+    }
+
+    fn events() -> Vec<&'static str> {
+        EVENTS.with(|events| events.take())
+    }
+
+    struct Flush {
+        polled: bool,
+        done: bool,
+    }
+
+    impl Future for Flush {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Self::Output> {
+            assert!(!self.done);
+            if self.polled {
+                self.done = true;
+                record("flushed");
+                Poll::Ready(())
+            } else {
+                self.polled = true;
+                record("pending");
+                Poll::Pending
+            }
+        }
+    }
+
+    impl Drop for Flush {
+        fn drop(&mut self) {
+            if !self.done {
+                record("cancelled");
+            }
+        }
+    }
+
+    enum Close {}
+
+    impl DetachedDrop for Close {
+        type Implementor = u32;
+
+        fn drop(_: Self::Implementor) {
+            record("sync");
+        }
+    }
+
+    impl AsyncDetachedDrop for Close {
+        type Future = Flush;
+
+        fn drop_async(_: Self::Implementor) -> Self::Future {
+            Flush { polled: false, done: false, }
+        }
+    }
+
+    #[test]
+    fn poll_finalize() {
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut helper = pin!(<AsyncHelper<_, Close>>::new(42));
+        assert_eq!(helper.inner(), Some(&42));
+        assert!(helper.as_mut().poll_finalize(&mut cx).is_pending());
+        assert_eq!(helper.inner(), None);
+        assert!(helper.as_mut().poll_finalize(&mut cx).is_ready());
+        assert!(helper.is_finalized());
+        assert!(helper.as_mut().poll_finalize(&mut cx).is_ready());
+        assert_eq!(events(), ["pending", "flushed"]);
+    }
+
+    #[test]
+    fn finalize() {
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut future = pin!(<AsyncHelper<_, Close>>::new(42).finalize());
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+        assert!(future.as_mut().poll(&mut cx).is_ready());
+        assert_eq!(events(), ["pending", "flushed"]);
+    }
+
+    #[test]
+    fn drop_falls_back_to_sync() {
+        core::mem::drop(<AsyncHelper<_, Close>>::new(42));
+        assert_eq!(events(), ["sync"]);
+    }
+
+    #[test]
+    fn drop_cancels_running() {
+        let mut cx = Context::from_waker(Waker::noop());
+        {
+            let mut helper = pin!(<AsyncHelper<_, Close>>::new(42));
+            assert!(helper.as_mut().poll_finalize(&mut cx).is_pending());
+        }
+        assert_eq!(events(), ["pending", "cancelled"]);
+    }
+
+    #[test]
+    fn into_inner() {
+        let helper = <AsyncHelper<_, Close>>::new(42);
+        assert_eq!(helper.into_inner(), Some(42));
+        assert!(events().is_empty());
+    }
+}
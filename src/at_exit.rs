@@ -0,0 +1,112 @@
+use std::boxed::Box;
+use std::sync::Mutex;
+use std::vec::Vec;
+use crate::{IntoInnerHelper, DetachedDrop};
+
+type Cleanup = Box<dyn FnOnce() + Send>;
+
+static REGISTERED: Mutex<Vec<Cleanup>> = Mutex::new(Vec::new());
+
+extern "C" {
+    // Provided by the C runtime on every platform supported by `std`.
+    fn atexit(callback: extern "C" fn()) -> core::ffi::c_int;
+}
+
+extern "C" fn run_registered() {
+    // Cleanups registered by other cleanups run too.
+    loop {
+        let cleanup = match REGISTERED.lock() {
+            Ok(mut registered) => registered.pop(),
+            Err(poisoned) => poisoned.into_inner().pop(),
+        };
+        match cleanup {
+            // Unwinding out of an `extern "C"` function aborts, so panics are contained here.
+            Some(cleanup) => { let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(cleanup)); },
+            None => break,
+        }
+    }
+}
+
+impl<T, D> IntoInnerHelper<T, D> where T: Send + 'static, D: DetachedDrop<Implementor=T> + 'static {
+    /// Defers the detached drop to process exit.
+    ///
+    /// The registered cleanups run in reverse order of registration when the process exits
+    /// normally, that is when `main` returns or `std::process::exit` is called. They don't run
+    /// if the process is aborted or killed by a signal, nor if it exits without going through the
+    /// C runtime.
+    ///
+    /// Code running at exit is fragile. Other threads may still be running, thread-local
+    /// variables of the main thread may already be destroyed and the standard streams may be
+    /// closed. Keep the detached drop simple, e.g. flushing a file or removing a lock file, and
+    /// don't rely on it for platforms without a C runtime. Panics in the cleanup are caught and
+    /// ignored.
+    ///
+    /// Returns `Err(self)` if registering the exit hook with the C runtime failed.
+    pub fn finalize_at_exit(self) -> Result<(), Self> {
+        static HOOKED: std::sync::Once = std::sync::Once::new();
+        static HOOK_FAILED: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(false);
+
+        // SAFETY: `run_registered` is a valid callback that never unwinds.
+        HOOKED.call_once(|| if unsafe { atexit(run_registered) } != 0 {
+            HOOK_FAILED.store(true, core::sync::atomic::Ordering::Relaxed);
+        });
+        if HOOK_FAILED.load(core::sync::atomic::Ordering::Relaxed) {
+            return Err(self);
+        }
+        let mut registered = REGISTERED.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        registered.push(Box::new(move || self.finalize()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::path::PathBuf;
+    use crate::{IntoInnerHelper, DetachedDrop};
+
+    const LOG_VAR: &str = "INTO_INNER_DROP_TEST_AT_EXIT_LOG";
+
+    struct AppendToLog {
+        path: PathBuf,
+        line: &'static str,
+    }
+
+    enum Append {}
+
+    impl DetachedDrop for Append {
+        type Implementor = AppendToLog;
+
+        fn drop(entry: Self::Implementor) {
+            let mut file = std::fs::OpenOptions::new().append(true).create(true).open(&entry.path).unwrap();
+            writeln!(file, "{}", entry.line).unwrap();
+        }
+    }
+
+    // The test spawns the test binary again, running only itself, with the log path set. The
+    // child registers the cleanups and the parent checks they ran after the child exited.
+    #[test]
+    fn runs_at_process_exit() {
+        if let Some(path) = std::env::var_os(LOG_VAR) {
+            let path = PathBuf::from(path);
+            for line in ["first", "second"] {
+                assert!(<IntoInnerHelper<_, Append>>::new(AppendToLog { path: path.clone(), line }).finalize_at_exit().is_ok());
+            }
+            let _ = <IntoInnerHelper<_, Append>>::new(AppendToLog { path, line: "extracted" }).into_inner();
+            return;
+        }
+
+        let path = std::env::temp_dir().join(std::format!("into_inner_drop_at_exit_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let status = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "at_exit::tests::runs_at_process_exit", "--test-threads=1"])
+            .env(LOG_VAR, &path)
+            .stdout(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(log, "second\nfirst\n");
+    }
+}
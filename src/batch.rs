@@ -0,0 +1,364 @@
+//! Dropping many values with the same marker.
+//!
+//! If the detached drop of one element panics, the remaining elements are still dropped and the
+//! panic is propagated afterwards, like Rust does for arrays and `Vec`. This is done by dropping
+//! the remaining elements while unwinding, so it works the same way with and without `std`. As
+//! with built-in collections, a second panic while unwinding aborts the process.
+
+use core::convert::Infallible;
+use core::marker::PhantomData;
+use crate::DetachedDrop;
+use crate::unwind::detached_drop;
+#[cfg(feature = "alloc")]
+use crate::{IntoInnerHelper, DetachedDropWithOutput};
+
+/// Drops the values remaining in the iterator, only non-empty if a drop panicked.
+struct DropRemaining<D, I>(I, PhantomData<D>) where D: DetachedDrop, I: Iterator<Item=D::Implementor>;
+
+impl<D, I> Drop for DropRemaining<D, I> where D: DetachedDrop, I: Iterator<Item=D::Implementor> {
+    fn drop(&mut self) {
+        self.0.by_ref().for_each(detached_drop::<D>);
+    }
+}
+
+/// Drops all `values` using `D`, in order.
+///
+/// If a drop panics, the remaining values are still dropped before the panic propagates.
+pub fn drop_each<D, I>(values: I) where D: DetachedDrop, I: IntoIterator<Item=D::Implementor> {
+    let mut remaining = DropRemaining::<D, _>(values.into_iter(), PhantomData);
+    remaining.0.by_ref().for_each(detached_drop::<D>);
+}
+
+/// Finalizes all `values` using `D`, in order, collecting the outputs.
+///
+/// If a drop panics, the remaining values are dropped using `DetachedDrop::drop` before the panic
+/// propagates.
+#[cfg(feature = "alloc")]
+fn finalize_each<D, I>(values: I) -> alloc::vec::Vec<D::Output> where D: DetachedDropWithOutput, I: IntoIterator<Item=D::Implementor> {
+    let mut remaining = DropRemaining::<D, _>(values.into_iter(), PhantomData);
+    remaining.0.by_ref().map(D::drop_with_output).collect()
+}
+
+/// A marker dropping each element of an array using `D`.
+pub struct DropEach<D, const N: usize>(Infallible, PhantomData<D>);
+
+impl<D, const N: usize> DetachedDrop for DropEach<D, N> where D: DetachedDrop {
+    type Implementor = [D::Implementor; N];
+
+    #[cfg(feature = "std")]
+    const RUN_ON_PANIC: bool = D::RUN_ON_PANIC;
+
+    fn drop(values: Self::Implementor) {
+        drop_each::<D, _>(values);
+    }
+
+    #[cfg(feature = "std")]
+    fn drop_during_panic(values: Self::Implementor, context: &crate::PanicContext, token: crate::HookToken) {
+        IntoIterator::into_iter(values).for_each(|value| crate::unwind::detached_drop_during_panic::<D>(value, context, token));
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, D, const N: usize> IntoInnerHelper<[T; N], DropEach<D, N>> where D: DetachedDropWithOutput<Implementor=T> {
+    /// Finalizes all elements in order, collecting the outputs.
+    pub fn finalize_all(self) -> alloc::vec::Vec<D::Output> {
+        finalize_each::<D, _>(self.into_inner())
+    }
+}
+
+#[cfg(feature = "alloc")]
+struct DropEachVec<D>(Infallible, PhantomData<D>);
+
+#[cfg(feature = "alloc")]
+impl<D> DetachedDrop for DropEachVec<D> where D: DetachedDrop {
+    type Implementor = alloc::vec::Vec<D::Implementor>;
+
+    #[cfg(feature = "std")]
+    const RUN_ON_PANIC: bool = D::RUN_ON_PANIC;
+
+    fn drop(values: Self::Implementor) {
+        drop_each::<D, _>(values);
+    }
+
+    #[cfg(feature = "std")]
+    fn drop_during_panic(values: Self::Implementor, context: &crate::PanicContext, token: crate::HookToken) {
+        IntoIterator::into_iter(values).for_each(|value| crate::unwind::detached_drop_during_panic::<D>(value, context, token));
+    }
+}
+
+/// A `Vec` running the detached drop of `D` on each element when dropped.
+#[cfg(feature = "alloc")]
+pub struct GuardVec<T, D> where D: DetachedDrop<Implementor=T> {
+    inner: IntoInnerHelper<alloc::vec::Vec<T>, DropEachVec<D>>,
+}
+
+#[cfg(feature = "alloc")]
+impl<T, D> GuardVec<T, D> where D: DetachedDrop<Implementor=T> {
+    /// Creates an empty vector.
+    pub fn new() -> Self {
+        GuardVec::from_vec(alloc::vec::Vec::new())
+    }
+
+    /// Guards all elements of `values`.
+    pub fn from_vec(values: alloc::vec::Vec<T>) -> Self {
+        GuardVec {
+            inner: IntoInnerHelper::new(values),
+        }
+    }
+
+    /// Appends a value to the back.
+    pub fn push(&mut self, value: T) {
+        self.inner.inner_mut().push(value)
+    }
+
+    /// Removes the last value, preventing its detached drop.
+    pub fn pop(&mut self) -> Option<T> {
+        self.inner.inner_mut().pop()
+    }
+
+    /// Returns the number of values.
+    pub fn len(&self) -> usize {
+        self.inner.inner().len()
+    }
+
+    /// Returns `true` if there are no values.
+    pub fn is_empty(&self) -> bool {
+        self.inner.inner().is_empty()
+    }
+
+    /// Runs the detached drop on the values in `range` and removes them, keeping the rest.
+    ///
+    /// If a drop panics, the remaining values in the range are still dropped and removed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds, like `Vec::drain`.
+    pub fn drain_finalize<R>(&mut self, range: R) where R: core::ops::RangeBounds<usize> {
+        drop_each::<D, _>(self.inner.inner_mut().drain(range))
+    }
+
+    /// Accesses the values.
+    pub fn as_slice(&self) -> &[T] {
+        self.inner.inner()
+    }
+
+    /// Accesses the values mutably.
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        self.inner.inner_mut()
+    }
+
+    /// Moves out the values, preventing their detached drops.
+    pub fn into_inner(self) -> alloc::vec::Vec<T> {
+        self.inner.into_inner()
+    }
+
+    /// Finalizes all values in order, collecting the outputs.
+    pub fn finalize_all(self) -> alloc::vec::Vec<D::Output> where D: DetachedDropWithOutput {
+        finalize_each::<D, _>(self.into_inner())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, D> core::iter::FromIterator<T> for GuardVec<T, D> where D: DetachedDrop<Implementor=T> {
+    fn from_iter<I: IntoIterator<Item=T>>(iter: I) -> Self {
+        GuardVec::from_vec(iter.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, D> Default for GuardVec<T, D> where D: DetachedDrop<Implementor=T> {
+    fn default() -> Self {
+        GuardVec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+    use core::cell::RefCell;
+    use super::{drop_each, DropEach};
+    use crate::{IntoInnerHelper, DetachedDrop};
+
+    std::thread_local! {
+        static DROPPED: RefCell<Vec<u32>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn dropped() -> Vec<u32> {
+        DROPPED.with(|dropped| dropped.take())
+    }
+
+    enum PanicOnTwo {}
+
+    impl DetachedDrop for PanicOnTwo {
+        type Implementor = u32;
+
+        fn drop(value: Self::Implementor) {
+            if value == 2 {
+                panic!("failed to drop 2");
+            }
+            DROPPED.with(|dropped| dropped.borrow_mut().push(value));
+        }
+    }
+
+    #[test]
+    fn drop_each_in_order() {
+        drop_each::<PanicOnTwo, _>([1, 3, 4]);
+        assert_eq!(dropped(), [1, 3, 4]);
+    }
+
+    #[test]
+    fn panic_continues_dropping() {
+        let result = std::panic::catch_unwind(|| drop_each::<PanicOnTwo, _>([1, 2, 3, 4]));
+        let error = result.unwrap_err();
+        assert_eq!(error.downcast_ref::<&str>(), Some(&"failed to drop 2"));
+        assert_eq!(dropped(), [1, 3, 4]);
+    }
+
+    #[test]
+    fn array_marker() {
+        let helper = <IntoInnerHelper<_, DropEach<PanicOnTwo, 3>>>::new([1, 3, 4]);
+        core::mem::drop(helper);
+        assert_eq!(dropped(), [1, 3, 4]);
+
+        let helper = <IntoInnerHelper<_, DropEach<PanicOnTwo, 3>>>::new([1, 2, 3]);
+        assert!(std::panic::catch_unwind(move || core::mem::drop(helper)).is_err());
+        assert_eq!(dropped(), [1, 3]);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn guard_vec() {
+        use super::GuardVec;
+
+        let mut guards = <GuardVec<_, PanicOnTwo>>::new();
+        guards.push(1);
+        guards.push(2);
+        guards.push(3);
+        guards.push(4);
+        assert_eq!(guards.pop(), Some(4));
+        guards.as_mut_slice()[0] = 5;
+        assert_eq!(guards.as_slice(), [5, 2, 3]);
+        assert_eq!(guards.len(), 3);
+        assert!(std::panic::catch_unwind(move || core::mem::drop(guards)).is_err());
+        assert_eq!(dropped(), [5, 3]);
+
+        let guards = <GuardVec<_, PanicOnTwo>>::from_vec(std::vec![1, 2]);
+        assert_eq!(guards.into_inner(), [1, 2]);
+        assert!(dropped().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn drain_finalize() {
+        use super::GuardVec;
+
+        let mut guards = (1..8).collect::<GuardVec<_, PanicOnTwo>>();
+        guards.drain_finalize(2..5);
+        assert_eq!(dropped(), [3, 4, 5]);
+        assert_eq!(guards.as_slice(), [1, 2, 6, 7]);
+        guards.drain_finalize(..0);
+        assert!(dropped().is_empty());
+
+        assert!(std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| guards.drain_finalize(..3))).is_err());
+        assert_eq!(dropped(), [1, 6]);
+        assert_eq!(guards.as_slice(), [7]);
+        core::mem::drop(guards);
+        assert_eq!(dropped(), [7]);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn collect() {
+        use super::GuardVec;
+
+        let guards: GuardVec<_, PanicOnTwo> = (3..6).collect();
+        assert_eq!(guards.as_slice(), [3, 4, 5]);
+        assert!(dropped().is_empty());
+        core::mem::drop(guards);
+        assert_eq!(dropped(), [3, 4, 5]);
+
+        let guards = [1, 3].iter().copied().collect::<GuardVec<_, PanicOnTwo>>();
+        assert_eq!(guards.into_inner(), [1, 3]);
+        assert!(dropped().is_empty());
+    }
+
+    #[cfg(feature = "alloc")]
+    mod output {
+        use std::vec::Vec;
+        use super::{dropped, DROPPED};
+        use crate::{IntoInnerHelper, DetachedDrop, DetachedDropWithOutput, DropEach, GuardVec};
+
+        // Flushes a buffer, returning the number of flushed bytes.
+        enum Flush {}
+
+        impl DetachedDrop for Flush {
+            type Implementor = Vec<u8>;
+
+            fn drop(value: Self::Implementor) {
+                let _ = Self::drop_with_output(value);
+            }
+        }
+
+        impl DetachedDropWithOutput for Flush {
+            type Output = usize;
+
+            fn drop_with_output(value: Self::Implementor) -> Self::Output {
+                if value.is_empty() {
+                    panic!("nothing to flush");
+                }
+                DROPPED.with(|dropped| dropped.borrow_mut().push(value.len() as u32));
+                value.len()
+            }
+        }
+
+        #[test]
+        fn single() {
+            let helper = <IntoInnerHelper<_, Flush>>::new(std::vec![1, 2, 3]);
+            assert_eq!(helper.finalize_with_output(), 3);
+            assert_eq!(dropped(), [3]);
+        }
+
+        #[test]
+        fn guard_vec() {
+            let guards = <GuardVec<_, Flush>>::from_vec(std::vec![std::vec![1], std::vec![1, 2, 3], std::vec![1, 2]]);
+            assert_eq!(guards.finalize_all(), [1, 3, 2]);
+            assert_eq!(dropped(), [1, 3, 2]);
+        }
+
+        #[test]
+        fn array() {
+            let helper = <IntoInnerHelper<_, DropEach<Flush, 2>>>::new([std::vec![1, 2], std::vec![1]]);
+            assert_eq!(helper.finalize_all(), [2, 1]);
+            assert_eq!(dropped(), [2, 1]);
+        }
+
+        #[test]
+        fn panic_drops_remaining() {
+            let guards = <GuardVec<_, Flush>>::from_vec(std::vec![std::vec![1], Vec::new(), std::vec![1, 2]]);
+            assert!(std::panic::catch_unwind(move || guards.finalize_all()).is_err());
+            assert_eq!(dropped(), [1, 2]);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unwinding() {
+        use crate::unwind::tests::{Record, SkipOnPanic, drop_while_panicking};
+
+        assert_eq!(drop_while_panicking(<IntoInnerHelper<_, DropEach<Record, 2>>>::new([1, 2])), ["drop_panicking", "drop_panicking"]);
+        assert!(drop_while_panicking(<IntoInnerHelper<_, DropEach<SkipOnPanic, 2>>>::new([1, 2])).is_empty());
+        assert_eq!(drop_while_panicking(super::GuardVec::<_, Record>::from_vec(std::vec![1, 2])), ["drop_panicking", "drop_panicking"]);
+        assert!(drop_while_panicking(super::GuardVec::<_, SkipOnPanic>::from_vec(std::vec![1, 2])).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unwinding_context() {
+        use crate::unwind::tests::{ContextRecord, drop_while_panicking, messages};
+
+        crate::install_panic_context_hook();
+        let helper = <IntoInnerHelper<_, DropEach<ContextRecord, 2>>>::new([1, 2]);
+        assert!(drop_while_panicking(helper).is_empty());
+        assert_eq!(messages(), std::vec![Some("unwinding".into()); 2]);
+    }
+}
@@ -0,0 +1,283 @@
+use core::panic::Location;
+use core::sync::atomic::{AtomicPtr, Ordering};
+use crate::{IntoInnerHelper, DetachedDrop, InPlace};
+use crate::unwind::{detached_drop, detached_drop_in_place};
+
+/// The hook called by `DropBomb::recover`, null if none is registered.
+static RECOVER_HOOK: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+
+/// The signature of hooks called by `DropBomb::recover`.
+///
+/// The hook receives the label of the bomb and the location where it was created.
+pub type RecoverHook = fn(&'static str, &'static Location<'static>);
+
+/// Registers a hook called whenever a bomb is recovered using `DropBomb::recover`, replacing the
+/// previous one.
+///
+/// This is intended for auditing escapes from the must-finalize contract, e.g. logging them.
+/// Passing `None` unregisters the hook. The hook is global, shared by all threads.
+pub fn set_bomb_recover_hook(hook: Option<RecoverHook>) {
+    let hook = hook.map_or(core::ptr::null_mut(), |hook| hook as *mut ());
+    RECOVER_HOOK.store(hook, Ordering::Release);
+}
+
+fn recover_hook() -> Option<RecoverHook> {
+    let hook = RECOVER_HOOK.load(Ordering::Acquire);
+    if hook.is_null() {
+        None
+    } else {
+        // SAFETY: non-null pointers are only stored by `set_bomb_recover_hook` casting a
+        // `RecoverHook`.
+        Some(unsafe { core::mem::transmute::<*mut (), RecoverHook>(hook) })
+    }
+}
+
+/// A helper that must be finalized explicitly.
+///
+/// Dropping the bomb without calling `finalize`, `defuse` or `into_inner` is considered a bug. It
+/// panics in debug builds or if the `release-bomb` feature is enabled, otherwise the detached
+/// drop runs as if `finalize` was called. The panic message contains the label passed to `new`.
+///
+/// With the `std` feature the bomb doesn't panic if the thread is already panicking, to avoid
+/// aborting the process. Without `std` this can't be detected, so a bomb dropped during
+/// unwinding aborts.
+pub struct DropBomb<T, D> where D: DetachedDrop<Implementor=T> {
+    inner: IntoInnerHelper<Bomb<T>, BombDrop<D>>,
+}
+
+struct Bomb<T> {
+    value: T,
+    label: &'static str,
+    location: &'static Location<'static>,
+}
+
+struct BombDrop<D>(core::convert::Infallible, core::marker::PhantomData<D>);
+
+impl<D> DetachedDrop for BombDrop<D> where D: DetachedDrop {
+    type Implementor = Bomb<D::Implementor>;
+
+    #[cfg(feature = "std")]
+    const RUN_ON_PANIC: bool = D::RUN_ON_PANIC;
+
+    fn drop(bomb: Self::Implementor) {
+        if should_explode() {
+            panic!("DropBomb `{}` was dropped without being finalized (created at {})", bomb.label, bomb.location);
+        }
+        detached_drop::<D>(bomb.value);
+    }
+
+    #[cfg(feature = "std")]
+    fn drop_during_panic(bomb: Self::Implementor, context: &crate::PanicContext, token: crate::HookToken) {
+        // A bomb never explodes while the thread is panicking.
+        crate::unwind::detached_drop_during_panic::<D>(bomb.value, context, token);
+    }
+
+    fn drop_in_place(bomb: InPlace<'_, Self::Implementor>) {
+        if should_explode() {
+            panic!("DropBomb `{}` was dropped without being finalized (created at {})", bomb.label, bomb.location);
+        }
+        // SAFETY: the label and the location don't need to be dropped.
+        detached_drop_in_place::<D>(unsafe { bomb.project(|bomb| &mut bomb.value) });
+    }
+}
+
+fn should_explode() -> bool {
+    #[cfg(feature = "std")]
+    {
+        if std::thread::panicking() {
+            return false;
+        }
+    }
+
+    cfg!(any(debug_assertions, feature = "release-bomb"))
+}
+
+impl<T, D> DropBomb<T, D> where D: DetachedDrop<Implementor=T> {
+    /// Creates the bomb with a label used in the panic message.
+    ///
+    /// `Drop` can't report the location where the bomb was dropped, so the panic message contains
+    /// the location where it was created instead.
+    #[track_caller]
+    pub fn new(inner: T, label: &'static str) -> Self {
+        DropBomb {
+            inner: IntoInnerHelper::new(Bomb { value: inner, label, location: Location::caller(), }),
+        }
+    }
+
+    /// Returns the label of the bomb.
+    pub fn label(&self) -> &'static str {
+        self.inner.inner().label
+    }
+
+    /// Accesses the inner value.
+    pub fn inner(&self) -> &T {
+        &self.inner.inner().value
+    }
+
+    /// Accesses the inner value mutably.
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner.inner_mut().value
+    }
+
+    /// Runs the detached drop, defusing the bomb.
+    pub fn finalize(self) {
+        detached_drop::<D>(self.into_inner())
+    }
+
+    /// Defuses the bomb returning the inner value without running the detached drop.
+    pub fn defuse(self) -> T {
+        self.into_inner()
+    }
+
+    /// Defuses the bomb returning the inner value, reporting it to the hook registered by
+    /// `set_bomb_recover_hook`.
+    ///
+    /// Unlike `defuse` this marks an exceptional escape from the must-finalize contract rather
+    /// than a regular way of taking the value out.
+    pub fn recover(self) -> T {
+        let bomb = self.inner.into_inner();
+        if let Some(hook) = recover_hook() {
+            hook(bomb.label, bomb.location);
+        }
+        bomb.value
+    }
+
+    /// Moves out the inner value without running the detached drop.
+    ///
+    /// This is the same as `defuse`.
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner().value
+    }
+}
+
+impl<T, D> crate::Finalize for DropBomb<T, D> where D: DetachedDrop<Implementor=T> {
+    type Output = ();
+
+    fn finalize(self) {
+        DropBomb::finalize(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use super::DropBomb;
+    use crate::DetachedDrop;
+
+    std::thread_local! {
+        static DROPPED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    fn dropped() -> usize {
+        DROPPED.with(Cell::get)
+    }
+
+    enum CountDrop {}
+
+    impl DetachedDrop for CountDrop {
+        type Implementor = dropcheck::DropToken;
+
+        fn drop(_: Self::Implementor) {
+            DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+        }
+    }
+
+    #[test]
+    fn finalize() {
+        let check = dropcheck::DropCheck::new();
+        let bomb = <DropBomb<_, CountDrop>>::new(check.token(), "finalize-test");
+        assert_eq!(bomb.label(), "finalize-test");
+        bomb.finalize();
+        assert_eq!(dropped(), 1);
+        assert!(check.all_dropped());
+    }
+
+    #[test]
+    fn defuse() {
+        let check = dropcheck::DropCheck::new();
+        let (drop_token, drop_state) = check.pair();
+        let mut bomb = <DropBomb<_, CountDrop>>::new(drop_token, "defuse-test");
+        let _ = bomb.inner_mut();
+        let token = bomb.defuse();
+        assert_eq!(dropped(), 0);
+        assert!(drop_state.is_not_dropped());
+        core::mem::drop(token);
+    }
+
+    #[test]
+    fn recover() {
+        use core::panic::Location;
+        use super::set_bomb_recover_hook;
+
+        std::thread_local! {
+            static RECOVERED: Cell<Option<(&'static str, u32)>> = const { Cell::new(None) };
+        }
+
+        fn audit(label: &'static str, location: &'static Location<'static>) {
+            RECOVERED.with(|recovered| recovered.set(Some((label, location.line()))));
+        }
+
+        set_bomb_recover_hook(Some(audit));
+        let check = dropcheck::DropCheck::new();
+        let (drop_token, drop_state) = check.pair();
+        let line = line!() + 1;
+        let bomb = <DropBomb<_, CountDrop>>::new(drop_token, "recovered");
+        let token = bomb.recover();
+        assert_eq!(RECOVERED.with(Cell::get), Some(("recovered", line)));
+        assert_eq!(dropped(), 0);
+        assert!(drop_state.is_not_dropped());
+        core::mem::drop(token);
+
+        let bomb = <DropBomb<_, CountDrop>>::new(check.token(), "defused");
+        core::mem::drop(bomb.defuse());
+        assert_eq!(RECOVERED.with(Cell::get), Some(("recovered", line)));
+    }
+
+    #[test]
+    #[cfg(any(debug_assertions, feature = "release-bomb"))]
+    #[should_panic(expected = "DropBomb `db-connection` was dropped without being finalized")]
+    fn implicit_drop_panics() {
+        let check = dropcheck::DropCheck::new();
+        let bomb = <DropBomb<_, CountDrop>>::new(check.token(), "db-connection");
+        core::mem::drop(bomb);
+    }
+
+    #[test]
+    #[cfg(any(debug_assertions, feature = "release-bomb"))]
+    fn panic_reports_creation_location() {
+        let check = dropcheck::DropCheck::new();
+        let line = line!() + 1;
+        let bomb = <DropBomb<_, CountDrop>>::new(check.token(), "located");
+        let error = std::panic::catch_unwind(core::panic::AssertUnwindSafe(move || core::mem::drop(bomb))).unwrap_err();
+        let message = error.downcast_ref::<std::string::String>().unwrap();
+        assert!(message.ends_with(&std::format!("(created at {}:{}:20)", file!(), line)), "{}", message);
+    }
+
+    #[test]
+    #[cfg(not(any(debug_assertions, feature = "release-bomb")))]
+    fn implicit_drop_finalizes_in_release() {
+        let check = dropcheck::DropCheck::new();
+        let bomb = <DropBomb<_, CountDrop>>::new(check.token(), "db-connection");
+        core::mem::drop(bomb);
+        assert_eq!(dropped(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unwinding() {
+        use crate::unwind::tests::{Record, SkipOnPanic, drop_while_panicking};
+
+        assert_eq!(drop_while_panicking(<DropBomb<_, Record>>::new(42, "bomb")), ["drop_panicking"]);
+        assert!(drop_while_panicking(<DropBomb<_, SkipOnPanic>>::new(42, "bomb")).is_empty());
+
+        struct Finalize(Option<DropBomb<u32, SkipOnPanic>>);
+
+        impl Drop for Finalize {
+            fn drop(&mut self) {
+                self.0.take().unwrap().finalize();
+            }
+        }
+
+        assert!(drop_while_panicking(Finalize(Some(DropBomb::new(42, "bomb")))).is_empty());
+    }
+}
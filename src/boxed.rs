@@ -0,0 +1,261 @@
+use alloc::boxed::Box;
+use core::pin::Pin;
+use core::ops::{Deref, DerefMut};
+use crate::{IntoInnerHelper, DetachedDrop};
+
+/// A helper for heap-allocated, possibly unsized values.
+///
+/// The detached drop receives the whole `Box<T>`, which makes it possible to guard DSTs like
+/// `[u8]` or `dyn Trait`. Unlike `IntoInnerHelper<Box<T>, D>` this dereferences straight to `T`
+/// and can give back the allocation without moving the value.
+pub struct BoxedHelper<T, D> where T: ?Sized, D: DetachedDrop<Implementor=Box<T>> {
+    inner: IntoInnerHelper<Box<T>, D>,
+}
+
+impl<T, D> BoxedHelper<T, D> where T: ?Sized, D: DetachedDrop<Implementor=Box<T>> {
+    /// Creates the helper.
+    pub fn new(inner: Box<T>) -> Self {
+        BoxedHelper {
+            inner: IntoInnerHelper::new(inner),
+        }
+    }
+
+    /// Accesses the inner value.
+    pub fn inner(&self) -> &T {
+        self.inner.inner()
+    }
+
+    /// Accesses the inner value mutably.
+    pub fn inner_mut(&mut self) -> &mut T {
+        self.inner.inner_mut()
+    }
+
+    /// Moves out the allocation holding the inner value.
+    ///
+    /// The value itself is not moved, so this is cheap even for large values.
+    pub fn into_inner_boxed(self) -> Box<T> {
+        self.inner.into_inner()
+    }
+}
+
+impl<T, D> BoxedHelper<T, D> where D: DetachedDrop<Implementor=Box<T>> {
+    /// Moves the inner value out of the allocation.
+    pub fn into_inner(self) -> T {
+        *self.into_inner_boxed()
+    }
+}
+
+impl<D> BoxedHelper<dyn core::any::Any, D> where D: DetachedDrop<Implementor=Box<dyn core::any::Any>> {
+    /// Returns the type-erased inner value if it's of type `U`.
+    pub fn downcast_inner<U: core::any::Any>(&self) -> Option<&U> {
+        self.inner().downcast_ref()
+    }
+}
+
+impl<T, D> Deref for BoxedHelper<T, D> where T: ?Sized, D: DetachedDrop<Implementor=Box<T>> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.inner()
+    }
+}
+
+impl<T, D> DerefMut for BoxedHelper<T, D> where T: ?Sized, D: DetachedDrop<Implementor=Box<T>> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.inner_mut()
+    }
+}
+
+impl<T, D> AsRef<T> for BoxedHelper<T, D> where T: ?Sized, D: DetachedDrop<Implementor=Box<T>> {
+    fn as_ref(&self) -> &T {
+        self.inner()
+    }
+}
+
+impl<T, D> AsMut<T> for BoxedHelper<T, D> where T: ?Sized, D: DetachedDrop<Implementor=Box<T>> {
+    fn as_mut(&mut self) -> &mut T {
+        self.inner_mut()
+    }
+}
+
+impl<T, D> IntoInnerHelper<T, D> where T: Unpin, D: DetachedDrop<Implementor=T> {
+    /// Moves out the inner value of a pinned, boxed helper.
+    ///
+    /// `T: Unpin` is required because the value is moved out of the pinned allocation.
+    pub fn into_inner_pinned(self: Pin<Box<Self>>) -> T {
+        Pin::into_inner(self).into_inner()
+    }
+}
+
+impl<T, D> IntoInnerHelper<T, D> where T: 'static, D: DetachedDrop<Implementor=T> + 'static {
+    /// Packages the pending detached drop into a closure that can be stored and run later.
+    ///
+    /// Calling the closure runs the detached drop. Dropping it without calling runs the detached
+    /// drop too, so the cleanup can't be lost either way.
+    pub fn into_deferred(self) -> Box<dyn FnOnce()> {
+        Box::new(move || self.finalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+    use core::cell::Cell;
+    use super::BoxedHelper;
+    use crate::DetachedDrop;
+
+    std::thread_local! {
+        static DROPPED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    fn dropped() -> usize {
+        DROPPED.with(Cell::get)
+    }
+
+    enum CountDrop {}
+
+    impl DetachedDrop for CountDrop {
+        type Implementor = Box<[u8]>;
+
+        fn drop(_: Self::Implementor) {
+            DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+        }
+    }
+
+    enum Dummy {}
+
+    impl DetachedDrop for Dummy {
+        type Implementor = Box<dropcheck::DropToken>;
+
+        fn drop(_: Self::Implementor) {}
+    }
+
+    #[test]
+    fn drop_once() {
+        let helper = <BoxedHelper<_, CountDrop>>::new(Box::new([1u8, 2, 3]) as Box<[u8]>);
+        assert_eq!(helper.inner(), [1, 2, 3]);
+        core::mem::drop(helper);
+        assert_eq!(dropped(), 1);
+    }
+
+    #[test]
+    fn downcast_inner() {
+        use core::any::Any;
+
+        enum AnyDrop {}
+
+        impl DetachedDrop for AnyDrop {
+            type Implementor = Box<dyn Any>;
+
+            fn drop(_: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+            }
+        }
+
+        let helper = <BoxedHelper<_, AnyDrop>>::new(Box::new(42u32) as Box<dyn Any>);
+        assert_eq!(helper.downcast_inner::<u32>(), Some(&42));
+        assert_eq!(helper.downcast_inner::<i32>(), None);
+        assert_eq!(dropped(), 0);
+        core::mem::drop(helper);
+        assert_eq!(dropped(), 1);
+    }
+
+    #[test]
+    fn into_inner_boxed_keeps_allocation() {
+        let mut helper = <BoxedHelper<_, CountDrop>>::new(Box::new([1u8, 2, 3]) as Box<[u8]>);
+        helper.inner_mut()[0] = 42;
+        let address = helper.inner().as_ptr();
+        let boxed = helper.into_inner_boxed();
+        assert_eq!(boxed.as_ptr(), address);
+        assert_eq!(*boxed, [42, 2, 3]);
+        assert_eq!(dropped(), 0);
+    }
+
+    #[test]
+    fn into_inner() {
+        let check = dropcheck::DropCheck::new();
+        let (drop_token, drop_state) = check.pair();
+        let helper = <BoxedHelper<_, Dummy>>::new(Box::new(drop_token));
+        let inner = helper.into_inner();
+        assert!(drop_state.is_not_dropped());
+        core::mem::drop(inner);
+        assert!(drop_state.is_dropped());
+    }
+
+    #[test]
+    fn into_inner_pinned() {
+        use crate::IntoInnerHelper;
+
+        let check = dropcheck::DropCheck::new();
+        let (drop_token, drop_state) = check.pair();
+        let helper = Box::pin(<IntoInnerHelper<_, Dummy>>::new(Box::new(drop_token)));
+        let inner = helper.into_inner_pinned();
+        assert!(drop_state.is_not_dropped());
+        core::mem::drop(inner);
+        assert!(drop_state.is_dropped());
+    }
+
+    #[test]
+    fn into_deferred() {
+        use crate::IntoInnerHelper;
+
+        std::thread_local! {
+            static RUN: Cell<[u8; 3]> = const { Cell::new([0; 3]) };
+        }
+
+        enum Record {}
+
+        impl DetachedDrop for Record {
+            type Implementor = usize;
+
+            fn drop(index: Self::Implementor) {
+                RUN.with(|run| {
+                    let mut counts = run.get();
+                    counts[index] += 1;
+                    run.set(counts);
+                });
+            }
+        }
+
+        let mut queue = (0..3).map(|index| <IntoInnerHelper<_, Record>>::new(index).into_deferred()).collect::<alloc::vec::Vec<_>>();
+        assert_eq!(RUN.with(Cell::get), [0; 3]);
+        let last = queue.pop().unwrap();
+        queue.into_iter().for_each(|deferred| deferred());
+        assert_eq!(RUN.with(Cell::get), [1, 1, 0]);
+        core::mem::drop(last);
+        assert_eq!(RUN.with(Cell::get), [1, 1, 1]);
+    }
+
+    #[test]
+    fn same_api_as_inline() {
+        use core::ops::{Deref, DerefMut};
+        use crate::IntoInnerHelper;
+
+        enum InlineDrop {}
+
+        impl DetachedDrop for InlineDrop {
+            type Implementor = [u8; 3];
+
+            fn drop(_: Self::Implementor) {}
+        }
+
+        enum BoxedDrop {}
+
+        impl DetachedDrop for BoxedDrop {
+            type Implementor = Box<[u8; 3]>;
+
+            fn drop(_: Self::Implementor) {}
+        }
+
+        fn increment_sum<H>(helper: &mut H) -> u8 where H: AsRef<[u8; 3]> + AsMut<[u8; 3]> + Deref<Target=[u8; 3]> + DerefMut {
+            helper.as_mut()[0] += 1;
+            helper[1] += 1;
+            helper.as_ref().iter().sum::<u8>() + helper.len() as u8
+        }
+
+        let mut inline = <IntoInnerHelper<_, InlineDrop>>::new([1, 2, 3]);
+        let mut boxed = <BoxedHelper<_, BoxedDrop>>::new(Box::new([1, 2, 3]));
+        assert_eq!(increment_sum(&mut inline), 11);
+        assert_eq!(increment_sum(&mut boxed), 11);
+    }
+}
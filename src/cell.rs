@@ -0,0 +1,353 @@
+use core::cell::{RefCell, Ref, RefMut, BorrowError, BorrowMutError, UnsafeCell};
+use crate::{IntoInnerHelper, DetachedDrop, InPlace};
+use crate::unwind::{detached_drop, detached_drop_in_place};
+
+/// An interior-mutable helper backed by `RefCell`.
+///
+/// This allows mutating the inner value through a shared reference, e.g. when the guard is stored
+/// in an `Rc`. The detached drop receives the inner value unwrapped from the cell.
+pub struct RefCellHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    inner: IntoInnerHelper<RefCell<T>, CellDrop<D>>,
+}
+
+struct CellDrop<D>(core::convert::Infallible, core::marker::PhantomData<D>);
+
+impl<D> DetachedDrop for CellDrop<D> where D: DetachedDrop {
+    type Implementor = RefCell<D::Implementor>;
+
+    #[cfg(feature = "std")]
+    const RUN_ON_PANIC: bool = D::RUN_ON_PANIC;
+
+    fn drop(value: Self::Implementor) {
+        detached_drop::<D>(value.into_inner());
+    }
+
+    #[cfg(feature = "std")]
+    fn drop_during_panic(value: Self::Implementor, context: &crate::PanicContext, token: crate::HookToken) {
+        crate::unwind::detached_drop_during_panic::<D>(value.into_inner(), context, token);
+    }
+
+    fn drop_in_place(value: InPlace<'_, Self::Implementor>) {
+        // SAFETY: `RefCell` doesn't need to be dropped apart from its content.
+        detached_drop_in_place::<D>(unsafe { value.project(RefCell::get_mut) });
+    }
+}
+
+impl<T, D> RefCellHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    /// Creates the helper.
+    pub fn new(inner: T) -> Self {
+        RefCellHelper {
+            inner: IntoInnerHelper::new(RefCell::new(inner)),
+        }
+    }
+
+    /// Immutably borrows the inner value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently mutably borrowed.
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.inner.inner().borrow()
+    }
+
+    /// Mutably borrows the inner value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently borrowed.
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
+        self.inner.inner().borrow_mut()
+    }
+
+    /// Immutably borrows the inner value, returning an error if it's currently mutably borrowed.
+    pub fn try_borrow(&self) -> Result<Ref<'_, T>, BorrowError> {
+        self.inner.inner().try_borrow()
+    }
+
+    /// Mutably borrows the inner value, returning an error if it's currently borrowed.
+    pub fn try_borrow_mut(&self) -> Result<RefMut<'_, T>, BorrowMutError> {
+        self.inner.inner().try_borrow_mut()
+    }
+
+    /// Immutably borrows a part of the inner value.
+    ///
+    /// The detached drop still receives the whole value once the helper is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently mutably borrowed.
+    pub fn map_ref<U, F>(&self, f: F) -> Ref<'_, U> where F: FnOnce(&T) -> &U, U: ?Sized {
+        Ref::map(self.borrow(), f)
+    }
+
+    /// Mutably borrows a part of the inner value.
+    ///
+    /// The detached drop still receives the whole value once the helper is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently borrowed.
+    pub fn map_mut<U, F>(&self, f: F) -> RefMut<'_, U> where F: FnOnce(&mut T) -> &mut U, U: ?Sized {
+        RefMut::map(self.borrow_mut(), f)
+    }
+
+    /// Accesses the inner value mutably without runtime borrow checking.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.inner_mut().get_mut()
+    }
+
+    /// Moves out the inner value.
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner().into_inner()
+    }
+}
+
+/// An interior-mutable helper backed by `UnsafeCell`, for building custom synchronization.
+///
+/// Unlike `RefCellHelper` this doesn't track borrows, so accessing the value through a shared
+/// reference requires `unsafe` code. The helper is `!Sync` like `UnsafeCell`, so a primitive built
+/// on top of it has to opt into `Sync` itself once it upholds the requirements below. The detached
+/// drop receives the inner value unwrapped from the cell.
+///
+/// # Safety requirements
+///
+/// The pointer returned by `get` and the reference returned by `cell` follow the rules of
+/// `UnsafeCell`: while a reference derived from the pointer is alive, no other reference to the
+/// value may be created, unless all of them are shared and the value isn't mutated through them.
+/// No reference derived from the pointer may outlive the borrow of the helper it was obtained
+/// from, in particular none may exist when the helper is dropped and the detached drop runs.
+pub struct UnsafeCellHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    inner: IntoInnerHelper<UnsafeCell<T>, UnsafeCellDrop<D>>,
+}
+
+struct UnsafeCellDrop<D>(core::convert::Infallible, core::marker::PhantomData<D>);
+
+impl<D> DetachedDrop for UnsafeCellDrop<D> where D: DetachedDrop {
+    type Implementor = UnsafeCell<D::Implementor>;
+
+    #[cfg(feature = "std")]
+    const RUN_ON_PANIC: bool = D::RUN_ON_PANIC;
+
+    fn drop(value: Self::Implementor) {
+        detached_drop::<D>(value.into_inner());
+    }
+
+    #[cfg(feature = "std")]
+    fn drop_during_panic(value: Self::Implementor, context: &crate::PanicContext, token: crate::HookToken) {
+        crate::unwind::detached_drop_during_panic::<D>(value.into_inner(), context, token);
+    }
+
+    fn drop_in_place(value: InPlace<'_, Self::Implementor>) {
+        // SAFETY: `UnsafeCell` doesn't need to be dropped apart from its content.
+        detached_drop_in_place::<D>(unsafe { value.project(UnsafeCell::get_mut) });
+    }
+}
+
+impl<T, D> UnsafeCellHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    /// Creates the helper.
+    pub fn new(inner: T) -> Self {
+        UnsafeCellHelper::from_cell(UnsafeCell::new(inner))
+    }
+
+    /// Creates the helper from a cell.
+    pub fn from_cell(cell: UnsafeCell<T>) -> Self {
+        UnsafeCellHelper {
+            inner: IntoInnerHelper::new(cell),
+        }
+    }
+
+    /// Accesses the cell holding the inner value.
+    pub fn cell(&self) -> &UnsafeCell<T> {
+        self.inner.inner()
+    }
+
+    /// Returns a mutable pointer to the inner value.
+    pub fn get(&self) -> *mut T {
+        self.cell().get()
+    }
+
+    /// Accesses the inner value mutably, this is safe because the borrow is unique.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.inner_mut().get_mut()
+    }
+
+    /// Moves out the cell, preventing the detached drop.
+    pub fn into_cell(self) -> UnsafeCell<T> {
+        self.inner.into_inner()
+    }
+
+    /// Moves out the inner value, preventing the detached drop.
+    pub fn into_inner(self) -> T {
+        self.into_cell().into_inner()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+    use std::rc::Rc;
+    use core::cell::Cell;
+    use super::RefCellHelper;
+    use crate::DetachedDrop;
+
+    std::thread_local! {
+        static DROPPED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    enum SumDrop {}
+
+    impl DetachedDrop for SumDrop {
+        type Implementor = Vec<usize>;
+
+        fn drop(values: Self::Implementor) {
+            DROPPED.with(|dropped| dropped.set(dropped.get() + values.iter().sum::<usize>()));
+        }
+    }
+
+    #[test]
+    fn shared_mutation() {
+        let helper = Rc::new(<RefCellHelper<_, SumDrop>>::new(Vec::new()));
+        let other = Rc::clone(&helper);
+        helper.borrow_mut().push(1);
+        other.borrow_mut().push(2);
+        assert_eq!(*helper.borrow(), [1, 2]);
+        core::mem::drop(helper);
+        assert_eq!(DROPPED.with(Cell::get), 0);
+        core::mem::drop(other);
+        assert_eq!(DROPPED.with(Cell::get), 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn conflicting_borrow() {
+        let helper = <RefCellHelper<_, SumDrop>>::new(Vec::new());
+        let _borrow = helper.borrow();
+        helper.borrow_mut().push(1);
+    }
+
+    #[test]
+    fn try_borrow() {
+        let helper = <RefCellHelper<_, SumDrop>>::new(Vec::new());
+        helper.try_borrow_mut().unwrap().push(1);
+        {
+            let first = helper.try_borrow().unwrap();
+            let second = helper.try_borrow().unwrap();
+            assert_eq!(*first, *second);
+            assert!(helper.try_borrow_mut().is_err());
+        }
+        {
+            let mut values = helper.try_borrow_mut().unwrap();
+            assert!(helper.try_borrow().is_err());
+            assert!(helper.try_borrow_mut().is_err());
+            values.push(2);
+        }
+        assert_eq!(DROPPED.with(Cell::get), 0);
+        core::mem::drop(helper);
+        assert_eq!(DROPPED.with(Cell::get), 3);
+    }
+
+    #[test]
+    fn map_projects_field() {
+        enum PairDrop {}
+
+        impl DetachedDrop for PairDrop {
+            type Implementor = (Vec<usize>, usize);
+
+            fn drop((values, extra): Self::Implementor) {
+                DROPPED.with(|dropped| dropped.set(dropped.get() + values.iter().sum::<usize>() + extra));
+            }
+        }
+
+        let helper = <RefCellHelper<_, PairDrop>>::new((Vec::new(), 0));
+        helper.map_mut(|pair| &mut pair.0).push(20);
+        *helper.map_mut(|pair| &mut pair.1) += 22;
+        {
+            let values = helper.map_ref(|pair| &*pair.0);
+            let extra = helper.map_ref(|pair| &pair.1);
+            assert_eq!((&*values, *extra), (&[20][..], 22));
+        }
+        assert_eq!(DROPPED.with(Cell::get), 0);
+        core::mem::drop(helper);
+        assert_eq!(DROPPED.with(Cell::get), 42);
+    }
+
+    #[test]
+    fn unsafe_cell() {
+        use super::UnsafeCellHelper;
+
+        // A minimal counter built on top of the helper, sound because it's `!Sync` and never
+        // hands out references.
+        struct Counter(UnsafeCellHelper<Vec<usize>, SumDrop>);
+
+        impl Counter {
+            fn push(&self, value: usize) {
+                // SAFETY: no reference to the value exists outside of this method.
+                unsafe { (*self.0.get()).push(value) }
+            }
+        }
+
+        let counter = Counter(UnsafeCellHelper::new(Vec::new()));
+        let shared = &counter;
+        shared.push(40);
+        shared.push(2);
+        assert_eq!(DROPPED.with(Cell::get), 0);
+        core::mem::drop(counter);
+        assert_eq!(DROPPED.with(Cell::get), 42);
+
+        let mut helper = <UnsafeCellHelper<_, SumDrop>>::from_cell(core::cell::UnsafeCell::new(Vec::new()));
+        helper.get_mut().push(1);
+        assert_eq!(helper.into_cell().into_inner(), [1]);
+        assert_eq!(DROPPED.with(Cell::get), 42);
+    }
+
+    #[test]
+    fn into_inner() {
+        let mut helper = <RefCellHelper<_, SumDrop>>::new(Vec::new());
+        helper.get_mut().push(42);
+        helper.borrow_mut().push(1);
+        assert_eq!(helper.into_inner(), [42, 1]);
+        assert_eq!(DROPPED.with(Cell::get), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unwinding() {
+        use crate::unwind::tests::{Record, SkipOnPanic, drop_while_panicking};
+
+        assert_eq!(drop_while_panicking(<RefCellHelper<_, Record>>::new(42)), ["drop_panicking"]);
+        assert!(drop_while_panicking(<RefCellHelper<_, SkipOnPanic>>::new(42)).is_empty());
+        assert_eq!(drop_while_panicking(<super::UnsafeCellHelper<_, Record>>::new(42)), ["drop_panicking"]);
+        assert!(drop_while_panicking(<super::UnsafeCellHelper<_, SkipOnPanic>>::new(42)).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn drop_in_place() {
+        use crate::unwind::tests::{InPlaceRecord, called, in_place_address};
+
+        let address = {
+            let helper = <RefCellHelper<_, InPlaceRecord>>::new(42);
+            let address = &*helper.borrow() as *const u32;
+            address
+        };
+        assert_eq!(called(), ["drop_in_place"]);
+        assert_eq!(in_place_address(), Some(address));
+        let address = {
+            let helper = <super::UnsafeCellHelper<_, InPlaceRecord>>::new(42);
+            helper.get() as *const u32
+        };
+        assert_eq!(called(), ["drop_in_place"]);
+        assert_eq!(in_place_address(), Some(address));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unwinding_context() {
+        use crate::unwind::tests::{ContextRecord, drop_while_panicking, messages};
+
+        crate::install_panic_context_hook();
+        let helper = <RefCellHelper<_, (ContextRecord, ContextRecord)>>::new((1, 2));
+        assert!(drop_while_panicking(helper).is_empty());
+        assert_eq!(messages(), std::vec![Some("unwinding".into()); 2]);
+    }
+}
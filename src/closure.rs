@@ -0,0 +1,289 @@
+use core::mem::ManuallyDrop;
+use core::cell::Cell;
+
+/// A helper running a closure on drop.
+///
+/// This is handy for one-off guards that don't deserve their own marker type. Unlike
+/// `IntoInnerHelper` the drop behavior is stored in the helper, so it can capture its environment.
+///
+/// The closure doesn't have to be `'static`, so it can borrow local state. The helper is `Send` if
+/// both the value and the closure are, which makes it usable with `std::thread::scope`: guards
+/// borrowing data owned outside of the scope can be created in or moved into scoped threads.
+pub struct ClosureHelper<T, F> where F: FnOnce(T) {
+    inner: ManuallyDrop<T>,
+    drop_fn: ManuallyDrop<F>,
+}
+
+/// Creates a helper calling `drop_fn` with `inner` when dropped.
+pub fn guard<T, F>(inner: T, drop_fn: F) -> ClosureHelper<T, F> where F: FnOnce(T) {
+    ClosureHelper::new(inner, drop_fn)
+}
+
+/// A closure helper with the drop behavior chosen at runtime.
+///
+/// Boxing the closure costs an allocation but all helpers have the same type regardless of the
+/// closure, so they can be stored together or selected e.g. based on configuration.
+#[cfg(feature = "alloc")]
+pub type DynHelper<'a, T> = ClosureHelper<T, alloc::boxed::Box<dyn FnOnce(T) + 'a>>;
+
+/// Creates a helper calling `drop_fn` with `inner` when dropped, boxing the closure.
+#[cfg(feature = "alloc")]
+pub fn dyn_guard<'a, T, F>(inner: T, drop_fn: F) -> DynHelper<'a, T> where F: FnOnce(T) + 'a {
+    ClosureHelper::new(inner, alloc::boxed::Box::new(drop_fn))
+}
+
+/// A closure helper storing a plain function pointer.
+///
+/// Like `DynHelper` the finalizer can be chosen at runtime among several functions, but it doesn't
+/// need an allocation, so it's usable in `no_std` environments.
+pub type FnPtrHelper<T> = ClosureHelper<T, fn(T)>;
+
+/// Creates a helper calling the function `drop_fn` with `inner` when dropped.
+pub fn fn_guard<T>(inner: T, drop_fn: fn(T)) -> FnPtrHelper<T> {
+    ClosureHelper::new(inner, drop_fn)
+}
+
+impl<T, F> ClosureHelper<T, F> where F: FnOnce(T) {
+    /// Creates the helper.
+    pub fn new(inner: T, drop_fn: F) -> Self {
+        ClosureHelper {
+            inner: ManuallyDrop::new(inner),
+            drop_fn: ManuallyDrop::new(drop_fn),
+        }
+    }
+
+    /// Accesses the inner value.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Accesses the inner value mutably.
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Moves out the inner value, cancelling the closure.
+    pub fn into_inner(self) -> T {
+        self.into_parts().0
+    }
+
+    /// Moves out both the inner value and the closure without calling it.
+    pub fn into_parts(self) -> (T, F) {
+        unsafe {
+            let inner = core::ptr::read(&*self.inner);
+            let drop_fn = core::ptr::read(&*self.drop_fn);
+            core::mem::forget(self);
+            (inner, drop_fn)
+        }
+    }
+}
+
+impl<T, F> Drop for ClosureHelper<T, F> where F: FnOnce(T) {
+    fn drop(&mut self) {
+        unsafe {
+            let inner = core::ptr::read(&*self.inner);
+            let drop_fn = core::ptr::read(&*self.drop_fn);
+            drop_fn(inner);
+        }
+    }
+}
+
+/// A flag cancelling closures of guards created by `guard_scope!` in `cancel as` mode.
+///
+/// Cancelling through a shared reference allows the guard closure to borrow the handle.
+#[derive(Debug, Default)]
+pub struct CancelHandle {
+    cancelled: Cell<bool>,
+}
+
+impl CancelHandle {
+    /// Creates a handle that's not cancelled.
+    pub fn new() -> Self {
+        CancelHandle::default()
+    }
+
+    /// Cancels the guard, its closure won't be called.
+    pub fn cancel(&self) {
+        self.cancelled.set(true)
+    }
+
+    /// Returns `true` if `cancel` was called.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.get()
+    }
+}
+
+/// Converts the helper into an equivalent `ScopeGuard`.
+#[cfg(feature = "scopeguard")]
+impl<T, F> From<ClosureHelper<T, F>> for ::scopeguard::ScopeGuard<T, F, ::scopeguard::Always> where F: FnOnce(T) {
+    fn from(value: ClosureHelper<T, F>) -> Self {
+        let (inner, drop_fn) = value.into_parts();
+        ::scopeguard::guard(inner, drop_fn)
+    }
+}
+
+/// Wraps the `ScopeGuard` in a helper.
+///
+/// `ScopeGuard` doesn't provide access to its closure, so the guard itself becomes the inner
+/// value and dropping the helper drops the guard. To cancel it, call `ScopeGuard::into_inner` on
+/// the value returned by `into_inner`.
+#[cfg(feature = "scopeguard")]
+impl<T, F, S> From<::scopeguard::ScopeGuard<T, F, S>> for ClosureHelper<::scopeguard::ScopeGuard<T, F, S>, fn(::scopeguard::ScopeGuard<T, F, S>)> where F: FnOnce(T), S: ::scopeguard::Strategy {
+    fn from(value: ::scopeguard::ScopeGuard<T, F, S>) -> Self {
+        ClosureHelper::new(value, core::mem::drop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use super::guard;
+
+    #[test]
+    fn drop_calls_closure() {
+        let called = Cell::new(0);
+        let helper = guard(21, |value| called.set(value * 2));
+        assert_eq!(*helper.inner(), 21);
+        core::mem::drop(helper);
+        assert_eq!(called.get(), 42);
+    }
+
+    #[test]
+    fn into_inner_cancels() {
+        let check = dropcheck::DropCheck::new();
+        let (drop_token, drop_state) = check.pair();
+        let called = Cell::new(false);
+        let mut helper = guard(drop_token, |_| called.set(true));
+        let _ = helper.inner_mut();
+        let token = helper.into_inner();
+        assert!(!called.get());
+        assert!(drop_state.is_not_dropped());
+        core::mem::drop(token);
+        assert!(drop_state.is_dropped());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn dyn_helper() {
+        use std::vec::Vec;
+        use core::cell::RefCell;
+        use crate::{dyn_guard, DynHelper};
+
+        let log = RefCell::new(Vec::new());
+        let helpers: Vec<DynHelper<'_, u32>> = ["double", "negate", "double"].iter().enumerate().map(|(i, action)| {
+            match *action {
+                "double" => dyn_guard(i as u32, |value| log.borrow_mut().push(i64::from(value) * 2)),
+                _ => dyn_guard(i as u32, |value| log.borrow_mut().push(-i64::from(value))),
+            }
+        }).collect();
+        let mut helpers = helpers.into_iter();
+        assert_eq!(helpers.next().map(|helper| helper.into_inner()), Some(0));
+        core::mem::drop(helpers);
+        assert_eq!(log.into_inner(), [-1, 4]);
+    }
+
+    #[test]
+    fn scoped_threads() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Mutex;
+        use std::vec::Vec;
+        use crate::ClosureHelper;
+
+        let finalized = AtomicUsize::new(0);
+        let log = Mutex::new(Vec::new());
+
+        let moved = guard(0, |id| {
+            finalized.fetch_add(1, Ordering::Relaxed);
+            log.lock().unwrap().push(id);
+        });
+        std::thread::scope(|scope| {
+            scope.spawn(move || core::mem::drop(moved));
+            for id in 1..4 {
+                let (finalized, log) = (&finalized, &log);
+                scope.spawn(move || {
+                    let _guard = guard(id, |id| {
+                        finalized.fetch_add(1, Ordering::Relaxed);
+                        log.lock().unwrap().push(id);
+                    });
+                });
+            }
+            let cancelled = ClosureHelper::new(42, |id| log.lock().unwrap().push(id));
+            scope.spawn(move || assert_eq!(cancelled.into_inner(), 42));
+        });
+
+        assert_eq!(finalized.into_inner(), 4);
+        let mut log = log.into_inner().unwrap();
+        log.sort_unstable();
+        assert_eq!(log, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn fn_ptr_helper() {
+        use crate::{fn_guard, FnPtrHelper};
+
+        std::thread_local! {
+            static LAST: Cell<i64> = const { Cell::new(0) };
+        }
+
+        fn double(value: i32) {
+            LAST.with(|last| last.set(i64::from(value) * 2));
+        }
+
+        fn negate(value: i32) {
+            LAST.with(|last| last.set(-i64::from(value)));
+        }
+
+        fn select(negative: bool) -> fn(i32) {
+            if negative {
+                negate
+            } else {
+                double
+            }
+        }
+
+        let helpers: [FnPtrHelper<i32>; 2] = [fn_guard(21, select(false)), fn_guard(42, select(true))];
+        let [doubled, negated] = helpers;
+        core::mem::drop(doubled);
+        assert_eq!(LAST.with(Cell::get), 42);
+        assert_eq!(negated.into_inner(), 42);
+        assert_eq!(LAST.with(Cell::get), 42);
+        core::mem::drop(fn_guard(42, select(true)));
+        assert_eq!(LAST.with(Cell::get), -42);
+        assert_eq!(core::mem::size_of::<FnPtrHelper<i32>>(), core::mem::size_of::<(i32, fn(i32))>());
+    }
+
+    #[cfg(feature = "scopeguard")]
+    mod scopeguard {
+        use core::cell::Cell;
+        use ::scopeguard::ScopeGuard;
+        use crate::{guard, ClosureHelper};
+
+        #[test]
+        fn into_scopeguard() {
+            let called = Cell::new(0);
+            let scope_guard = ScopeGuard::from(guard(42, |value| called.set(value)));
+            assert_eq!(*scope_guard, 42);
+            core::mem::drop(scope_guard);
+            assert_eq!(called.get(), 42);
+        }
+
+        #[test]
+        fn round_trip() {
+            let called = Cell::new(0);
+            let scope_guard = ScopeGuard::from(guard(42, |value| called.set(called.get() + value)));
+            let helper = ClosureHelper::from(scope_guard);
+            assert_eq!(**helper.inner(), 42);
+            core::mem::drop(helper);
+            assert_eq!(called.get(), 42);
+        }
+
+        #[test]
+        fn round_trip_cancel() {
+            let called = Cell::new(false);
+            let scope_guard = ScopeGuard::from(guard(42, |_| called.set(true)));
+            let helper = ClosureHelper::from(scope_guard);
+            assert_eq!(ScopeGuard::into_inner(helper.into_inner()), 42);
+            assert!(!called.get());
+        }
+    }
+}
@@ -0,0 +1,279 @@
+//! Helper methods specific to guards over `alloc` collections.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::collections::{btree_map, BTreeMap};
+use crate::{IntoInnerHelper, DetachedDrop};
+
+impl<U, D> IntoInnerHelper<Vec<U>, D> where D: DetachedDrop<Implementor=Vec<U>> {
+    /// Moves out the contents as a boxed slice, preventing the detached drop.
+    ///
+    /// The allocation is reused, it only gets shrunk if the capacity exceeds the length.
+    pub fn into_boxed_slice(self) -> Box<[U]> {
+        self.into_inner().into_boxed_slice()
+    }
+
+    /// Retains only the elements satisfying `f`, e.g. to prune the collection before the detached
+    /// drop runs.
+    pub fn retain<F>(&mut self, f: F) where F: FnMut(&U) -> bool {
+        self.inner_mut().retain(f)
+    }
+
+    /// Returns the number of elements.
+    pub fn len(&self) -> usize {
+        self.inner().len()
+    }
+
+    /// Returns `true` if there are no elements.
+    pub fn is_empty(&self) -> bool {
+        self.inner().is_empty()
+    }
+
+    /// Returns the number of elements the buffer can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.inner().capacity()
+    }
+
+    /// Reserves capacity for at least `additional` more elements.
+    pub fn reserve(&mut self, additional: usize) {
+        self.inner_mut().reserve(additional)
+    }
+
+    /// Removes all elements, keeping the capacity. The detached drop later receives the empty buffer.
+    pub fn clear(&mut self) {
+        self.inner_mut().clear()
+    }
+}
+
+impl<D> IntoInnerHelper<String, D> where D: DetachedDrop<Implementor=String> {
+    /// Returns the length in bytes.
+    pub fn len(&self) -> usize {
+        self.inner().len()
+    }
+
+    /// Returns `true` if the string is empty.
+    pub fn is_empty(&self) -> bool {
+        self.inner().is_empty()
+    }
+
+    /// Returns the number of bytes the buffer can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.inner().capacity()
+    }
+
+    /// Reserves capacity for at least `additional` more bytes.
+    pub fn reserve(&mut self, additional: usize) {
+        self.inner_mut().reserve(additional)
+    }
+
+    /// Truncates the string to zero length, keeping the capacity. The detached drop later receives
+    /// the empty string.
+    pub fn clear(&mut self) {
+        self.inner_mut().clear()
+    }
+}
+
+impl<K, V, D> IntoInnerHelper<BTreeMap<K, V>, D> where K: Ord, D: DetachedDrop<Implementor=BTreeMap<K, V>> {
+    /// Gets the entry for `key` in the map, for in-place manipulation.
+    pub fn entry(&mut self, key: K) -> btree_map::Entry<'_, K, V> {
+        self.inner_mut().entry(key)
+    }
+
+    /// Retains only the entries satisfying `f`, e.g. to evict stale entries before the detached
+    /// drop runs.
+    pub fn retain<F>(&mut self, f: F) where F: FnMut(&K, &mut V) -> bool {
+        self.inner_mut().retain(f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S, D> IntoInnerHelper<std::collections::HashMap<K, V, S>, D> where K: Eq + core::hash::Hash, S: core::hash::BuildHasher, D: DetachedDrop<Implementor=std::collections::HashMap<K, V, S>> {
+    /// Gets the entry for `key` in the map, for in-place manipulation.
+    pub fn entry(&mut self, key: K) -> std::collections::hash_map::Entry<'_, K, V> {
+        self.inner_mut().entry(key)
+    }
+
+    /// Retains only the entries satisfying `f`, e.g. to evict stale entries before the detached
+    /// drop runs.
+    pub fn retain<F>(&mut self, f: F) where F: FnMut(&K, &mut V) -> bool {
+        self.inner_mut().retain(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::cell::Cell;
+    use crate::{IntoInnerHelper, DetachedDrop};
+
+    std::thread_local! {
+        static DROPPED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    enum CountDrop {}
+
+    impl DetachedDrop for CountDrop {
+        type Implementor = Vec<u8>;
+
+        fn drop(_: Self::Implementor) {
+            DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+        }
+    }
+
+    #[test]
+    fn into_boxed_slice() {
+        let mut helper = <IntoInnerHelper<_, CountDrop>>::new(Vec::with_capacity(3));
+        helper.extend_from_slice(&[1, 2, 3]);
+        let address = helper.as_ptr();
+        let slice = helper.into_boxed_slice();
+        assert_eq!(*slice, [1, 2, 3]);
+        assert_eq!(slice.as_ptr(), address);
+        assert_eq!(DROPPED.with(Cell::get), 0);
+    }
+
+    #[test]
+    fn buffer_methods() {
+        let mut helper = <IntoInnerHelper<_, CountDrop>>::new(Vec::new());
+        assert!(helper.is_empty());
+        helper.reserve(16);
+        assert!(helper.capacity() >= 16);
+        helper.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(helper.len(), 3);
+        assert!(!helper.is_empty());
+        let capacity = helper.capacity();
+        helper.clear();
+        assert!(helper.is_empty());
+        assert_eq!(helper.capacity(), capacity);
+        assert_eq!(DROPPED.with(Cell::get), 0);
+        core::mem::drop(helper);
+        assert_eq!(DROPPED.with(Cell::get), 1);
+
+        enum Flush {}
+
+        impl DetachedDrop for Flush {
+            type Implementor = alloc::string::String;
+
+            fn drop(_: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+            }
+        }
+
+        let mut helper = <IntoInnerHelper<_, Flush>>::new(alloc::string::String::new());
+        helper.reserve(8);
+        assert!(helper.capacity() >= 8);
+        helper.push_str("hello");
+        assert_eq!((helper.len(), helper.is_empty()), (5, false));
+        helper.clear();
+        assert!(helper.is_empty());
+        core::mem::drop(helper);
+        assert_eq!(DROPPED.with(Cell::get), 2);
+    }
+
+    #[test]
+    fn vec_retain() {
+        std::thread_local! {
+            static PERSISTED: core::cell::RefCell<Vec<u8>> = const { core::cell::RefCell::new(Vec::new()) };
+        }
+
+        enum Persist {}
+
+        impl DetachedDrop for Persist {
+            type Implementor = Vec<u8>;
+
+            fn drop(values: Self::Implementor) {
+                PERSISTED.with(|persisted| *persisted.borrow_mut() = values);
+            }
+        }
+
+        let mut helper = <IntoInnerHelper<_, Persist>>::new((1..=6).collect());
+        helper.retain(|value| value % 2 == 0);
+        assert!(PERSISTED.with(|persisted| persisted.borrow().is_empty()));
+        core::mem::drop(helper);
+        PERSISTED.with(|persisted| assert_eq!(*persisted.borrow(), [2, 4, 6]));
+    }
+
+    #[test]
+    fn btree_map_retain() {
+        use alloc::collections::BTreeMap;
+
+        std::thread_local! {
+            static PERSISTED: Cell<Option<usize>> = const { Cell::new(None) };
+        }
+
+        enum Persist {}
+
+        impl DetachedDrop for Persist {
+            type Implementor = BTreeMap<u32, &'static str>;
+
+            fn drop(cache: Self::Implementor) {
+                assert!(cache.keys().all(|age| *age < 10));
+                PERSISTED.with(|persisted| persisted.set(Some(cache.len())));
+            }
+        }
+
+        let mut cache = <IntoInnerHelper<_, Persist>>::new(BTreeMap::from([(1, "fresh"), (20, "stale"), (5, "fresh"), (30, "stale")]));
+        cache.retain(|age, _| *age < 10);
+        assert_eq!(PERSISTED.with(Cell::get), None);
+        core::mem::drop(cache);
+        assert_eq!(PERSISTED.with(Cell::get), Some(2));
+    }
+
+    #[test]
+    fn btree_map_entry() {
+        use alloc::collections::BTreeMap;
+        use core::cell::RefCell;
+
+        std::thread_local! {
+            static PERSISTED: RefCell<Vec<(&'static str, u32)>> = const { RefCell::new(Vec::new()) };
+        }
+
+        enum Persist {}
+
+        impl DetachedDrop for Persist {
+            type Implementor = BTreeMap<&'static str, u32>;
+
+            fn drop(cache: Self::Implementor) {
+                PERSISTED.with(|persisted| persisted.borrow_mut().extend(cache));
+            }
+        }
+
+        let mut cache = <IntoInnerHelper<_, Persist>>::new(BTreeMap::new());
+        for word in ["b", "a", "b", "c", "b"] {
+            *cache.entry(word).or_insert(0) += 1;
+        }
+        cache.entry("a").and_modify(|count| *count *= 10);
+        assert!(PERSISTED.with(|persisted| persisted.borrow().is_empty()));
+        core::mem::drop(cache);
+        PERSISTED.with(|persisted| assert_eq!(*persisted.borrow(), [("a", 10), ("b", 3), ("c", 1)]));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn hash_map_entry() {
+        use std::collections::HashMap;
+
+        std::thread_local! {
+            static PERSISTED: Cell<Option<u32>> = const { Cell::new(None) };
+        }
+
+        enum Persist {}
+
+        impl DetachedDrop for Persist {
+            type Implementor = HashMap<&'static str, u32>;
+
+            fn drop(cache: Self::Implementor) {
+                PERSISTED.with(|persisted| persisted.set(Some(cache.values().sum())));
+            }
+        }
+
+        let mut cache = <IntoInnerHelper<_, Persist>>::new(HashMap::new());
+        *cache.entry("a").or_default() += 40;
+        *cache.entry("b").or_insert(1) += 1;
+        *cache.entry("stale").or_default() += 100;
+        cache.retain(|key, _| *key != "stale");
+        assert_eq!(PERSISTED.with(Cell::get), None);
+        core::mem::drop(cache);
+        assert_eq!(PERSISTED.with(Cell::get), Some(42));
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{IntoInnerHelper, DetachedDrop, InPlace};
+use crate::unwind::{detached_drop, detached_drop_in_place};
+
+/// A helper running the detached drop only if the inner value was mutably accessed.
+///
+/// Every call to `inner_mut` marks the helper dirty. Dropping a clean helper just drops the inner
+/// value, skipping a cleanup that would be a no-op anyway, e.g. writing back an unchanged cache.
+/// Note that the helper can't tell whether the value was actually changed, only whether it could
+/// have been.
+pub struct DirtyHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    inner: IntoInnerHelper<Dirty<T>, DirtyDrop<D>>,
+}
+
+struct Dirty<T> {
+    value: T,
+    dirty: bool,
+}
+
+struct DirtyDrop<D>(core::convert::Infallible, core::marker::PhantomData<D>);
+
+impl<D> DetachedDrop for DirtyDrop<D> where D: DetachedDrop {
+    type Implementor = Dirty<D::Implementor>;
+
+    #[cfg(feature = "std")]
+    const RUN_ON_PANIC: bool = D::RUN_ON_PANIC;
+
+    fn drop(value: Self::Implementor) {
+        if value.dirty {
+            detached_drop::<D>(value.value);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn drop_during_panic(value: Self::Implementor, context: &crate::PanicContext, token: crate::HookToken) {
+        if value.dirty {
+            crate::unwind::detached_drop_during_panic::<D>(value.value, context, token);
+        }
+    }
+
+    fn drop_in_place(value: InPlace<'_, Self::Implementor>) {
+        if value.dirty {
+            // SAFETY: the flag doesn't need to be dropped.
+            detached_drop_in_place::<D>(unsafe { value.project(|value| &mut value.value) });
+        }
+    }
+}
+
+impl<T, D> DirtyHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    /// Creates a clean helper.
+    pub fn new(inner: T) -> Self {
+        DirtyHelper {
+            inner: IntoInnerHelper::new(Dirty { value: inner, dirty: false, }),
+        }
+    }
+
+    /// Accesses the inner value without marking the helper dirty.
+    pub fn inner(&self) -> &T {
+        &self.inner.inner().value
+    }
+
+    /// Accesses the inner value mutably, marking the helper dirty.
+    pub fn inner_mut(&mut self) -> &mut T {
+        let inner = self.inner.inner_mut();
+        inner.dirty = true;
+        &mut inner.value
+    }
+
+    /// Returns `true` if the detached drop will run when the helper is dropped.
+    pub fn is_dirty(&self) -> bool {
+        self.inner.inner().dirty
+    }
+
+    /// Marks the helper clean, e.g. after the changes were saved manually.
+    pub fn mark_clean(&mut self) {
+        self.inner.inner_mut().dirty = false;
+    }
+
+    /// Moves out the inner value, preventing the detached drop even if the helper is dirty.
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner().value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use super::DirtyHelper;
+    use crate::DetachedDrop;
+
+    std::thread_local! {
+        static WRITTEN_BACK: Cell<Option<u32>> = const { Cell::new(None) };
+    }
+
+    fn written_back() -> Option<u32> {
+        WRITTEN_BACK.with(Cell::take)
+    }
+
+    enum WriteBack {}
+
+    impl DetachedDrop for WriteBack {
+        type Implementor = u32;
+
+        fn drop(value: Self::Implementor) {
+            WRITTEN_BACK.with(|written| written.set(Some(value)));
+        }
+    }
+
+    #[test]
+    fn mutated() {
+        let mut helper = <DirtyHelper<_, WriteBack>>::new(41);
+        assert!(!helper.is_dirty());
+        *helper.inner_mut() += 1;
+        assert!(helper.is_dirty());
+        core::mem::drop(helper);
+        assert_eq!(written_back(), Some(42));
+    }
+
+    #[test]
+    fn untouched() {
+        let helper = <DirtyHelper<_, WriteBack>>::new(42);
+        assert_eq!(*helper.inner(), 42);
+        core::mem::drop(helper);
+        assert_eq!(written_back(), None);
+
+        let mut helper = <DirtyHelper<_, WriteBack>>::new(42);
+        *helper.inner_mut() += 1;
+        helper.mark_clean();
+        core::mem::drop(helper);
+        assert_eq!(written_back(), None);
+    }
+
+    #[test]
+    fn into_inner() {
+        let mut helper = <DirtyHelper<_, WriteBack>>::new(41);
+        *helper.inner_mut() += 1;
+        assert_eq!(helper.into_inner(), 42);
+        assert_eq!(written_back(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unwinding() {
+        use crate::unwind::tests::{Record, SkipOnPanic, drop_while_panicking};
+
+        let mut helper = <DirtyHelper<_, Record>>::new(42);
+        helper.inner_mut();
+        assert_eq!(drop_while_panicking(helper), ["drop_panicking"]);
+        let mut helper = <DirtyHelper<_, SkipOnPanic>>::new(42);
+        helper.inner_mut();
+        assert!(drop_while_panicking(helper).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn drop_in_place() {
+        use crate::unwind::tests::{InPlaceRecord, called, in_place_address};
+
+        let address = {
+            let mut helper = <DirtyHelper<_, InPlaceRecord>>::new(42);
+            helper.inner_mut() as *const u32
+        };
+        assert_eq!(called(), ["drop_in_place"]);
+        assert_eq!(in_place_address(), Some(address));
+    }
+}
@@ -0,0 +1,450 @@
+use crate::{IntoInnerHelper, DetachedDrop, InPlace};
+use crate::unwind::{detached_drop, detached_drop_in_place};
+
+/// A helper whose detached drop can be switched off and on at runtime.
+///
+/// The helper starts armed. Dropping an armed helper runs the detached drop, dropping a disarmed
+/// one just drops the inner value.
+pub struct DisarmableHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    inner: IntoInnerHelper<Armed<T>, ArmedDrop<D>>,
+}
+
+struct Armed<T> {
+    value: T,
+    armed: bool,
+}
+
+struct ArmedDrop<D>(core::convert::Infallible, core::marker::PhantomData<D>);
+
+impl<D> DetachedDrop for ArmedDrop<D> where D: DetachedDrop {
+    type Implementor = Armed<D::Implementor>;
+
+    #[cfg(feature = "std")]
+    const RUN_ON_PANIC: bool = D::RUN_ON_PANIC;
+
+    fn drop(value: Self::Implementor) {
+        if value.armed {
+            detached_drop::<D>(value.value);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn drop_during_panic(value: Self::Implementor, context: &crate::PanicContext, token: crate::HookToken) {
+        if value.armed {
+            crate::unwind::detached_drop_during_panic::<D>(value.value, context, token);
+        }
+    }
+
+    fn drop_in_place(value: InPlace<'_, Self::Implementor>) {
+        if value.armed {
+            // SAFETY: the flag doesn't need to be dropped.
+            detached_drop_in_place::<D>(unsafe { value.project(|value| &mut value.value) });
+        }
+    }
+}
+
+impl<T, D> DisarmableHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    /// Creates an armed helper.
+    pub fn new(inner: T) -> Self {
+        DisarmableHelper {
+            inner: IntoInnerHelper::new(Armed { value: inner, armed: true, }),
+        }
+    }
+
+    /// Accesses the inner value.
+    pub fn inner(&self) -> &T {
+        &self.inner.inner().value
+    }
+
+    /// Accesses the inner value mutably.
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner.inner_mut().value
+    }
+
+    /// Returns `true` if the detached drop will run when the helper is dropped.
+    pub fn is_armed(&self) -> bool {
+        self.inner.inner().armed
+    }
+
+    /// Enables the detached drop.
+    pub fn arm(&mut self) {
+        self.inner.inner_mut().armed = true;
+    }
+
+    /// Disables the detached drop.
+    pub fn disarm(&mut self) {
+        self.inner.inner_mut().armed = false;
+    }
+
+    /// Borrows the inner value together with a handle controlling the armed state.
+    ///
+    /// This allows inspecting the value and deciding whether to cancel the cleanup based on it.
+    pub fn split_borrow(&mut self) -> (&T, DisarmHandle<'_>) {
+        let Armed { value, armed, } = self.inner.inner_mut();
+        (value, DisarmHandle { armed, })
+    }
+
+    /// Disarms the helper until the returned guard is dropped.
+    ///
+    /// Dropping the guard, including during unwinding, restores the armed state the helper had
+    /// before freezing, so a cleanup cancelled earlier isn't re-armed.
+    pub fn freeze(&mut self) -> FreezeGuard<'_, T> {
+        let Armed { value, armed, } = self.inner.inner_mut();
+        let was_armed = core::mem::replace(armed, false);
+        FreezeGuard { value, armed, was_armed, }
+    }
+
+    /// Replaces the inner value with `value` and re-arms the helper.
+    ///
+    /// The previous value is returned without running the detached drop on it, so the helper
+    /// behaves as if it was freshly created.
+    pub fn reset(&mut self, value: T) -> T {
+        let inner = self.inner.inner_mut();
+        inner.armed = true;
+        core::mem::replace(&mut inner.value, value)
+    }
+
+    /// Moves out the inner value, preventing the detached drop regardless of the armed state.
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner().value
+    }
+
+    /// Changes the drop marker, keeping the armed state.
+    ///
+    /// A disarmed helper stays disarmed, so switching the cleanup semantics can't accidentally
+    /// re-arm a cancelled cleanup.
+    pub fn replace_marker_preserving_disarm<D2>(self) -> DisarmableHelper<T, D2> where D2: DetachedDrop<Implementor=T> {
+        let Armed { value, armed, } = self.inner.into_inner();
+        DisarmableHelper {
+            inner: IntoInnerHelper::new(Armed { value, armed, }),
+        }
+    }
+
+    /// Clones the inner value into a new, armed helper regardless of the armed state of `self`.
+    pub fn clone_armed(&self) -> Self where T: Clone {
+        DisarmableHelper::new(self.inner().clone())
+    }
+
+    /// Runs the detached drop if armed, otherwise returns the inner value.
+    ///
+    /// In other words, the caller gets the value only if the cleanup was cancelled.
+    pub fn take_or_run(self) -> Option<T> {
+        let inner = self.inner.into_inner();
+        if inner.armed {
+            detached_drop::<D>(inner.value);
+            None
+        } else {
+            Some(inner.value)
+        }
+    }
+}
+
+/// Clones the inner value, the clone inherits the armed state.
+///
+/// Cloning a disarmed helper never silently re-arms the cleanup. Use `clone_armed` to get an
+/// armed clone explicitly.
+impl<T, D> Clone for DisarmableHelper<T, D> where T: Clone, D: DetachedDrop<Implementor=T> {
+    fn clone(&self) -> Self {
+        let mut clone = self.clone_armed();
+        if !self.is_armed() {
+            clone.disarm();
+        }
+        clone
+    }
+}
+
+/// A handle controlling the armed state of `DisarmableHelper`, returned by `split_borrow`.
+pub struct DisarmHandle<'a> {
+    armed: &'a mut bool,
+}
+
+impl DisarmHandle<'_> {
+    /// Returns `true` if the detached drop will run when the helper is dropped.
+    pub fn is_armed(&self) -> bool {
+        *self.armed
+    }
+
+    /// Enables the detached drop.
+    pub fn arm(&mut self) {
+        *self.armed = true;
+    }
+
+    /// Disables the detached drop.
+    pub fn disarm(&mut self) {
+        *self.armed = false;
+    }
+}
+
+/// A guard keeping `DisarmableHelper` disarmed, returned by `freeze`.
+pub struct FreezeGuard<'a, T> {
+    value: &'a mut T,
+    armed: &'a mut bool,
+    was_armed: bool,
+}
+
+impl<T> FreezeGuard<'_, T> {
+    /// Accesses the inner value of the frozen helper.
+    pub fn inner(&self) -> &T {
+        self.value
+    }
+
+    /// Accesses the inner value of the frozen helper mutably.
+    pub fn inner_mut(&mut self) -> &mut T {
+        self.value
+    }
+
+    /// Restores the armed state of the helper.
+    ///
+    /// This is the same as dropping the guard, but explicit.
+    pub fn thaw(self) {}
+}
+
+impl<T> Drop for FreezeGuard<'_, T> {
+    fn drop(&mut self) {
+        *self.armed = self.was_armed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use super::DisarmableHelper;
+    use crate::DetachedDrop;
+
+    std::thread_local! {
+        static DROPPED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    fn dropped() -> usize {
+        DROPPED.with(Cell::get)
+    }
+
+    enum CountDrop {}
+
+    impl DetachedDrop for CountDrop {
+        type Implementor = dropcheck::DropToken;
+
+        fn drop(_: Self::Implementor) {
+            DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+        }
+    }
+
+    #[test]
+    fn armed_drop() {
+        let check = dropcheck::DropCheck::new();
+        let helper = <DisarmableHelper<_, CountDrop>>::new(check.token());
+        assert!(helper.is_armed());
+        core::mem::drop(helper);
+        assert_eq!(dropped(), 1);
+        assert!(check.all_dropped());
+    }
+
+    #[test]
+    fn disarmed_drop() {
+        let check = dropcheck::DropCheck::new();
+        let mut helper = <DisarmableHelper<_, CountDrop>>::new(check.token());
+        helper.disarm();
+        assert!(!helper.is_armed());
+        core::mem::drop(helper);
+        assert_eq!(dropped(), 0);
+        assert!(check.all_dropped());
+    }
+
+    #[test]
+    fn rearmed_drop() {
+        let check = dropcheck::DropCheck::new();
+        let mut helper = <DisarmableHelper<_, CountDrop>>::new(check.token());
+        helper.disarm();
+        helper.arm();
+        core::mem::drop(helper);
+        assert_eq!(dropped(), 1);
+    }
+
+    #[test]
+    fn replace_marker_preserving_disarm() {
+        enum Other {}
+
+        impl DetachedDrop for Other {
+            type Implementor = dropcheck::DropToken;
+
+            fn drop(_: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.set(dropped.get() + 10));
+            }
+        }
+
+        let check = dropcheck::DropCheck::new();
+        let mut helper = <DisarmableHelper<_, CountDrop>>::new(check.token());
+        helper.disarm();
+        let helper = helper.replace_marker_preserving_disarm::<Other>();
+        assert!(!helper.is_armed());
+        core::mem::drop(helper);
+        assert_eq!(dropped(), 0);
+
+        let helper = <DisarmableHelper<_, CountDrop>>::new(check.token()).replace_marker_preserving_disarm::<Other>();
+        assert!(helper.is_armed());
+        core::mem::drop(helper);
+        assert_eq!(dropped(), 10);
+        assert!(check.all_dropped());
+    }
+
+    #[test]
+    fn clone_inherits_armed_state() {
+        #[derive(Clone)]
+        struct Token;
+
+        enum TokenDrop {}
+
+        impl DetachedDrop for TokenDrop {
+            type Implementor = Token;
+
+            fn drop(_: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+            }
+        }
+
+        let mut helper = <DisarmableHelper<_, TokenDrop>>::new(Token);
+        let armed = helper.clone();
+        assert!(armed.is_armed());
+        helper.disarm();
+        let disarmed = helper.clone();
+        assert!(!disarmed.is_armed());
+        let rearmed = helper.clone_armed();
+        assert!(rearmed.is_armed());
+        core::mem::drop((helper, armed, disarmed, rearmed));
+        assert_eq!(dropped(), 2);
+    }
+
+    #[test]
+    fn take_or_run_armed() {
+        let check = dropcheck::DropCheck::new();
+        let helper = <DisarmableHelper<_, CountDrop>>::new(check.token());
+        assert!(helper.take_or_run().is_none());
+        assert_eq!(dropped(), 1);
+        assert!(check.all_dropped());
+    }
+
+    #[test]
+    fn take_or_run_disarmed() {
+        let check = dropcheck::DropCheck::new();
+        let (drop_token, drop_state) = check.pair();
+        let mut helper = <DisarmableHelper<_, CountDrop>>::new(drop_token);
+        helper.disarm();
+        let inner = helper.take_or_run().unwrap();
+        assert_eq!(dropped(), 0);
+        assert!(drop_state.is_not_dropped());
+        core::mem::drop(inner);
+        assert!(drop_state.is_dropped());
+    }
+
+    #[test]
+    fn reset_rearms() {
+        let check = dropcheck::DropCheck::new();
+        let (first, first_state) = check.pair();
+        let mut helper = <DisarmableHelper<_, CountDrop>>::new(first);
+        helper.disarm();
+        let first = helper.reset(check.token());
+        assert!(helper.is_armed());
+        assert_eq!(dropped(), 0);
+        assert!(first_state.is_not_dropped());
+        core::mem::drop(first);
+        core::mem::drop(helper);
+        assert_eq!(dropped(), 1);
+        assert!(check.all_dropped());
+    }
+
+    #[test]
+    fn split_borrow() {
+        enum CountLenDrop {}
+
+        impl DetachedDrop for CountLenDrop {
+            type Implementor = std::vec::Vec<u8>;
+
+            fn drop(_: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+            }
+        }
+
+        fn cancel_if_empty(helper: &mut DisarmableHelper<std::vec::Vec<u8>, CountLenDrop>) {
+            let (value, mut handle) = helper.split_borrow();
+            assert!(handle.is_armed());
+            if value.is_empty() {
+                handle.disarm();
+            }
+        }
+
+        let mut empty = <DisarmableHelper<_, CountLenDrop>>::new(std::vec::Vec::new());
+        cancel_if_empty(&mut empty);
+        assert!(!empty.is_armed());
+        core::mem::drop(empty);
+        assert_eq!(dropped(), 0);
+
+        let mut full = <DisarmableHelper<_, CountLenDrop>>::new(std::vec![42]);
+        cancel_if_empty(&mut full);
+        let (_, mut handle) = full.split_borrow();
+        handle.disarm();
+        handle.arm();
+        assert!(full.is_armed());
+        core::mem::drop(full);
+        assert_eq!(dropped(), 1);
+    }
+
+    #[test]
+    fn freeze() {
+        let check = dropcheck::DropCheck::new();
+        let mut helper = <DisarmableHelper<_, CountDrop>>::new(check.token());
+        {
+            let frozen = helper.freeze();
+            assert!(!*frozen.armed);
+        }
+        assert!(helper.is_armed());
+        let frozen = helper.freeze();
+        frozen.thaw();
+        assert!(helper.is_armed());
+        let result = std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| {
+            let frozen = helper.freeze();
+            assert!(!*frozen.armed);
+            panic!("failed while frozen");
+        }));
+        assert!(result.is_err());
+        assert!(helper.is_armed());
+        helper.disarm();
+        helper.freeze().thaw();
+        assert!(!helper.is_armed());
+        helper.arm();
+        core::mem::drop(helper);
+        assert_eq!(dropped(), 1);
+        assert!(check.all_dropped());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unwinding() {
+        use crate::unwind::tests::{Record, SkipOnPanic, drop_while_panicking};
+
+        assert_eq!(drop_while_panicking(<DisarmableHelper<_, Record>>::new(42)), ["drop_panicking"]);
+        assert!(drop_while_panicking(<DisarmableHelper<_, SkipOnPanic>>::new(42)).is_empty());
+
+        struct TakeOrRun(Option<DisarmableHelper<u32, SkipOnPanic>>);
+
+        impl Drop for TakeOrRun {
+            fn drop(&mut self) {
+                assert_eq!(self.0.take().unwrap().take_or_run(), None);
+            }
+        }
+
+        assert!(drop_while_panicking(TakeOrRun(Some(DisarmableHelper::new(42)))).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn drop_in_place() {
+        use crate::unwind::tests::{InPlaceRecord, called, in_place_address};
+
+        let address = {
+            let helper = <DisarmableHelper<_, InPlaceRecord>>::new(42);
+            helper.inner() as *const u32
+        };
+        assert_eq!(called(), ["drop_in_place"]);
+        assert_eq!(in_place_address(), Some(address));
+    }
+}
@@ -0,0 +1,322 @@
+use crate::DetachedDrop;
+use crate::unwind::detached_drop;
+
+/// A helper that can be emptied without being consumed.
+///
+/// This is similar to `IntoInnerHelper` but the inner value is stored in an `Option`, so it can be
+/// taken out through a mutable reference. The detached drop only runs if the helper still holds
+/// the value when dropped.
+///
+/// Taking the value out of an already emptied helper is a logic bug. It panics in debug builds
+/// and returns `None` in release builds. Taking the value out of a helper created using `empty`
+/// that was never filled just returns `None`. Accessing the value is fine, `inner` and
+/// `inner_mut` return `None` if the helper is empty while `expect_inner` and `expect_inner_mut`
+/// panic.
+pub struct EmptiableHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    inner: Option<T>,
+    emptied: bool,
+    _phantom: core::marker::PhantomData<fn() -> D>,
+}
+
+impl<T, D> EmptiableHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    /// Creates the helper holding `inner`.
+    pub fn new(inner: T) -> Self {
+        EmptiableHelper {
+            inner: Some(inner),
+            emptied: false,
+            _phantom: Default::default(),
+        }
+    }
+
+    /// Creates an empty helper.
+    ///
+    /// This is useful for deferred resource acquisition using `inner_or_init`, the detached drop
+    /// only runs if the value was initialized.
+    pub fn empty() -> Self {
+        EmptiableHelper {
+            inner: None,
+            emptied: false,
+            _phantom: Default::default(),
+        }
+    }
+
+    /// Accesses the inner value, initializing it using `f` if the helper is empty.
+    pub fn inner_or_init<F>(&mut self, f: F) -> &mut T where F: FnOnce() -> T {
+        self.inner.get_or_insert_with(f)
+    }
+
+    /// Accesses the inner value, returning `None` if it was taken out.
+    pub fn inner(&self) -> Option<&T> {
+        self.inner.as_ref()
+    }
+
+    /// Accesses the inner value mutably, returning `None` if it was taken out.
+    pub fn inner_mut(&mut self) -> Option<&mut T> {
+        self.inner.as_mut()
+    }
+
+    /// Accesses the inner value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the helper is empty.
+    #[track_caller]
+    pub fn expect_inner(&self) -> &T {
+        self.inner.as_ref().expect("attempted to access the value of an empty EmptiableHelper")
+    }
+
+    /// Accesses the inner value mutably.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the helper is empty.
+    #[track_caller]
+    pub fn expect_inner_mut(&mut self) -> &mut T {
+        self.inner.as_mut().expect("attempted to access the value of an empty EmptiableHelper")
+    }
+
+    /// Returns `true` if the helper doesn't hold a value.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_none()
+    }
+
+    /// Stores `value` in the helper, returning the previous value if there was any.
+    ///
+    /// The detached drop doesn't run on the returned value, so the helper behaves as if it was
+    /// freshly created.
+    pub fn reset(&mut self, value: T) -> Option<T> {
+        self.emptied = false;
+        self.inner.replace(value)
+    }
+
+    /// Takes out the inner value, preventing the detached drop.
+    ///
+    /// # Panics
+    ///
+    /// Panics in debug builds if the value was already taken out. Returns `None` in release
+    /// builds in such case. Returns `None` without panicking if the helper was never filled.
+    #[track_caller]
+    pub fn take(&mut self) -> Option<T> {
+        debug_assert!(self.inner.is_some() || !self.emptied, "attempted to take the value out of an already emptied EmptiableHelper");
+        let inner = self.inner.take();
+        self.emptied |= inner.is_some();
+        inner
+    }
+
+    /// Moves out the inner value, preventing the detached drop.
+    ///
+    /// # Panics
+    ///
+    /// Panics in debug builds if the value was already taken out. Returns `None` in release
+    /// builds in such case. Returns `None` without panicking if the helper was never filled.
+    #[track_caller]
+    pub fn into_inner(mut self) -> Option<T> {
+        debug_assert!(self.inner.is_some() || !self.emptied, "attempted to move the value out of an already emptied EmptiableHelper");
+        self.inner.take()
+    }
+}
+
+impl<T, D> Drop for EmptiableHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.take() {
+            detached_drop::<D>(inner);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EmptiableHelper;
+    use crate::DetachedDrop;
+
+    enum Dummy {}
+
+    impl DetachedDrop for Dummy {
+        type Implementor = dropcheck::DropToken;
+
+        fn drop(_: Self::Implementor) {}
+    }
+
+    #[test]
+    fn drop_once() {
+        let check = dropcheck::DropCheck::new();
+        let (drop_token, drop_state) = check.pair();
+        let helper = <EmptiableHelper<_, Dummy>>::new(drop_token);
+        assert!(!helper.is_empty());
+        core::mem::drop(helper);
+        assert!(drop_state.is_dropped());
+    }
+
+    #[test]
+    fn take() {
+        let check = dropcheck::DropCheck::new();
+        let (drop_token, drop_state) = check.pair();
+        let mut helper = <EmptiableHelper<_, Dummy>>::new(drop_token);
+        let inner = helper.take().unwrap();
+        assert!(helper.is_empty());
+        core::mem::drop(helper);
+        assert!(drop_state.is_not_dropped());
+        core::mem::drop(inner);
+        assert!(drop_state.is_dropped());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "attempted to take the value out of an already emptied EmptiableHelper")]
+    fn take_twice_panics_in_debug() {
+        let check = dropcheck::DropCheck::new();
+        let mut helper = <EmptiableHelper<_, Dummy>>::new(check.token());
+        let _inner = helper.take();
+        let _ = helper.take();
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "attempted to move the value out of an already emptied EmptiableHelper")]
+    fn into_inner_after_take_panics_in_debug() {
+        let check = dropcheck::DropCheck::new();
+        let mut helper = <EmptiableHelper<_, Dummy>>::new(check.token());
+        let _inner = helper.take();
+        let _ = helper.into_inner();
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn take_twice_returns_none_in_release() {
+        let check = dropcheck::DropCheck::new();
+        let mut helper = <EmptiableHelper<_, Dummy>>::new(check.token());
+        assert!(helper.take().is_some());
+        assert!(helper.take().is_none());
+        assert!(helper.into_inner().is_none());
+    }
+
+    #[test]
+    fn inner() {
+        let check = dropcheck::DropCheck::new();
+        let (drop_token, drop_state) = check.pair();
+        let mut helper = <EmptiableHelper<_, Dummy>>::new(drop_token);
+        assert!(helper.inner().is_some());
+        assert!(helper.inner_mut().is_some());
+        let address: *const _ = helper.expect_inner();
+        assert_eq!(helper.expect_inner_mut() as *const _, address);
+        assert!(drop_state.is_not_dropped());
+        let inner = helper.take().unwrap();
+        assert!(helper.inner().is_none());
+        assert!(helper.inner_mut().is_none());
+        core::mem::drop(inner);
+        assert!(drop_state.is_dropped());
+    }
+
+    #[test]
+    #[should_panic(expected = "attempted to access the value of an empty EmptiableHelper")]
+    fn expect_inner_panics_when_empty() {
+        let check = dropcheck::DropCheck::new();
+        let mut helper = <EmptiableHelper<_, Dummy>>::new(check.token());
+        let _inner = helper.take();
+        let _ = helper.expect_inner();
+    }
+
+    #[test]
+    fn panic_location_is_callers() {
+        use std::sync::Once;
+        use std::string::{String, ToString};
+        use core::cell::RefCell;
+
+        std::thread_local! {
+            static LOCATION: RefCell<Option<String>> = const { RefCell::new(None) };
+        }
+
+        static HOOK: Once = Once::new();
+
+        HOOK.call_once(|| {
+            let previous = std::panic::take_hook();
+            std::panic::set_hook(std::boxed::Box::new(move |info| {
+                LOCATION.with(|location| *location.borrow_mut() = info.location().map(ToString::to_string));
+                previous(info);
+            }));
+        });
+
+        let check = dropcheck::DropCheck::new();
+        let mut helper = <EmptiableHelper<_, Dummy>>::new(check.token());
+        let _inner = helper.take();
+        let line = line!() + 1;
+        let result = std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| { let _ = helper.expect_inner(); }));
+        assert!(result.is_err());
+        let expected = std::format!("{}:{}:", file!(), line);
+        LOCATION.with(|location| assert!(location.borrow().as_ref().unwrap().starts_with(&expected)));
+    }
+
+    #[test]
+    fn lazy_init() {
+        let check = dropcheck::DropCheck::new();
+        let (drop_token, drop_state) = check.pair();
+        let mut helper = <EmptiableHelper<_, Dummy>>::empty();
+        assert!(helper.is_empty());
+        let address: *const _ = helper.inner_or_init(|| drop_token);
+        assert_eq!(helper.inner_or_init(|| panic!("already initialized")) as *const _, address);
+        assert!(drop_state.is_not_dropped());
+        core::mem::drop(helper);
+        assert!(drop_state.is_dropped());
+    }
+
+    #[test]
+    fn never_initialized() {
+        enum PanicDrop {}
+
+        impl DetachedDrop for PanicDrop {
+            type Implementor = u32;
+
+            fn drop(_: Self::Implementor) {
+                panic!("never initialized value dropped");
+            }
+        }
+
+        let helper = <EmptiableHelper<_, PanicDrop>>::empty();
+        assert!(helper.inner().is_none());
+        core::mem::drop(helper);
+    }
+
+    #[test]
+    fn never_filled_into_inner() {
+        let helper = <EmptiableHelper<_, Dummy>>::empty();
+        assert!(helper.into_inner().is_none());
+
+        let mut helper = <EmptiableHelper<_, Dummy>>::empty();
+        assert!(helper.take().is_none());
+        assert!(helper.take().is_none());
+        assert!(helper.into_inner().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "attempted to access the value of an empty EmptiableHelper")]
+    fn expect_inner_panics_when_never_filled() {
+        let mut helper = <EmptiableHelper<_, Dummy>>::empty();
+        let _ = helper.expect_inner_mut();
+    }
+
+    #[test]
+    fn reset_refills() {
+        let check = dropcheck::DropCheck::new();
+        let (first, first_state) = check.pair();
+        let (second, second_state) = check.pair();
+        let mut helper = <EmptiableHelper<_, Dummy>>::new(first);
+        let first = helper.take().unwrap();
+        assert!(helper.reset(second).is_none());
+        assert!(!helper.is_empty());
+        let second = helper.reset(first).unwrap();
+        assert!(first_state.is_not_dropped());
+        assert!(second_state.is_not_dropped());
+        core::mem::drop(helper);
+        assert!(first_state.is_dropped());
+        core::mem::drop(second);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unwinding() {
+        use crate::unwind::tests::{Record, SkipOnPanic, drop_while_panicking};
+
+        assert_eq!(drop_while_panicking(<EmptiableHelper<_, Record>>::new(42)), ["drop_panicking"]);
+        assert!(drop_while_panicking(<EmptiableHelper<_, SkipOnPanic>>::new(42)).is_empty());
+    }
+}
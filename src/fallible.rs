@@ -0,0 +1,301 @@
+use core::fmt;
+use crate::{IntoInnerHelper, DetachedDrop};
+
+/// A replacement trait for providing fallible Drop implementation.
+///
+/// This is the fallible version of `DetachedDrop`. The error can only be observed by finalizing
+/// the helper explicitly using `TryIntoInnerHelper::try_finalize`, it's ignored when the helper is
+/// simply dropped.
+///
+/// Every `DetachedDrop` marker implements this trait too, with `Infallible` error, so generic code
+/// can be written against this trait only. Because of this a marker can't implement both traits.
+pub trait TryDetachedDrop {
+    /// The inner type you want to implement Drop for.
+    type Implementor;
+
+    /// The error returned when the drop implementation fails.
+    type Error: fmt::Debug;
+
+    /// The drop implementation called by `TryIntoInnerHelper<Self::Implementor, Self>`.
+    ///
+    /// This function will only be called if `into_inner` was NOT called.
+    fn try_drop(value: Self::Implementor) -> Result<(), Self::Error>;
+}
+
+impl<D> TryDetachedDrop for D where D: DetachedDrop {
+    type Implementor = <D as DetachedDrop>::Implementor;
+    type Error = core::convert::Infallible;
+
+    fn try_drop(value: Self::Implementor) -> Result<(), Self::Error> {
+        <D as DetachedDrop>::drop(value);
+        Ok(())
+    }
+}
+
+struct IgnoreErrors<D>(core::convert::Infallible, core::marker::PhantomData<D>);
+
+impl<D> DetachedDrop for IgnoreErrors<D> where D: TryDetachedDrop {
+    type Implementor = D::Implementor;
+
+    fn drop(value: Self::Implementor) {
+        let _ = D::try_drop(value);
+    }
+}
+
+/// The helper which allows you to implement fallible `Drop` for your type while still allowing to
+/// take it apart by moving out.
+pub struct TryIntoInnerHelper<T, D> where D: TryDetachedDrop<Implementor=T> {
+    inner: IntoInnerHelper<T, IgnoreErrors<D>>,
+}
+
+impl<T, D> TryIntoInnerHelper<T, D> where D: TryDetachedDrop<Implementor=T> {
+    /// Creates the helper.
+    pub fn new(inner: T) -> Self {
+        TryIntoInnerHelper {
+            inner: IntoInnerHelper::new(inner),
+        }
+    }
+
+    /// Accesses the inner value.
+    pub fn inner(&self) -> &T {
+        self.inner.inner()
+    }
+
+    /// Accesses the inner value mutably.
+    pub fn inner_mut(&mut self) -> &mut T {
+        self.inner.inner_mut()
+    }
+
+    /// Moves out the inner value.
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+
+    /// Runs the detached drop now, returning the error if it failed.
+    pub fn try_finalize(self) -> Result<(), FinalizeError<D::Error>> {
+        D::try_drop(self.into_inner()).map_err(FinalizeError::new::<T>)
+    }
+}
+
+/// Finalizing returns the error, so `guard.finalize()?` propagates it.
+impl<T, D> crate::Finalize for TryIntoInnerHelper<T, D> where D: TryDetachedDrop<Implementor=T> {
+    type Output = Result<(), FinalizeError<D::Error>>;
+
+    fn finalize(self) -> Self::Output {
+        self.try_finalize()
+    }
+}
+
+/// The error returned when finalizing a guard failed.
+///
+/// Apart from the error returned by the finalizer it carries the name of the finalized type to
+/// give the error some context. This doesn't require `std`.
+#[derive(Debug, Clone)]
+pub struct FinalizeError<E> {
+    error: E,
+    type_name: &'static str,
+}
+
+impl<E> FinalizeError<E> {
+    /// Creates the error caused by `error` when finalizing a value of type `T`.
+    pub fn new<T: ?Sized>(error: E) -> Self {
+        FinalizeError {
+            error,
+            type_name: core::any::type_name::<T>(),
+        }
+    }
+
+    /// Returns the error returned by the finalizer.
+    pub fn error(&self) -> &E {
+        &self.error
+    }
+
+    /// Returns the name of the type that failed to finalize.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Converts the error into the error returned by the finalizer.
+    pub fn into_error(self) -> E {
+        self.error
+    }
+
+    /// Converts the error returned by the finalizer, keeping the context.
+    pub fn map<F, G: FnOnce(E) -> F>(self, f: G) -> FinalizeError<F> {
+        FinalizeError {
+            error: f(self.error),
+            type_name: self.type_name,
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for FinalizeError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to finalize {}: {}", self.type_name, self.error)
+    }
+}
+
+/// The finalizer error is the source.
+#[cfg(feature = "std")]
+impl<E: std::error::Error + 'static> std::error::Error for FinalizeError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::ToString;
+    use core::cell::Cell;
+    use super::{TryIntoInnerHelper, TryDetachedDrop};
+
+    std::thread_local! {
+        static ATTEMPTS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    struct Connection {
+        fail: bool,
+    }
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct CloseError;
+
+    impl core::fmt::Display for CloseError {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            f.write_str("connection reset")
+        }
+    }
+
+    enum Close {}
+
+    impl TryDetachedDrop for Close {
+        type Implementor = Connection;
+        type Error = CloseError;
+
+        fn try_drop(value: Self::Implementor) -> Result<(), Self::Error> {
+            ATTEMPTS.with(|attempts| attempts.set(attempts.get() + 1));
+            if value.fail {
+                Err(CloseError)
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn try_finalize_ok() {
+        let helper = <TryIntoInnerHelper<_, Close>>::new(Connection { fail: false });
+        helper.try_finalize().unwrap();
+        assert_eq!(ATTEMPTS.with(Cell::get), 1);
+    }
+
+    #[test]
+    fn try_finalize_err() {
+        let helper = <TryIntoInnerHelper<_, Close>>::new(Connection { fail: true });
+        let error = helper.try_finalize().unwrap_err();
+        assert_eq!(*error.error(), CloseError);
+        assert!(error.type_name().ends_with("Connection"));
+        assert!(error.to_string().starts_with("failed to finalize "));
+        assert!(error.to_string().ends_with("Connection: connection reset"));
+        assert_eq!(error.map(|_| 42).into_error(), 42);
+        assert_eq!(ATTEMPTS.with(Cell::get), 1);
+    }
+
+    #[cfg(feature = "std")]
+    impl std::error::Error for CloseError {}
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn std_error() {
+        use std::boxed::Box;
+        use std::error::Error;
+
+        fn close(connection: Connection) -> Result<(), Box<dyn Error>> {
+            <TryIntoInnerHelper<_, Close>>::new(connection).try_finalize()?;
+            Ok(())
+        }
+
+        close(Connection { fail: false }).unwrap();
+        let error = close(Connection { fail: true }).unwrap_err();
+        assert!(error.to_string().ends_with("Connection: connection reset"));
+        let source = error.source().unwrap();
+        assert_eq!(source.downcast_ref::<CloseError>(), Some(&CloseError));
+        assert!(source.source().is_none());
+        assert_eq!(ATTEMPTS.with(Cell::get), 2);
+    }
+
+    #[test]
+    fn finalize_question_mark() {
+        use crate::{Finalize, FinalizeError};
+
+        #[derive(Debug)]
+        enum AppError {
+            Close(FinalizeError<CloseError>),
+        }
+
+        impl From<FinalizeError<CloseError>> for AppError {
+            fn from(error: FinalizeError<CloseError>) -> Self {
+                AppError::Close(error)
+            }
+        }
+
+        fn shutdown(connections: [Connection; 2]) -> Result<usize, AppError> {
+            let [first, second] = connections;
+            <TryIntoInnerHelper<_, Close>>::new(first).finalize()?;
+            <TryIntoInnerHelper<_, Close>>::new(second).finalize()?;
+            Ok(2)
+        }
+
+        assert_eq!(shutdown([Connection { fail: false }, Connection { fail: false }]).unwrap(), 2);
+        assert_eq!(ATTEMPTS.with(Cell::get), 2);
+        let AppError::Close(error) = shutdown([Connection { fail: true }, Connection { fail: false }]).unwrap_err();
+        assert_eq!(*error.error(), CloseError);
+        // The failed guard is consumed by the attempt, so it's not finalized again when unwinding
+        // the function and the second connection is never guarded.
+        assert_eq!(ATTEMPTS.with(Cell::get), 3);
+    }
+
+    #[test]
+    fn drop_ignores_error() {
+        let helper = <TryIntoInnerHelper<_, Close>>::new(Connection { fail: true });
+        core::mem::drop(helper);
+        assert_eq!(ATTEMPTS.with(Cell::get), 1);
+    }
+
+    #[test]
+    fn into_inner() {
+        let mut helper = <TryIntoInnerHelper<_, Close>>::new(Connection { fail: true });
+        helper.inner_mut().fail = false;
+        assert!(!helper.inner().fail);
+        let _connection = helper.into_inner();
+        assert_eq!(ATTEMPTS.with(Cell::get), 0);
+    }
+
+    #[test]
+    fn plain_marker() {
+        use crate::DetachedDrop;
+
+        enum CountAttempt {}
+
+        impl DetachedDrop for CountAttempt {
+            type Implementor = Connection;
+
+            fn drop(_: Self::Implementor) {
+                ATTEMPTS.with(|attempts| attempts.set(attempts.get() + 1));
+            }
+        }
+
+        fn close_all<D: TryDetachedDrop<Implementor=Connection>>(connections: std::vec::Vec<Connection>) -> Result<(), D::Error> {
+            connections.into_iter().try_for_each(D::try_drop)
+        }
+
+        let connections = std::vec![Connection { fail: false }, Connection { fail: true }];
+        let result: Result<(), core::convert::Infallible> = close_all::<CountAttempt>(connections);
+        assert!(result.is_ok());
+        assert_eq!(ATTEMPTS.with(Cell::get), 2);
+
+        let helper = <TryIntoInnerHelper<_, CountAttempt>>::new(Connection { fail: true });
+        helper.try_finalize().unwrap();
+        assert_eq!(ATTEMPTS.with(Cell::get), 3);
+    }
+}
@@ -0,0 +1,127 @@
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+use crate::{IntoInnerHelper, DetachedDrop};
+
+/// A marker that additionally declares a typed, by-value view of the inner value.
+///
+/// This is intended for packed representations, such as a `u32` register holding several bit
+/// fields. Unlike `DetachedView` the fields are decoded into a separate value and encoded back
+/// after modification, so no reinterpretation of memory happens and the representation doesn't
+/// need to match the layout of `Fields`.
+pub trait DetachedFields: DetachedDrop {
+    /// The typed view of the inner value.
+    type Fields;
+
+    /// Decodes the fields from the inner value.
+    fn decode(value: &Self::Implementor) -> Self::Fields;
+
+    /// Encodes the fields into the inner value.
+    fn encode(value: &mut Self::Implementor, fields: Self::Fields);
+}
+
+impl<T, D> IntoInnerHelper<T, D> where D: DetachedFields<Implementor=T> {
+    /// Decodes the fields of the inner value declared by `D`.
+    pub fn fields(&self) -> D::Fields {
+        D::decode(self.inner())
+    }
+
+    /// Decodes the fields for modification, encoding them back when the returned guard is dropped.
+    pub fn fields_mut(&mut self) -> FieldsMut<'_, T, D> {
+        let fields = D::decode(self.inner());
+        FieldsMut {
+            inner: self.inner_mut(),
+            fields: ManuallyDrop::new(fields),
+        }
+    }
+}
+
+/// Decoded fields of a guarded value, encoded back into it when dropped.
+///
+/// Returned by `IntoInnerHelper::fields_mut`.
+pub struct FieldsMut<'a, T, D> where D: DetachedFields<Implementor=T> {
+    inner: &'a mut T,
+    fields: ManuallyDrop<D::Fields>,
+}
+
+impl<T, D> Deref for FieldsMut<'_, T, D> where D: DetachedFields<Implementor=T> {
+    type Target = D::Fields;
+
+    fn deref(&self) -> &Self::Target {
+        &self.fields
+    }
+}
+
+impl<T, D> DerefMut for FieldsMut<'_, T, D> where D: DetachedFields<Implementor=T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.fields
+    }
+}
+
+impl<T, D> Drop for FieldsMut<'_, T, D> where D: DetachedFields<Implementor=T> {
+    fn drop(&mut self) {
+        // SAFETY: the fields are initialized and never accessed again.
+        let fields = unsafe { ManuallyDrop::take(&mut self.fields) };
+        D::encode(self.inner, fields);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use super::DetachedFields;
+    use crate::{IntoInnerHelper, DetachedDrop};
+
+    std::thread_local! {
+        static WRITTEN: Cell<Option<u32>> = const { Cell::new(None) };
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Control {
+        enabled: bool,
+        mode: u8,
+        prescaler: u16,
+    }
+
+    enum ControlRegister {}
+
+    impl DetachedDrop for ControlRegister {
+        type Implementor = u32;
+
+        fn drop(bits: Self::Implementor) {
+            WRITTEN.with(|written| written.set(Some(bits)));
+        }
+    }
+
+    impl DetachedFields for ControlRegister {
+        type Fields = Control;
+
+        fn decode(bits: &Self::Implementor) -> Self::Fields {
+            Control {
+                enabled: bits & 1 != 0,
+                mode: ((bits >> 1) & 0b111) as u8,
+                prescaler: (bits >> 16) as u16,
+            }
+        }
+
+        fn encode(bits: &mut Self::Implementor, fields: Self::Fields) {
+            // bits 4..16 are reserved and must be preserved
+            *bits = (*bits & 0xfff0) | u32::from(fields.enabled) | (u32::from(fields.mode & 0b111) << 1) | (u32::from(fields.prescaler) << 16);
+        }
+    }
+
+    #[test]
+    fn read_and_write_fields() {
+        let mut register = <IntoInnerHelper<_, ControlRegister>>::new(0x0003_00a5);
+        assert_eq!(register.fields(), Control { enabled: true, mode: 2, prescaler: 3 });
+        {
+            let mut fields = register.fields_mut();
+            fields.enabled = false;
+            fields.mode = 5;
+        }
+        register.fields_mut().prescaler = 0x1234;
+        assert_eq!(*register.inner(), 0x1234_00aa);
+        assert_eq!(WRITTEN.with(Cell::get), None);
+        core::mem::drop(register);
+        assert_eq!(WRITTEN.with(Cell::get), Some(0x1234_00aa));
+    }
+}
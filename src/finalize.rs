@@ -0,0 +1,206 @@
+use crate::{IntoInnerHelper, EmptiableHelper, DisarmableHelper, AdaptedHelper, ClosureHelper, DetachedDrop};
+
+/// A guard that can be finalized explicitly.
+///
+/// Finalizing runs the cleanup immediately, exactly like dropping the guard would, but makes the
+/// intent visible and allows the cleanup to produce a value.
+///
+/// Tuples of guards (up to 12 elements) implement this trait too, finalizing the elements in
+/// order and collecting their outputs into a tuple.
+pub trait Finalize {
+    /// The value produced by finalization.
+    type Output;
+
+    /// Runs the cleanup, consuming the guard.
+    fn finalize(self) -> Self::Output;
+}
+
+/// A detached drop producing a value.
+///
+/// This is useful when the cleanup yields a result worth inspecting, e.g. the number of bytes
+/// flushed. The output can be obtained by `IntoInnerHelper::finalize_with_output` or collected
+/// from batches of guards using `GuardVec::finalize_all`. The `drop` implementation of
+/// `DetachedDrop` is still used when the helper is simply dropped, so it should normally call
+/// `drop_with_output` and discard the result.
+pub trait DetachedDropWithOutput: DetachedDrop {
+    /// The value produced by the drop implementation.
+    type Output;
+
+    /// The drop implementation returning the output.
+    fn drop_with_output(value: Self::Implementor) -> Self::Output;
+}
+
+impl<T, D> IntoInnerHelper<T, D> where D: DetachedDropWithOutput<Implementor=T> {
+    /// Runs the detached drop now, returning its output.
+    pub fn finalize_with_output(self) -> D::Output {
+        D::drop_with_output(self.into_inner())
+    }
+}
+
+/// Finalizes all guards in `guards`, in order.
+///
+/// This is intended to be used with tuples of different guard types, e.g. at the end of a scope
+/// managing several unrelated resources.
+pub fn finalize_all<G: Finalize>(guards: G) -> G::Output {
+    guards.finalize()
+}
+
+impl<T, D> Finalize for IntoInnerHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    type Output = ();
+
+    fn finalize(self) {
+        IntoInnerHelper::finalize(self)
+    }
+}
+
+impl<T, D> Finalize for EmptiableHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    type Output = ();
+
+    fn finalize(self) {
+        core::mem::drop(self)
+    }
+}
+
+impl<T, D> Finalize for DisarmableHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    type Output = ();
+
+    fn finalize(self) {
+        core::mem::drop(self)
+    }
+}
+
+impl<U, G, D> Finalize for AdaptedHelper<U, G, D> where D: DetachedDrop, G: FnOnce(U) -> D::Implementor {
+    type Output = ();
+
+    fn finalize(self) {
+        core::mem::drop(self)
+    }
+}
+
+impl<T, F> Finalize for ClosureHelper<T, F> where F: FnOnce(T) {
+    type Output = ();
+
+    fn finalize(self) {
+        core::mem::drop(self)
+    }
+}
+
+impl<T, D> Finalize for crate::ProjectedHelper<T, D> where D: crate::DetachedView<Implementor=T> {
+    type Output = ();
+
+    fn finalize(self) {
+        core::mem::drop(self)
+    }
+}
+
+impl<T, D> Finalize for crate::RefCellHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    type Output = ();
+
+    fn finalize(self) {
+        core::mem::drop(self)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, D> Finalize for crate::BoxedHelper<T, D> where T: ?Sized, D: DetachedDrop<Implementor=alloc::boxed::Box<T>> {
+    type Output = ();
+
+    fn finalize(self) {
+        core::mem::drop(self)
+    }
+}
+
+macro_rules! impl_finalize_tuple {
+    ($($guard:ident $value:ident),+) => {
+        impl<$($guard),+> Finalize for ($($guard,)+) where $($guard: Finalize),+ {
+            type Output = ($($guard::Output,)+);
+
+            fn finalize(self) -> Self::Output {
+                let ($($value,)+) = self;
+                ($($value.finalize(),)+)
+            }
+        }
+    }
+}
+
+impl_finalize_tuple!(A a);
+impl_finalize_tuple!(A a, B b);
+impl_finalize_tuple!(A a, B b, C c);
+impl_finalize_tuple!(A a, B b, C c, D d);
+impl_finalize_tuple!(A a, B b, C c, D d, E e);
+impl_finalize_tuple!(A a, B b, C c, D d, E e, F f);
+impl_finalize_tuple!(A a, B b, C c, D d, E e, F f, G g);
+impl_finalize_tuple!(A a, B b, C c, D d, E e, F f, G g, H h);
+impl_finalize_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i);
+impl_finalize_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j);
+impl_finalize_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k);
+impl_finalize_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l);
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+    use std::string::String;
+    use core::cell::RefCell;
+    use super::finalize_all;
+    use crate::{IntoInnerHelper, EmptiableHelper, DisarmableHelper, DetachedDrop};
+
+    std::thread_local! {
+        static ORDER: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn record(name: &'static str) {
+        ORDER.with(|order| order.borrow_mut().push(name));
+    }
+
+    enum TokenDrop {}
+
+    impl DetachedDrop for TokenDrop {
+        type Implementor = dropcheck::DropToken;
+
+        fn drop(_: Self::Implementor) {
+            record("token");
+        }
+    }
+
+    enum StringDrop {}
+
+    impl DetachedDrop for StringDrop {
+        type Implementor = String;
+
+        fn drop(_: Self::Implementor) {
+            record("string");
+        }
+    }
+
+    enum NumberDrop {}
+
+    impl DetachedDrop for NumberDrop {
+        type Implementor = u32;
+
+        fn drop(_: Self::Implementor) {
+            record("number");
+        }
+    }
+
+    #[test]
+    fn finalize() {
+        let check = dropcheck::DropCheck::new();
+        let helper = <IntoInnerHelper<_, TokenDrop>>::new(check.token());
+        helper.finalize();
+        assert!(check.all_dropped());
+        assert_eq!(ORDER.with(|order| order.borrow().clone()), ["token"]);
+    }
+
+    #[test]
+    fn heterogeneous_tuple() {
+        let check = dropcheck::DropCheck::new();
+        let guards = (
+            <DisarmableHelper<_, StringDrop>>::new(String::from("first")),
+            <IntoInnerHelper<_, TokenDrop>>::new(check.token()),
+            <EmptiableHelper<_, NumberDrop>>::new(42),
+        );
+        finalize_all(guards);
+        assert!(check.all_dropped());
+        assert_eq!(ORDER.with(|order| order.borrow().clone()), ["string", "token", "number"]);
+    }
+}
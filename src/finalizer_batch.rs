@@ -0,0 +1,129 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use crate::{IntoInnerHelper, DetachedDrop};
+
+/// A batch of guards of different types, finalized together by `flush`.
+///
+/// Adding a guard to the batch transfers its detached drop to the batch, so it doesn't run when
+/// the guard would have gone out of scope. `flush` runs the detached drops in the order the guards
+/// were added. If one of them panics, the remaining ones still run while unwinding. Dropping the
+/// batch without flushing runs them too, so no cleanup is lost.
+pub struct FinalizerBatch<'a> {
+    pending: Vec<Box<dyn FnOnce() + 'a>>,
+}
+
+impl<'a> FinalizerBatch<'a> {
+    /// Creates an empty batch.
+    pub fn new() -> Self {
+        FinalizerBatch {
+            pending: Vec::new(),
+        }
+    }
+
+    /// Adds the guard to the batch.
+    pub fn add<T, D>(&mut self, guard: IntoInnerHelper<T, D>) where T: 'a, D: DetachedDrop<Implementor=T> + 'a {
+        self.pending.push(Box::new(move || guard.finalize()));
+    }
+
+    /// Returns the number of guards waiting to be finalized.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns `true` if there are no guards in the batch.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Runs the detached drops of all guards in the batch.
+    pub fn flush(self) {
+        // If a detached drop panics, dropping the iterator finalizes the remaining guards.
+        self.pending.into_iter().for_each(|finalize| finalize());
+    }
+}
+
+impl Default for FinalizerBatch<'_> {
+    fn default() -> Self {
+        FinalizerBatch::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+    use std::vec::Vec;
+    use super::FinalizerBatch;
+    use crate::{IntoInnerHelper, DetachedDrop};
+
+    std::thread_local! {
+        static COMMITTED: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn committed() -> Vec<&'static str> {
+        COMMITTED.with(|committed| committed.borrow().clone())
+    }
+
+    enum Commit {}
+
+    impl DetachedDrop for Commit {
+        type Implementor = &'static str;
+
+        fn drop(name: Self::Implementor) {
+            COMMITTED.with(|committed| committed.borrow_mut().push(name));
+        }
+    }
+
+    enum CommitNumber {}
+
+    impl DetachedDrop for CommitNumber {
+        type Implementor = u32;
+
+        fn drop(number: Self::Implementor) {
+            COMMITTED.with(|committed| committed.borrow_mut().push(if number == 1 { "one" } else { "other" }));
+        }
+    }
+
+    #[test]
+    fn flush_runs_each_once() {
+        let mut batch = FinalizerBatch::new();
+        {
+            batch.add(<IntoInnerHelper<_, Commit>>::new("first"));
+            batch.add(<IntoInnerHelper<_, CommitNumber>>::new(1));
+            batch.add(<IntoInnerHelper<_, Commit>>::new("last"));
+        }
+        assert_eq!(batch.len(), 3);
+        assert!(committed().is_empty());
+        batch.flush();
+        assert_eq!(committed(), ["first", "one", "last"]);
+    }
+
+    #[test]
+    fn drop_without_flush() {
+        let mut batch = FinalizerBatch::default();
+        assert!(batch.is_empty());
+        batch.add(<IntoInnerHelper<_, Commit>>::new("first"));
+        core::mem::drop(batch);
+        assert_eq!(committed(), ["first"]);
+    }
+
+    #[test]
+    fn panic_runs_remaining() {
+        enum Fail {}
+
+        impl DetachedDrop for Fail {
+            type Implementor = ();
+
+            fn drop(_: Self::Implementor) {
+                panic!("commit failed");
+            }
+        }
+
+        let mut batch = FinalizerBatch::new();
+        batch.add(<IntoInnerHelper<_, Commit>>::new("first"));
+        batch.add(<IntoInnerHelper<_, Fail>>::new(()));
+        batch.add(<IntoInnerHelper<_, Commit>>::new("last"));
+        let result = std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| batch.flush()));
+        assert!(result.is_err());
+        assert_eq!(committed(), ["first", "last"]);
+    }
+}
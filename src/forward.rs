@@ -0,0 +1,747 @@
+//! Implementations of standard traits that forward to the inner value.
+//!
+//! None of these ever run the detached drop - they only borrow the inner value.
+
+use core::fmt;
+use core::ops::{Deref, DerefMut, Index, IndexMut};
+use core::iter::FusedIterator;
+use core::hash::{Hash, Hasher};
+use core::borrow::Borrow;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use crate::{IntoInnerHelper, DetachedDrop};
+
+impl<T, D> fmt::Debug for IntoInnerHelper<T, D> where T: fmt::Debug, D: DetachedDrop<Implementor=T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.inner(), f)
+    }
+}
+
+impl<T, D> fmt::Display for IntoInnerHelper<T, D> where T: fmt::Display, D: DetachedDrop<Implementor=T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.inner(), f)
+    }
+}
+
+macro_rules! forward_numeric_fmt {
+    ($($trait:ident),*) => {
+        $(
+            impl<T, D> fmt::$trait for IntoInnerHelper<T, D> where T: fmt::$trait, D: DetachedDrop<Implementor=T> {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    fmt::$trait::fmt(self.inner(), f)
+                }
+            }
+        )*
+    }
+}
+
+forward_numeric_fmt!(Binary, Octal, LowerHex, UpperHex, LowerExp, UpperExp);
+
+impl<T, D> Deref for IntoInnerHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.inner()
+    }
+}
+
+impl<T, D> DerefMut for IntoInnerHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.inner_mut()
+    }
+}
+
+impl<T, D> AsRef<T> for IntoInnerHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    fn as_ref(&self) -> &T {
+        self.inner()
+    }
+}
+
+impl<T, D> AsMut<T> for IntoInnerHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    fn as_mut(&mut self) -> &mut T {
+        self.inner_mut()
+    }
+}
+
+impl<T, D, I> Index<I> for IntoInnerHelper<T, D> where T: Index<I>, D: DetachedDrop<Implementor=T> {
+    type Output = T::Output;
+
+    fn index(&self, index: I) -> &Self::Output {
+        &self.inner()[index]
+    }
+}
+
+impl<T, D, I> IndexMut<I> for IntoInnerHelper<T, D> where T: IndexMut<I>, D: DetachedDrop<Implementor=T> {
+    fn index_mut(&mut self, index: I) -> &mut Self::Output {
+        &mut self.inner_mut()[index]
+    }
+}
+
+// `&mut IntoInnerHelper` can't be forwarded since it's an iterator itself if `T` is.
+impl<'a, T, D> IntoIterator for &'a IntoInnerHelper<T, D> where &'a T: IntoIterator, D: DetachedDrop<Implementor=T> {
+    type Item = <&'a T as IntoIterator>::Item;
+    type IntoIter = <&'a T as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.inner().into_iter()
+    }
+}
+
+impl<T, D> Iterator for IntoInnerHelper<T, D> where T: Iterator, D: DetachedDrop<Implementor=T> {
+    type Item = T::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner_mut().next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner().size_hint()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.inner_mut().nth(n)
+    }
+
+    // The consuming methods can't move the iterator out of the helper because the detached drop
+    // has to run afterwards. Going through `&mut T` still uses the specialized `try_fold` of `T`.
+
+    fn count(mut self) -> usize {
+        self.inner_mut().by_ref().count()
+    }
+
+    fn last(mut self) -> Option<Self::Item> {
+        self.inner_mut().by_ref().last()
+    }
+
+    fn fold<B, F>(mut self, init: B, f: F) -> B where F: FnMut(B, Self::Item) -> B {
+        self.inner_mut().by_ref().fold(init, f)
+    }
+}
+
+impl<T, D> DoubleEndedIterator for IntoInnerHelper<T, D> where T: DoubleEndedIterator, D: DetachedDrop<Implementor=T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner_mut().next_back()
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        self.inner_mut().nth_back(n)
+    }
+
+    fn rfold<B, F>(mut self, init: B, f: F) -> B where F: FnMut(B, Self::Item) -> B {
+        self.inner_mut().by_ref().rfold(init, f)
+    }
+}
+
+impl<T, D> ExactSizeIterator for IntoInnerHelper<T, D> where T: ExactSizeIterator, D: DetachedDrop<Implementor=T> {}
+
+impl<T, D> FusedIterator for IntoInnerHelper<T, D> where T: FusedIterator, D: DetachedDrop<Implementor=T> {}
+
+impl<T, D> PartialEq for IntoInnerHelper<T, D> where T: PartialEq, D: DetachedDrop<Implementor=T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.inner() == other.inner()
+    }
+}
+
+impl<T, D> Eq for IntoInnerHelper<T, D> where T: Eq, D: DetachedDrop<Implementor=T> {}
+
+impl<T, D> PartialOrd for IntoInnerHelper<T, D> where T: PartialOrd, D: DetachedDrop<Implementor=T> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        self.inner().partial_cmp(other.inner())
+    }
+}
+
+impl<T, D> Ord for IntoInnerHelper<T, D> where T: Ord, D: DetachedDrop<Implementor=T> {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.inner().cmp(other.inner())
+    }
+}
+
+/// Compares the guarded value with a raw value.
+impl<T, D> PartialEq<T> for IntoInnerHelper<T, D> where T: PartialEq, D: DetachedDrop<Implementor=T> {
+    fn eq(&self, other: &T) -> bool {
+        self.inner() == other
+    }
+}
+
+/// Compares the guarded value with a raw value.
+impl<T, D> PartialOrd<T> for IntoInnerHelper<T, D> where T: PartialOrd, D: DetachedDrop<Implementor=T> {
+    fn partial_cmp(&self, other: &T) -> Option<core::cmp::Ordering> {
+        self.inner().partial_cmp(other)
+    }
+}
+
+/// Implements the comparisons of raw primitives with guards.
+///
+/// The orphan rules don't allow doing this for any `T`, so only primitives are covered.
+macro_rules! forward_reverse_cmp {
+    ($($ty:ty),*) => {
+        $(
+            impl<D> PartialEq<IntoInnerHelper<$ty, D>> for $ty where D: DetachedDrop<Implementor=$ty> {
+                fn eq(&self, other: &IntoInnerHelper<$ty, D>) -> bool {
+                    self == other.inner()
+                }
+            }
+
+            impl<D> PartialOrd<IntoInnerHelper<$ty, D>> for $ty where D: DetachedDrop<Implementor=$ty> {
+                fn partial_cmp(&self, other: &IntoInnerHelper<$ty, D>) -> Option<core::cmp::Ordering> {
+                    self.partial_cmp(other.inner())
+                }
+            }
+        )*
+    }
+}
+
+forward_reverse_cmp!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, bool, char);
+
+/// Hashes exactly like the inner value.
+///
+/// This is guaranteed, so a helper and its inner value can be used interchangeably as keys, e.g.
+/// a key extracted using `into_inner` finds the same entries.
+impl<T, D> Hash for IntoInnerHelper<T, D> where T: Hash, D: DetachedDrop<Implementor=T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.inner().hash(state)
+    }
+}
+
+/// Allows looking up buffer guards in maps by slice.
+///
+/// This requires `T: Borrow<[U]>` rather than `AsRef<[U]>` because `Borrow` guarantees that `T`
+/// hashes and compares the same as the slice, which is required for map keys.
+impl<T, U, D> Borrow<[U]> for IntoInnerHelper<T, D> where T: Borrow<[U]>, D: DetachedDrop<Implementor=T> {
+    fn borrow(&self) -> &[U] {
+        self.inner().borrow()
+    }
+}
+
+macro_rules! forward_hasher_writes {
+    ($($method:ident($ty:ty)),*) => {
+        $(
+            fn $method(&mut self, value: $ty) {
+                self.inner_mut().$method(value)
+            }
+        )*
+    }
+}
+
+impl<T, D> Hasher for IntoInnerHelper<T, D> where T: Hasher, D: DetachedDrop<Implementor=T> {
+    fn finish(&self) -> u64 {
+        self.inner().finish()
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.inner_mut().write(bytes)
+    }
+
+    forward_hasher_writes!(
+        write_u8(u8), write_u16(u16), write_u32(u32), write_u64(u64), write_u128(u128), write_usize(usize),
+        write_i8(i8), write_i16(i16), write_i32(i32), write_i64(i64), write_i128(i128), write_isize(isize)
+    );
+}
+
+#[cfg(feature = "std")]
+impl<T, D> std::io::Read for IntoInnerHelper<T, D> where T: std::io::Read, D: DetachedDrop<Implementor=T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner_mut().read(buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [std::io::IoSliceMut<'_>]) -> std::io::Result<usize> {
+        self.inner_mut().read_vectored(bufs)
+    }
+
+    fn read_to_end(&mut self, buf: &mut std::vec::Vec<u8>) -> std::io::Result<usize> {
+        self.inner_mut().read_to_end(buf)
+    }
+
+    fn read_to_string(&mut self, buf: &mut std::string::String) -> std::io::Result<usize> {
+        self.inner_mut().read_to_string(buf)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+        self.inner_mut().read_exact(buf)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T, D> std::io::Write for IntoInnerHelper<T, D> where T: std::io::Write, D: DetachedDrop<Implementor=T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.inner_mut().write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> {
+        self.inner_mut().write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner_mut().flush()
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        self.inner_mut().write_all(buf)
+    }
+
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> std::io::Result<()> {
+        self.inner_mut().write_fmt(args)
+    }
+}
+
+#[cfg(all(feature = "std", unix))]
+impl<T, D> std::os::unix::io::AsRawFd for IntoInnerHelper<T, D> where T: std::os::unix::io::AsRawFd, D: DetachedDrop<Implementor=T> {
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        self.inner().as_raw_fd()
+    }
+}
+
+/// Borrows the file descriptor of the inner value, the detached drop still runs when the helper
+/// is dropped.
+#[cfg(all(feature = "std", unix))]
+impl<T, D> std::os::unix::io::AsFd for IntoInnerHelper<T, D> where T: std::os::unix::io::AsFd, D: DetachedDrop<Implementor=T> {
+    fn as_fd(&self) -> std::os::unix::io::BorrowedFd<'_> {
+        self.inner().as_fd()
+    }
+}
+
+/// Polls the inner future, the detached drop runs when the helper is dropped.
+///
+/// The inner future has to be `Unpin` because the drop moves it out of the helper, so pinning
+/// can't be structural. Use `Pin<Box<F>>` to guard futures that aren't `Unpin`.
+impl<T, D> Future for IntoInnerHelper<T, D> where T: Future + Unpin, D: DetachedDrop<Implementor=T> {
+    type Output = T::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(self.inner_mut()).poll(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::String;
+    use std::borrow::Cow;
+    use std::format;
+    use core::borrow::Borrow;
+    use crate::{IntoInnerHelper, DetachedDrop};
+
+    enum CountDrop {}
+
+    std::thread_local! {
+        static DROPPED: core::cell::Cell<usize> = const { core::cell::Cell::new(0) };
+    }
+
+    impl DetachedDrop for CountDrop {
+        type Implementor = String;
+
+        fn drop(_: Self::Implementor) {
+            DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+        }
+    }
+
+    #[test]
+    fn display() {
+        let helper = <IntoInnerHelper<_, CountDrop>>::new(String::from("Hello world!"));
+        assert_eq!(format!("{}", helper), "Hello world!");
+        assert_eq!(format!("{:>14}", helper), "  Hello world!");
+        assert_eq!(format!("{:?}", helper), "\"Hello world!\"");
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 0);
+        core::mem::drop(helper);
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 1);
+    }
+
+    #[test]
+    fn numeric_fmt() {
+        enum NumberDrop {}
+
+        impl DetachedDrop for NumberDrop {
+            type Implementor = u32;
+
+            fn drop(_: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+            }
+        }
+
+        let helper = <IntoInnerHelper<_, NumberDrop>>::new(42);
+        assert_eq!(format!("{:x}", helper), "2a");
+        assert_eq!(format!("{:#X}", helper), "0x2A");
+        assert_eq!(format!("{:08b}", helper), "00101010");
+        assert_eq!(format!("{:o}", helper), "52");
+        assert_eq!(format!("{:e}", helper), "4.2e1");
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 0);
+        core::mem::drop(helper);
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 1);
+    }
+
+    #[test]
+    fn compare_with_raw() {
+        enum NumberDrop {}
+
+        impl DetachedDrop for NumberDrop {
+            type Implementor = u32;
+
+            fn drop(_: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+            }
+        }
+
+        let helper = <IntoInnerHelper<_, NumberDrop>>::new(42);
+        assert!(helper < 43);
+        assert!(helper > 41);
+        assert!(helper == 42);
+        assert!(helper != 41);
+        assert!(helper <= 42);
+        assert!(helper >= 42);
+        assert!(41 < helper);
+        assert!(43 > helper);
+        assert!(42 == helper);
+        assert_eq!(helper.partial_cmp(&42), Some(core::cmp::Ordering::Equal));
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 0);
+        core::mem::drop(helper);
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn stream_in_chunks() {
+        use std::io::{Cursor, Read};
+        use std::vec::Vec;
+
+        enum Close {}
+
+        impl DetachedDrop for Close {
+            type Implementor = Cursor<&'static [u8]>;
+
+            fn drop(reader: Self::Implementor) {
+                assert_eq!(reader.position(), 11);
+                DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+            }
+        }
+
+        fn read_chunk(reader: &mut IntoInnerHelper<Cursor<&'static [u8]>, Close>, len: u64) -> Vec<u8> {
+            let mut chunk = Vec::new();
+            reader.stream().by_ref().take(len).read_to_end(&mut chunk).unwrap();
+            chunk
+        }
+
+        let mut reader = <IntoInnerHelper<_, Close>>::new(Cursor::new(&b"hello world"[..]));
+        assert_eq!(read_chunk(&mut reader, 5), b"hello");
+        assert_eq!(read_chunk(&mut reader, 1), b" ");
+        let mut rest = std::string::String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "world");
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 0);
+        core::mem::drop(reader);
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 1);
+    }
+
+    #[test]
+    #[cfg(all(feature = "std", unix))]
+    fn borrow_fd() {
+        use std::io::{Read, Write};
+        use std::os::unix::io::{AsFd, AsRawFd};
+        use std::os::unix::net::UnixStream;
+
+        enum Shutdown {}
+
+        impl DetachedDrop for Shutdown {
+            type Implementor = UnixStream;
+
+            fn drop(mut stream: Self::Implementor) {
+                stream.write_all(b"bye").unwrap();
+                DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+            }
+        }
+
+        let (stream, mut peer) = UnixStream::pair().unwrap();
+        let raw = stream.as_raw_fd();
+        let helper = <IntoInnerHelper<_, Shutdown>>::new(stream);
+        assert_eq!(helper.as_raw_fd(), raw);
+        assert_eq!(helper.as_fd().as_raw_fd(), raw);
+        let duplicate = helper.as_fd().try_clone_to_owned().unwrap();
+        assert_ne!(duplicate.as_raw_fd(), raw);
+        core::mem::drop(duplicate);
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 0);
+        core::mem::drop(helper);
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 1);
+        let mut received = std::vec::Vec::new();
+        peer.read_to_end(&mut received).unwrap();
+        assert_eq!(received, b"bye");
+    }
+
+    enum CowDrop {}
+
+    impl DetachedDrop for CowDrop {
+        type Implementor = Cow<'static, str>;
+
+        fn drop(_: Self::Implementor) {
+            DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+        }
+    }
+
+    #[test]
+    fn deref() {
+        let mut helper = <IntoInnerHelper<_, CountDrop>>::new(String::from("Hello"));
+        helper.push_str(" world!");
+        assert_eq!(helper.len(), 12);
+        assert_eq!(&*helper, "Hello world!");
+        core::mem::drop(helper);
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 1);
+    }
+
+    #[test]
+    fn cow_borrowed() {
+        let helper = <IntoInnerHelper<_, CowDrop>>::new(Cow::Borrowed("borrowed"));
+        assert_eq!(helper.as_borrowed::<str>(), "borrowed");
+        assert!(matches!(helper.into_inner(), Cow::Borrowed("borrowed")));
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 0);
+    }
+
+    #[test]
+    fn cow_owned() {
+        let helper = <IntoInnerHelper<_, CowDrop>>::new(Cow::Owned(String::from("owned")));
+        assert_eq!(helper.as_borrowed::<str>(), "owned");
+        core::mem::drop(helper);
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 1);
+    }
+
+    enum RangeDrop {}
+
+    impl DetachedDrop for RangeDrop {
+        type Implementor = core::ops::Range<u32>;
+
+        fn drop(_: Self::Implementor) {
+            DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+        }
+    }
+
+    #[test]
+    fn fused_iterator() {
+        fn assert_fused<I: core::iter::FusedIterator>(iter: I) -> I {
+            iter
+        }
+
+        let mut helper = assert_fused(<IntoInnerHelper<_, RangeDrop>>::new(0..3));
+        assert_eq!(helper.len(), 3);
+        assert_eq!(helper.next(), Some(0));
+        assert_eq!(helper.next_back(), Some(2));
+        assert_eq!(helper.next(), Some(1));
+        for _ in 0..3 {
+            assert_eq!(helper.next(), None);
+        }
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 0);
+        core::mem::drop(helper);
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 1);
+    }
+
+    #[test]
+    fn iterator_methods_forwarded() {
+        use core::cell::Cell;
+
+        // Counts calls to `next` to detect whether the specialized methods are used.
+        struct Counted<'a> {
+            range: core::ops::Range<usize>,
+            nexts: &'a Cell<usize>,
+        }
+
+        impl Iterator for Counted<'_> {
+            type Item = usize;
+
+            fn next(&mut self) -> Option<usize> {
+                self.nexts.set(self.nexts.get() + 1);
+                self.range.next()
+            }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                (self.range.len(), None)
+            }
+
+            fn nth(&mut self, n: usize) -> Option<usize> {
+                self.range.nth(n)
+            }
+        }
+
+        enum CountedDrop {}
+
+        impl DetachedDrop for CountedDrop {
+            type Implementor = Counted<'static>;
+
+            fn drop(_: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+            }
+        }
+
+        let nexts = std::boxed::Box::leak(std::boxed::Box::new(Cell::new(0)));
+        let mut helper = <IntoInnerHelper<_, CountedDrop>>::new(Counted { range: 0..10, nexts });
+        assert_eq!(helper.size_hint(), (10, None));
+        assert_eq!(helper.nth(5), Some(5));
+        assert_eq!(nexts.get(), 0);
+        assert_eq!(helper.size_hint(), helper.inner().size_hint());
+
+        let chain = (0..3).chain(5..9);
+        let expected = (chain.size_hint(), chain.clone().count(), chain.clone().last(), chain.clone().fold(0, |a, b| a * 10 + b));
+        let helper = <IntoInnerHelper<_, ChainDrop>>::new(chain.clone());
+        assert_eq!(helper.size_hint(), expected.0);
+        assert_eq!(helper.count(), expected.1);
+        assert_eq!(<IntoInnerHelper<_, ChainDrop>>::new(chain.clone()).last(), expected.2);
+        assert_eq!(<IntoInnerHelper<_, ChainDrop>>::new(chain.clone()).fold(0, |a, b| a * 10 + b), expected.3);
+        let mut helper = <IntoInnerHelper<_, ChainDrop>>::new(chain.clone());
+        assert_eq!(helper.nth_back(1), Some(7));
+        assert_eq!(helper.rfold(0, |a, b| a * 10 + b), 65210);
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 4);
+    }
+
+    enum ChainDrop {}
+
+    impl DetachedDrop for ChainDrop {
+        type Implementor = core::iter::Chain<core::ops::Range<u32>, core::ops::Range<u32>>;
+
+        fn drop(_: Self::Implementor) {
+            DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+        }
+    }
+
+    enum HasherDrop {}
+
+    impl DetachedDrop for HasherDrop {
+        type Implementor = std::collections::hash_map::DefaultHasher;
+
+        fn drop(_: Self::Implementor) {
+            DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+        }
+    }
+
+    #[test]
+    fn hasher() {
+        use core::hash::{Hash, Hasher};
+        use std::collections::hash_map::DefaultHasher;
+
+        let mut expected = DefaultHasher::new();
+        let mut helper = <IntoInnerHelper<_, HasherDrop>>::new(DefaultHasher::new());
+        for hasher in [&mut expected as &mut dyn Hasher, &mut helper] {
+            hasher.write(b"Hello world!");
+            hasher.write_u32(42);
+            "string".hash(&mut &mut *hasher);
+        }
+        assert_eq!(helper.finish(), expected.finish());
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 0);
+        core::mem::drop(helper);
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 1);
+    }
+
+    #[test]
+    fn hash_same_as_inner() {
+        use core::hash::{BuildHasher, Hash};
+        use std::collections::hash_map::RandomState;
+
+        struct Forget<T>(core::convert::Infallible, core::marker::PhantomData<T>);
+
+        impl<T> DetachedDrop for Forget<T> {
+            type Implementor = T;
+
+            fn drop(_: Self::Implementor) {}
+        }
+
+        fn check<T: Hash + Clone>(values: &[T]) {
+            let state = RandomState::new();
+            for value in values {
+                let expected = state.hash_one(value);
+                let mut helper = <IntoInnerHelper<_, Forget<T>>>::new(value.clone());
+                assert_eq!(state.hash_one(&helper), expected);
+                let _ = helper.inner_mut();
+                assert_eq!(state.hash_one(&helper), expected);
+                assert_eq!(state.hash_one(helper.into_inner()), expected);
+            }
+        }
+
+        check(&[0u8, 1, 42, u8::MAX]);
+        check(&[i64::MIN, -1, 0, 1, i64::MAX]);
+        check(&["", "a", "Hello world!"].map(String::from));
+        check(&[std::vec![], std::vec![1u16], std::vec![1, 2, 3]]);
+        check(&[(1, "a"), (2, "b")]);
+        check(&[None, Some('x')]);
+    }
+
+    enum BufferDrop {}
+
+    impl DetachedDrop for BufferDrop {
+        type Implementor = std::vec::Vec<u8>;
+
+        fn drop(_: Self::Implementor) {
+            DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+        }
+    }
+
+    #[test]
+    fn index_and_iterate() {
+        let mut helper = <IntoInnerHelper<_, BufferDrop>>::new(b"abc".to_vec());
+        helper[0] = b'A';
+        assert_eq!(&helper[..2], b"Ab");
+        assert_eq!((&helper).into_iter().copied().collect::<std::vec::Vec<_>>(), b"Abc");
+        core::mem::drop(helper);
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 1);
+    }
+
+    #[test]
+    fn map_lookup_by_slice() {
+        use std::collections::{HashMap, BTreeSet};
+
+        let mut map = HashMap::new();
+        map.insert(<IntoInnerHelper<_, BufferDrop>>::new(b"foo".to_vec()), 1);
+        map.insert(<IntoInnerHelper<_, BufferDrop>>::new(b"bar".to_vec()), 2);
+        assert_eq!(map.get(&b"foo"[..]), Some(&1));
+        assert_eq!(map.get(&b"bar"[..]), Some(&2));
+        assert_eq!(map.get(&b"baz"[..]), None);
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 0);
+        let (key, _) = map.remove_entry(&b"foo"[..]).unwrap();
+        assert_eq!(key, <IntoInnerHelper<_, BufferDrop>>::new(b"foo".to_vec()));
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 1);
+        core::mem::drop(key);
+        core::mem::drop(map);
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 3);
+
+        let mut set = BTreeSet::new();
+        set.insert(b"foo".to_vec());
+        let key = <IntoInnerHelper<_, BufferDrop>>::new(b"foo".to_vec());
+        assert!(set.contains::<[u8]>(key.borrow()));
+    }
+
+    struct Countdown(u32);
+
+    impl core::future::Future for Countdown {
+        type Output = &'static str;
+
+        fn poll(mut self: core::pin::Pin<&mut Self>, cx: &mut core::task::Context<'_>) -> core::task::Poll<Self::Output> {
+            if self.0 == 0 {
+                core::task::Poll::Ready("done")
+            } else {
+                self.0 -= 1;
+                cx.waker().wake_by_ref();
+                core::task::Poll::Pending
+            }
+        }
+    }
+
+    enum CountdownDrop {}
+
+    impl DetachedDrop for CountdownDrop {
+        type Implementor = Countdown;
+
+        fn drop(countdown: Self::Implementor) {
+            assert_eq!(countdown.0, 0);
+            DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+        }
+    }
+
+    #[test]
+    fn future() {
+        use core::future::Future;
+        use core::task::{Context, Poll, Waker};
+
+        let mut helper = <IntoInnerHelper<_, CountdownDrop>>::new(Countdown(2));
+        let mut cx = Context::from_waker(Waker::noop());
+        assert_eq!(core::pin::Pin::new(&mut helper).poll(&mut cx), Poll::Pending);
+        assert_eq!(core::pin::Pin::new(&mut helper).poll(&mut cx), Poll::Pending);
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 0);
+        assert_eq!(core::pin::Pin::new(&mut helper).poll(&mut cx), Poll::Ready("done"));
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 0);
+        core::mem::drop(helper);
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), 1);
+    }
+}
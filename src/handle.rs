@@ -0,0 +1,127 @@
+use core::ptr::NonNull;
+use crate::{IntoInnerHelper, DetachedDrop};
+
+/// A helper owning a raw FFI handle, freeing it on drop.
+///
+/// `H` is the pointee of the handle. It may be unsized, so opaque types declared for FFI work
+/// regardless of how they are modeled. The free function is typically a non-capturing closure
+/// calling the `free`/`close` function of the C library. `into_inner` returns the raw handle
+/// without freeing it.
+///
+/// # Example
+///
+/// ```
+/// use core::ptr::NonNull;
+/// use into_inner_drop::HandleHelper;
+///
+/// #[repr(C)]
+/// struct Connection { _private: [u8; 0] }
+///
+/// # unsafe fn connection_open() -> *mut Connection { NonNull::dangling().as_ptr() }
+/// # unsafe fn connection_close(_: *mut Connection) {}
+/// let raw = NonNull::new(unsafe { connection_open() }).expect("failed to connect");
+/// let connection = HandleHelper::new(raw, |handle| unsafe { connection_close(handle.as_ptr()) });
+/// // connection_close is called when `connection` goes out of scope
+/// ```
+pub struct HandleHelper<H> where H: ?Sized {
+    inner: IntoInnerHelper<Handle<H>, FreeHandle<H>>,
+}
+
+struct Handle<H> where H: ?Sized {
+    handle: NonNull<H>,
+    free: fn(NonNull<H>),
+}
+
+struct FreeHandle<H>(core::convert::Infallible, core::marker::PhantomData<fn(NonNull<H>)>) where H: ?Sized;
+
+impl<H> DetachedDrop for FreeHandle<H> where H: ?Sized {
+    type Implementor = Handle<H>;
+
+    fn drop(value: Self::Implementor) {
+        (value.free)(value.handle);
+    }
+}
+
+impl<H> HandleHelper<H> where H: ?Sized {
+    /// Creates the helper calling `free` with the handle when dropped.
+    pub fn new(handle: NonNull<H>, free: fn(NonNull<H>)) -> Self {
+        HandleHelper {
+            inner: IntoInnerHelper::new(Handle { handle, free, }),
+        }
+    }
+
+    /// Returns the raw handle without giving up ownership.
+    pub fn handle(&self) -> NonNull<H> {
+        self.inner.inner().handle
+    }
+
+    /// Returns the raw handle as a pointer, e.g. to pass it to FFI functions.
+    pub fn as_ptr(&self) -> *mut H {
+        self.handle().as_ptr()
+    }
+
+    /// Moves out the raw handle without freeing it.
+    pub fn into_inner(self) -> NonNull<H> {
+        self.inner.into_inner().handle
+    }
+}
+
+impl<H> core::fmt::Debug for HandleHelper<H> where H: ?Sized {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("HandleHelper").field(&self.handle()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ptr::NonNull;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use super::HandleHelper;
+
+    // The fake library counts the opened handles.
+    static OPEN: AtomicUsize = AtomicUsize::new(0);
+
+    #[repr(C)]
+    struct Opaque {
+        _private: [u8; 0],
+    }
+
+    fn open() -> NonNull<Opaque> {
+        OPEN.fetch_add(1, Ordering::Relaxed);
+        NonNull::from(std::boxed::Box::leak(std::boxed::Box::new(Opaque { _private: [] })))
+    }
+
+    fn close(handle: NonNull<Opaque>) {
+        OPEN.fetch_sub(1, Ordering::Relaxed);
+        unsafe { core::mem::drop(std::boxed::Box::from_raw(handle.as_ptr())) };
+    }
+
+    // The counter is global, so all uses of it are in a single test to avoid races.
+    #[test]
+    fn free_on_drop_and_extract() {
+        let helper = HandleHelper::new(open(), close);
+        assert_eq!(OPEN.load(Ordering::Relaxed), 1);
+        assert_eq!(helper.as_ptr(), helper.handle().as_ptr());
+        core::mem::drop(helper);
+        assert_eq!(OPEN.load(Ordering::Relaxed), 0);
+
+        let helper = HandleHelper::new(open(), close);
+        let raw = helper.into_inner();
+        assert_eq!(OPEN.load(Ordering::Relaxed), 1);
+        close(raw);
+        assert_eq!(OPEN.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn unsized_handle() {
+        std::thread_local! {
+            static FREED: core::cell::Cell<usize> = const { core::cell::Cell::new(0) };
+        }
+
+        let mut values = [1u8, 2, 3];
+        let helper = HandleHelper::new(NonNull::from(&mut values[..]), |handle| FREED.with(|freed| freed.set(handle.len())));
+        assert_eq!(helper.handle().len(), 3);
+        core::mem::drop(helper);
+        assert_eq!(FREED.with(core::cell::Cell::get), 3);
+    }
+}
@@ -0,0 +1,114 @@
+//! Helper methods specific to guards over `heapless` collections.
+
+use heapless::{Vec, String, LenType};
+use crate::{IntoInnerHelper, DetachedDrop};
+
+impl<U, const N: usize, L, D> IntoInnerHelper<Vec<U, N, L>, D> where L: LenType, D: DetachedDrop<Implementor=Vec<U, N, L>> {
+    /// Returns the number of elements.
+    pub fn len(&self) -> usize {
+        self.inner().len()
+    }
+
+    /// Returns `true` if there are no elements.
+    pub fn is_empty(&self) -> bool {
+        self.inner().is_empty()
+    }
+
+    /// Returns the maximum number of elements the vector can hold.
+    pub fn capacity(&self) -> usize {
+        self.inner().capacity()
+    }
+
+    /// Appends an element, returning it back if the vector is full.
+    pub fn push(&mut self, value: U) -> Result<(), U> {
+        self.inner_mut().push(value)
+    }
+
+    /// Removes all elements. The detached drop later receives the empty vector.
+    pub fn clear(&mut self) {
+        self.inner_mut().clear()
+    }
+}
+
+impl<const N: usize, L, D> IntoInnerHelper<String<N, L>, D> where L: LenType, D: DetachedDrop<Implementor=String<N, L>> {
+    /// Returns the length in bytes.
+    pub fn len(&self) -> usize {
+        self.inner().len()
+    }
+
+    /// Returns `true` if the string is empty.
+    pub fn is_empty(&self) -> bool {
+        self.inner().is_empty()
+    }
+
+    /// Returns the maximum number of bytes the string can hold.
+    pub fn capacity(&self) -> usize {
+        self.inner().capacity()
+    }
+
+    /// Appends a string slice, failing without changing the string if it doesn't fit.
+    pub fn push_str(&mut self, string: &str) -> Result<(), heapless::CapacityError> {
+        self.inner_mut().push_str(string)
+    }
+
+    /// Truncates the string to zero length. The detached drop later receives the empty string.
+    pub fn clear(&mut self) {
+        self.inner_mut().clear()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use heapless::{Vec, String};
+    use crate::{IntoInnerHelper, DetachedDrop};
+
+    std::thread_local! {
+        static DROPPED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    enum CountDrop {}
+
+    impl DetachedDrop for CountDrop {
+        type Implementor = Vec<u8, 4>;
+
+        fn drop(_: Self::Implementor) {
+            DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+        }
+    }
+
+    #[test]
+    fn vec_forwarding() {
+        let mut helper = <IntoInnerHelper<_, CountDrop>>::new(Vec::new());
+        assert!(helper.is_empty());
+        assert_eq!(helper.capacity(), 4);
+        for i in 0..4 {
+            helper.push(i).unwrap();
+        }
+        assert_eq!(helper.push(4), Err(4));
+        assert_eq!(helper.len(), 4);
+        helper.clear();
+        assert!(helper.is_empty());
+        core::mem::drop(helper);
+        assert_eq!(DROPPED.with(Cell::get), 1);
+    }
+
+    #[test]
+    fn string_forwarding() {
+        enum Noop {}
+
+        impl DetachedDrop for Noop {
+            type Implementor = String<8>;
+
+            fn drop(_: Self::Implementor) {}
+        }
+
+        let mut helper = <IntoInnerHelper<_, Noop>>::new(String::new());
+        helper.push_str("hello").unwrap();
+        assert!(helper.push_str(" world").is_err());
+        assert_eq!(helper.len(), 5);
+        assert_eq!(helper.capacity(), 8);
+        helper.clear();
+        assert!(helper.is_empty());
+    }
+}
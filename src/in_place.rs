@@ -0,0 +1,142 @@
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+
+/// The inner value of a helper being dropped, passed to `DetachedDrop::drop_in_place`.
+///
+/// The value can be accessed through `Deref` and `DerefMut` without moving it. Unless it's moved
+/// out using `take`, it's dropped in place when `InPlace` is dropped.
+pub struct InPlace<'a, T> {
+    value: &'a mut ManuallyDrop<T>,
+}
+
+impl<'a, T> InPlace<'a, T> {
+    /// Wraps the value.
+    ///
+    /// # Safety
+    ///
+    /// The value must be initialized and the caller must not use or drop it afterwards.
+    pub(crate) unsafe fn new(value: &'a mut ManuallyDrop<T>) -> Self {
+        InPlace { value, }
+    }
+
+    /// Moves the value out.
+    pub fn take(self) -> T {
+        let this = ManuallyDrop::new(self);
+        // SAFETY: the value is initialized and `this` never drops it.
+        unsafe {
+            core::ptr::read(&**this.value)
+        }
+    }
+
+    /// Narrows the value down to a part of it, e.g. a field, which is then dropped in place.
+    ///
+    /// # Safety
+    ///
+    /// The rest of the value must not need to be dropped or it must have been moved out before.
+    pub(crate) unsafe fn project<U, F>(self, f: F) -> InPlace<'a, U> where F: FnOnce(&'a mut T) -> &'a mut U {
+        let this = ManuallyDrop::new(self);
+        // SAFETY: `this` is never used again, so the reference is moved out of it.
+        let value = unsafe { core::ptr::read(&this.value) };
+        let part = f(&mut **value);
+        // SAFETY: `ManuallyDrop<U>` has the same layout as `U`, the part is initialized and, as
+        // promised by the caller of `new`, nobody else uses or drops it.
+        unsafe { InPlace::new(&mut *(part as *mut U as *mut ManuallyDrop<U>)) }
+    }
+}
+
+impl<T> Deref for InPlace<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+impl<T> DerefMut for InPlace<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.value
+    }
+}
+
+impl<T> Drop for InPlace<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: the value is initialized and nobody uses it afterwards, as promised by `new`.
+        unsafe {
+            ManuallyDrop::drop(self.value)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use crate::{IntoInnerHelper, DetachedDrop, InPlace};
+
+    std::thread_local! {
+        static IN_PLACE: Cell<Option<*const u8>> = const { Cell::new(None) };
+    }
+
+    struct Buffer {
+        bytes: [u8; 4096],
+        _token: dropcheck::DropToken,
+    }
+
+    enum ZeroBuffer {}
+
+    impl DetachedDrop for ZeroBuffer {
+        type Implementor = Buffer;
+
+        fn drop(_: Self::Implementor) {
+            panic!("drop_in_place is overriden");
+        }
+
+        fn drop_in_place(mut buffer: InPlace<'_, Self::Implementor>) {
+            buffer.bytes = [0; 4096];
+            assert!(IN_PLACE.with(|in_place| in_place.replace(Some(buffer.bytes.as_ptr()))).is_none());
+        }
+    }
+
+    #[test]
+    fn runs_once_without_moving() {
+        let check = dropcheck::DropCheck::new();
+        let (token, state) = check.pair();
+        let address = {
+            // Dropped at the end of the scope, passing it to `drop` would move it.
+            let helper = <IntoInnerHelper<_, ZeroBuffer>>::new(Buffer { bytes: [42; 4096], _token: token, });
+            helper.inner().bytes.as_ptr()
+        };
+        assert_eq!(IN_PLACE.with(Cell::get), Some(address));
+        assert!(state.is_dropped());
+    }
+
+    #[test]
+    fn into_inner_unaffected() {
+        let check = dropcheck::DropCheck::new();
+        let (token, state) = check.pair();
+        let helper = <IntoInnerHelper<_, ZeroBuffer>>::new(Buffer { bytes: [42; 4096], _token: token, });
+        let buffer = helper.into_inner();
+        assert_eq!(buffer.bytes[0], 42);
+        assert!(state.is_not_dropped());
+        assert_eq!(IN_PLACE.with(Cell::get), None);
+    }
+
+    #[test]
+    fn default_calls_drop() {
+        std::thread_local! {
+            static DROPPED: Cell<usize> = const { Cell::new(0) };
+        }
+
+        enum CountDrop {}
+
+        impl DetachedDrop for CountDrop {
+            type Implementor = u32;
+
+            fn drop(_: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+            }
+        }
+
+        core::mem::drop(<IntoInnerHelper<_, CountDrop>>::new(42));
+        assert_eq!(DROPPED.with(Cell::get), 1);
+    }
+}
@@ -0,0 +1,143 @@
+use core::iter::FusedIterator;
+use crate::{EmptiableHelper, DetachedDrop};
+use crate::unwind::detached_drop;
+
+/// An iterator running the detached drop as soon as the inner iterator is exhausted.
+///
+/// This is useful for streaming resources that should be closed right after the last item was
+/// read rather than at the end of the scope. If the iterator is dropped before being exhausted,
+/// the detached drop runs then. Once exhausted, the iterator keeps returning `None`.
+pub struct FinalizingIter<I, D> where D: DetachedDrop<Implementor=I> {
+    inner: EmptiableHelper<I, D>,
+}
+
+impl<I, D> FinalizingIter<I, D> where I: Iterator, D: DetachedDrop<Implementor=I> {
+    /// Creates the iterator.
+    pub fn new(inner: I) -> Self {
+        FinalizingIter {
+            inner: EmptiableHelper::new(inner),
+        }
+    }
+
+    /// Accesses the inner iterator, returning `None` if it was already exhausted and finalized.
+    pub fn inner(&self) -> Option<&I> {
+        self.inner.inner()
+    }
+
+    /// Returns `true` if the inner iterator was exhausted and the detached drop ran.
+    pub fn is_finalized(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Moves out the inner iterator, preventing the detached drop.
+    ///
+    /// Returns `None` if the iterator was already exhausted and finalized.
+    pub fn into_inner(self) -> Option<I> {
+        if self.is_finalized() {
+            None
+        } else {
+            self.inner.into_inner()
+        }
+    }
+}
+
+impl<I, D> Iterator for FinalizingIter<I, D> where I: Iterator, D: DetachedDrop<Implementor=I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.inner.inner_mut()?.next();
+        if item.is_none() {
+            if let Some(inner) = self.inner.take() {
+                detached_drop::<D>(inner);
+            }
+        }
+        item
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner().map_or((0, Some(0)), Iterator::size_hint)
+    }
+}
+
+impl<I, D> FusedIterator for FinalizingIter<I, D> where I: Iterator, D: DetachedDrop<Implementor=I> {}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use core::ops::Range;
+    use super::FinalizingIter;
+    use crate::DetachedDrop;
+
+    std::thread_local! {
+        static CLOSED: Cell<Option<Range<u32>>> = const { Cell::new(None) };
+    }
+
+    fn closed() -> Option<Range<u32>> {
+        CLOSED.with(Cell::take)
+    }
+
+    enum Close {}
+
+    impl DetachedDrop for Close {
+        type Implementor = Range<u32>;
+
+        fn drop(range: Self::Implementor) {
+            CLOSED.with(|closed| closed.set(Some(range)));
+        }
+    }
+
+    #[test]
+    fn exhaustion_finalizes_early() {
+        let mut iter = <FinalizingIter<_, Close>>::new(0..3);
+        assert_eq!(iter.by_ref().sum::<u32>(), 3);
+        assert!(iter.is_finalized());
+        assert_eq!(closed(), Some(3..3));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.size_hint(), (0, Some(0)));
+        core::mem::drop(iter);
+        assert_eq!(closed(), None);
+    }
+
+    #[test]
+    fn partial_iteration_finalizes_at_scope_end() {
+        let mut iter = <FinalizingIter<_, Close>>::new(0..3);
+        assert_eq!(iter.next(), Some(0));
+        assert_eq!(iter.size_hint(), (2, Some(2)));
+        assert_eq!(closed(), None);
+        core::mem::drop(iter);
+        assert_eq!(closed(), Some(1..3));
+    }
+
+    #[test]
+    fn into_inner() {
+        let mut iter = <FinalizingIter<_, Close>>::new(0..3);
+        assert_eq!(iter.next(), Some(0));
+        assert_eq!(iter.into_inner(), Some(1..3));
+        assert_eq!(closed(), None);
+
+        let mut iter = <FinalizingIter<_, Close>>::new(0..0);
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.into_inner(), None);
+        assert_eq!(closed(), Some(0..0));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unwinding() {
+        use crate::unwind::tests::{Record, SkipOnPanic, drop_while_panicking};
+
+        assert_eq!(drop_while_panicking(<FinalizingIter<_, Record<Range<u32>>>>::new(0..1)), ["drop_panicking"]);
+        assert!(drop_while_panicking(<FinalizingIter<_, SkipOnPanic<Range<u32>>>>::new(0..1)).is_empty());
+
+        struct Exhaust(FinalizingIter<Range<u32>, Record<Range<u32>>>);
+
+        impl Drop for Exhaust {
+            fn drop(&mut self) {
+                assert_eq!(self.0.next(), None);
+                assert!(self.0.is_finalized());
+            }
+        }
+
+        assert_eq!(drop_while_panicking(Exhaust(FinalizingIter::new(0..0))), ["drop_panicking"]);
+    }
+}
@@ -0,0 +1,201 @@
+//! A ring buffer of recent drop events, enabled by the `journal` feature.
+//!
+//! `LabeledHelper` records every detached drop and extraction into a global journal that can be
+//! inspected after the fact, e.g. from a debugger or a panic handler on targets without logging.
+//! The journal only uses `core`, so it's available in `no_std` builds.
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// The number of events kept by the global journal returned by `drop_journal`.
+pub const JOURNAL_CAPACITY: usize = 32;
+
+/// How many times `record` tries to acquire a busy journal before discarding the event.
+const RECORD_ATTEMPTS: usize = 64;
+
+static JOURNAL: DropJournal<JOURNAL_CAPACITY> = DropJournal::new();
+
+/// Returns the global journal recording events of `LabeledHelper`.
+pub fn drop_journal() -> &'static DropJournal<JOURNAL_CAPACITY> {
+    &JOURNAL
+}
+
+/// What happened to a guard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropEventKind {
+    /// The detached drop ran.
+    Dropped,
+    /// The inner value was extracted without running the detached drop.
+    Extracted,
+}
+
+/// A single event recorded in `DropJournal`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DropEvent {
+    label: &'static str,
+    kind: DropEventKind,
+}
+
+impl DropEvent {
+    /// Creates the event.
+    pub const fn new(label: &'static str, kind: DropEventKind) -> Self {
+        DropEvent { label, kind, }
+    }
+
+    /// Returns the label of the guard.
+    pub fn label(&self) -> &'static str {
+        self.label
+    }
+
+    /// Returns what happened to the guard.
+    pub fn kind(&self) -> DropEventKind {
+        self.kind
+    }
+}
+
+/// A fixed-size ring buffer keeping the last `N` drop events.
+///
+/// Recording never blocks indefinitely: if the journal stays busy for a few attempts, e.g. because
+/// it's being accessed by the code an interrupt handler interrupted, the event is discarded
+/// instead, so recording can't deadlock.
+pub struct DropJournal<const N: usize> {
+    locked: AtomicBool,
+    ring: UnsafeCell<Ring<N>>,
+}
+
+struct Ring<const N: usize> {
+    events: [Option<DropEvent>; N],
+    next: usize,
+}
+
+// SAFETY: `ring` is only accessed while `locked` is held.
+unsafe impl<const N: usize> Sync for DropJournal<N> {}
+
+impl<const N: usize> DropJournal<N> {
+    /// Creates an empty journal.
+    pub const fn new() -> Self {
+        DropJournal {
+            locked: AtomicBool::new(false),
+            ring: UnsafeCell::new(Ring { events: [None; N], next: 0, }),
+        }
+    }
+
+    /// Records the event, overwriting the oldest one if the journal is full.
+    ///
+    /// Returns `false` if the event was discarded because the journal was busy.
+    pub fn record(&self, event: DropEvent) -> bool {
+        for _ in 0..RECORD_ATTEMPTS {
+            let recorded = self.try_with(|ring| {
+                if N > 0 {
+                    ring.events[ring.next] = Some(event);
+                    ring.next = (ring.next + 1) % N;
+                }
+            });
+            if recorded.is_some() {
+                return true;
+            }
+            core::hint::spin_loop();
+        }
+        false
+    }
+
+    /// Returns the recorded events, oldest first.
+    pub fn events(&self) -> impl Iterator<Item=DropEvent> {
+        let events = self.with(|ring| {
+            let mut events = ring.events;
+            events.rotate_left(ring.next);
+            events
+        });
+        // Unused slots are at the start until the journal wraps around for the first time.
+        IntoIterator::into_iter(events).flatten()
+    }
+
+    /// Removes all recorded events.
+    pub fn clear(&self) {
+        self.with(|ring| *ring = Ring { events: [None; N], next: 0, })
+    }
+
+    fn try_with<R>(&self, f: impl FnOnce(&mut Ring<N>) -> R) -> Option<R> {
+        if self.locked.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            return None;
+        }
+        // SAFETY: the lock is held, so no other reference to the ring exists.
+        let result = f(unsafe { &mut *self.ring.get() });
+        self.locked.store(false, Ordering::Release);
+        Some(result)
+    }
+
+    fn with<R>(&self, mut f: impl FnMut(&mut Ring<N>) -> R) -> R {
+        loop {
+            if let Some(result) = self.try_with(&mut f) {
+                return result;
+            }
+            core::hint::spin_loop();
+        }
+    }
+}
+
+impl<const N: usize> Default for DropJournal<N> {
+    fn default() -> Self {
+        DropJournal::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+    use super::{DropJournal, DropEvent, DropEventKind};
+
+    fn labels<const N: usize>(journal: &DropJournal<N>) -> Vec<&'static str> {
+        journal.events().map(|event| event.label()).collect()
+    }
+
+    #[test]
+    fn fill_and_wrap() {
+        let journal = DropJournal::<3>::new();
+        assert_eq!(journal.events().count(), 0);
+        assert!(journal.record(DropEvent::new("first", DropEventKind::Dropped)));
+        assert!(journal.record(DropEvent::new("second", DropEventKind::Extracted)));
+        assert_eq!(labels(&journal), ["first", "second"]);
+        journal.record(DropEvent::new("third", DropEventKind::Dropped));
+        journal.record(DropEvent::new("fourth", DropEventKind::Dropped));
+        journal.record(DropEvent::new("fifth", DropEventKind::Extracted));
+        assert_eq!(labels(&journal), ["third", "fourth", "fifth"]);
+        assert_eq!(journal.events().map(|event| event.kind()).collect::<Vec<_>>(), [DropEventKind::Dropped, DropEventKind::Dropped, DropEventKind::Extracted]);
+        journal.clear();
+        assert_eq!(journal.events().count(), 0);
+    }
+
+    #[test]
+    fn zero_capacity() {
+        let journal = DropJournal::<0>::new();
+        assert!(journal.record(DropEvent::new("ignored", DropEventKind::Dropped)));
+        assert_eq!(journal.events().count(), 0);
+    }
+
+    #[test]
+    fn busy_journal_discards() {
+        let journal = DropJournal::<2>::new();
+        let recorded = journal.try_with(|_| journal.record(DropEvent::new("nested", DropEventKind::Dropped)));
+        assert_eq!(recorded, Some(false));
+        assert_eq!(journal.events().count(), 0);
+    }
+
+    #[test]
+    fn labeled_helper_records() {
+        use crate::{LabeledHelper, DetachedDrop};
+
+        enum Noop {}
+
+        impl DetachedDrop for Noop {
+            type Implementor = ();
+
+            fn drop(_: Self::Implementor) {}
+        }
+
+        core::mem::drop(<LabeledHelper<_, Noop>>::new((), "journal-dropped"));
+        <LabeledHelper<_, Noop>>::new((), "journal-extracted").into_inner();
+        let events = super::drop_journal().events().filter(|event| event.label().starts_with("journal-")).collect::<Vec<_>>();
+        assert_eq!(events, [DropEvent::new("journal-dropped", DropEventKind::Dropped), DropEvent::new("journal-extracted", DropEventKind::Extracted)]);
+    }
+}
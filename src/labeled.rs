@@ -0,0 +1,229 @@
+use core::fmt;
+use crate::{IntoInnerHelper, DropBomb, DetachedDrop, InPlace};
+use crate::unwind::{detached_drop, detached_drop_in_place};
+
+/// A helper carrying a label for diagnostics.
+///
+/// The label is included in the `Debug` output, in the panic message of `DropBomb` created by
+/// `into_bomb` and, with the `log` feature, in log records emitted when the helper is finalized or
+/// the inner value is extracted. With the `journal` feature these events are also recorded in
+/// `drop_journal`. This is a separate type to keep `IntoInnerHelper` zero-cost.
+pub struct LabeledHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    inner: IntoInnerHelper<Labeled<T>, LabeledDrop<D>>,
+}
+
+struct Labeled<T> {
+    value: T,
+    label: &'static str,
+}
+
+impl<T> Labeled<T> {
+    /// Reports that the detached drop is about to run.
+    fn report_drop(&self) {
+        #[cfg(feature = "log")]
+        log::debug!(target: "into_inner_drop", "{}: running detached drop", self.label);
+        #[cfg(feature = "journal")]
+        crate::journal::drop_journal().record(crate::journal::DropEvent::new(self.label, crate::journal::DropEventKind::Dropped));
+    }
+}
+
+struct LabeledDrop<D>(core::convert::Infallible, core::marker::PhantomData<D>);
+
+impl<D> DetachedDrop for LabeledDrop<D> where D: DetachedDrop {
+    type Implementor = Labeled<D::Implementor>;
+
+    // `RUN_ON_PANIC` is left `true` so the event is logged during unwinding too,
+    // `detached_drop` respects `D::RUN_ON_PANIC`.
+
+    fn drop(value: Self::Implementor) {
+        value.report_drop();
+        detached_drop::<D>(value.value);
+    }
+
+    #[cfg(feature = "std")]
+    fn drop_during_panic(value: Self::Implementor, context: &crate::PanicContext, token: crate::HookToken) {
+        value.report_drop();
+        crate::unwind::detached_drop_during_panic::<D>(value.value, context, token);
+    }
+
+    fn drop_in_place(value: InPlace<'_, Self::Implementor>) {
+        value.report_drop();
+        // SAFETY: the label doesn't need to be dropped.
+        detached_drop_in_place::<D>(unsafe { value.project(|value| &mut value.value) });
+    }
+}
+
+impl<T, D> LabeledHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    /// Creates the helper with the given label.
+    pub fn new(inner: T, label: &'static str) -> Self {
+        LabeledHelper {
+            inner: IntoInnerHelper::new(Labeled { value: inner, label, }),
+        }
+    }
+
+    /// Returns the label of the helper.
+    pub fn label(&self) -> &'static str {
+        self.inner.inner().label
+    }
+
+    /// Accesses the inner value.
+    pub fn inner(&self) -> &T {
+        &self.inner.inner().value
+    }
+
+    /// Accesses the inner value mutably.
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner.inner_mut().value
+    }
+
+    /// Moves out the inner value.
+    pub fn into_inner(self) -> T {
+        let inner = self.inner.into_inner();
+        #[cfg(feature = "log")]
+        log::debug!(target: "into_inner_drop", "{}: extracted inner value", inner.label);
+        #[cfg(feature = "journal")]
+        crate::journal::drop_journal().record(crate::journal::DropEvent::new(inner.label, crate::journal::DropEventKind::Extracted));
+        inner.value
+    }
+
+    /// Converts the helper into a `DropBomb` with the same label.
+    #[track_caller]
+    pub fn into_bomb(self) -> DropBomb<T, D> {
+        let inner = self.inner.into_inner();
+        DropBomb::new(inner.value, inner.label)
+    }
+}
+
+impl<T, D> fmt::Debug for LabeledHelper<T, D> where T: fmt::Debug, D: DetachedDrop<Implementor=T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LabeledHelper")
+            .field("label", &self.label())
+            .field("inner", self.inner())
+            .finish()
+    }
+}
+
+impl<T, D> crate::Finalize for LabeledHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    type Output = ();
+
+    fn finalize(self) {
+        core::mem::drop(self)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use core::cell::Cell;
+    use super::LabeledHelper;
+    use crate::DetachedDrop;
+
+    std::thread_local! {
+        static DROPPED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    fn dropped() -> usize {
+        DROPPED.with(Cell::get)
+    }
+
+    enum CountDrop {}
+
+    impl DetachedDrop for CountDrop {
+        type Implementor = u32;
+
+        fn drop(_: Self::Implementor) {
+            DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+        }
+    }
+
+    #[test]
+    fn debug_contains_label() {
+        let helper = <LabeledHelper<_, CountDrop>>::new(42, "answer-guard");
+        assert_eq!(std::format!("{:?}", helper), "LabeledHelper { label: \"answer-guard\", inner: 42 }");
+        core::mem::drop(helper);
+        assert_eq!(dropped(), 1);
+    }
+
+    #[test]
+    fn into_inner() {
+        let mut helper = <LabeledHelper<_, CountDrop>>::new(42, "answer-guard");
+        *helper.inner_mut() += 1;
+        assert_eq!(helper.into_inner(), 43);
+        assert_eq!(dropped(), 0);
+    }
+
+    #[test]
+    #[cfg(any(debug_assertions, feature = "release-bomb"))]
+    #[should_panic(expected = "DropBomb `db-connection-guard` was dropped without being finalized")]
+    fn bomb_panic_contains_label() {
+        let helper = <LabeledHelper<_, CountDrop>>::new(42, "db-connection-guard");
+        core::mem::drop(helper.into_bomb());
+    }
+
+    #[cfg(feature = "log")]
+    pub(crate) mod logger {
+        use std::sync::{Mutex, Once};
+        use std::vec::Vec;
+        use std::string::{String, ToString};
+
+        static RECORDS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+        struct Capture;
+
+        impl log::Log for Capture {
+            fn enabled(&self, _: &log::Metadata<'_>) -> bool {
+                true
+            }
+
+            fn log(&self, record: &log::Record<'_>) {
+                RECORDS.lock().unwrap().push(record.args().to_string());
+            }
+
+            fn flush(&self) {}
+        }
+
+        /// Installs the capturing logger and returns all records mentioning `label` so far.
+        pub(crate) fn records(label: &str) -> Vec<String> {
+            static INIT: Once = Once::new();
+
+            INIT.call_once(|| {
+                log::set_logger(&Capture).unwrap();
+                log::set_max_level(log::LevelFilter::Trace);
+            });
+            RECORDS.lock().unwrap().iter().filter(|record| record.contains(label)).cloned().collect()
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "log")]
+    fn log_contains_label() {
+        logger::records("");
+        let helper = <LabeledHelper<_, CountDrop>>::new(42, "logged-drop-guard");
+        core::mem::drop(helper);
+        let helper = <LabeledHelper<_, CountDrop>>::new(42, "logged-extract-guard");
+        helper.into_inner();
+        assert_eq!(logger::records("logged-drop-guard"), ["logged-drop-guard: running detached drop"]);
+        assert_eq!(logger::records("logged-extract-guard"), ["logged-extract-guard: extracted inner value"]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unwinding() {
+        use crate::unwind::tests::{Record, SkipOnPanic, drop_while_panicking};
+
+        assert_eq!(drop_while_panicking(<LabeledHelper<_, Record>>::new(42, "connection")), ["drop_panicking"]);
+        assert!(drop_while_panicking(<LabeledHelper<_, SkipOnPanic>>::new(42, "connection")).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn drop_in_place() {
+        use crate::unwind::tests::{InPlaceRecord, called, in_place_address};
+
+        let address = {
+            let helper = <LabeledHelper<_, InPlaceRecord>>::new(42, "connection");
+            helper.inner() as *const u32
+        };
+        assert_eq!(called(), ["drop_in_place"]);
+        assert_eq!(in_place_address(), Some(address));
+    }
+}
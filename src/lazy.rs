@@ -0,0 +1,193 @@
+use core::cell::{Cell, OnceCell};
+use core::ops::{Deref, DerefMut};
+use crate::{IntoInnerHelper, DetachedDrop, InPlace};
+use crate::unwind::{detached_drop, detached_drop_in_place};
+
+/// A helper computing the inner value on first access.
+///
+/// The value is computed by `F` when the helper is dereferenced for the first time. The detached
+/// drop only runs if the value was computed. If `F` panics, the helper is poisoned and every
+/// further access panics too.
+pub struct LazyHelper<T, F, D> where F: FnOnce() -> T, D: DetachedDrop<Implementor=T> {
+    inner: IntoInnerHelper<Lazy<T, F>, LazyDrop<D, F>>,
+}
+
+struct Lazy<T, F> {
+    value: OnceCell<T>,
+    init: Cell<Option<F>>,
+}
+
+impl<T, F> Lazy<T, F> where F: FnOnce() -> T {
+    fn force(&self) -> &T {
+        self.value.get_or_init(|| match self.init.take() {
+            Some(init) => init(),
+            None => panic!("LazyHelper instance has previously been poisoned"),
+        })
+    }
+}
+
+struct LazyDrop<D, F>(core::convert::Infallible, core::marker::PhantomData<(D, F)>);
+
+impl<D, F> DetachedDrop for LazyDrop<D, F> where D: DetachedDrop {
+    type Implementor = Lazy<D::Implementor, F>;
+
+    #[cfg(feature = "std")]
+    const RUN_ON_PANIC: bool = D::RUN_ON_PANIC;
+
+    fn drop(value: Self::Implementor) {
+        if let Some(value) = value.value.into_inner() {
+            detached_drop::<D>(value);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn drop_during_panic(value: Self::Implementor, context: &crate::PanicContext, token: crate::HookToken) {
+        if let Some(value) = value.value.into_inner() {
+            crate::unwind::detached_drop_during_panic::<D>(value, context, token);
+        }
+    }
+
+    fn drop_in_place(value: InPlace<'_, Self::Implementor>) {
+        if value.value.get().is_some() {
+            // SAFETY: the initializer is moved out before projecting and never used through
+            // `value` again. `OnceCell` doesn't need to be dropped apart from its content.
+            let init = unsafe { core::ptr::read(&value.init) };
+            detached_drop_in_place::<D>(unsafe { value.project(|value| value.value.get_mut().expect("checked above")) });
+            core::mem::drop(init);
+        }
+    }
+}
+
+impl<T, F, D> LazyHelper<T, F, D> where F: FnOnce() -> T, D: DetachedDrop<Implementor=T> {
+    /// Creates the helper computing the value using `init`.
+    pub fn new(init: F) -> Self {
+        LazyHelper {
+            inner: IntoInnerHelper::new(Lazy { value: OnceCell::new(), init: Cell::new(Some(init)), }),
+        }
+    }
+
+    /// Accesses the inner value, computing it if needed.
+    pub fn force(&self) -> &T {
+        self.inner.inner().force()
+    }
+
+    /// Accesses the inner value if it was already computed.
+    pub fn get(&self) -> Option<&T> {
+        self.inner.inner().value.get()
+    }
+
+    /// Returns `true` if the value was already computed.
+    pub fn is_initialized(&self) -> bool {
+        self.get().is_some()
+    }
+
+    /// Moves out the inner value if it was computed, preventing the detached drop.
+    pub fn into_inner(self) -> Option<T> {
+        self.inner.into_inner().value.into_inner()
+    }
+}
+
+impl<T, F, D> Deref for LazyHelper<T, F, D> where F: FnOnce() -> T, D: DetachedDrop<Implementor=T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.force()
+    }
+}
+
+impl<T, F, D> DerefMut for LazyHelper<T, F, D> where F: FnOnce() -> T, D: DetachedDrop<Implementor=T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        let lazy = self.inner.inner_mut();
+        lazy.force();
+        lazy.value.get_mut().expect("the value was just initialized")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use super::LazyHelper;
+    use crate::DetachedDrop;
+
+    std::thread_local! {
+        static DROPPED: Cell<Option<u32>> = const { Cell::new(None) };
+    }
+
+    enum Record {}
+
+    impl DetachedDrop for Record {
+        type Implementor = u32;
+
+        fn drop(value: Self::Implementor) {
+            DROPPED.with(|dropped| dropped.set(Some(value)));
+        }
+    }
+
+    #[test]
+    fn deref_initializes() {
+        let computed = Cell::new(0);
+        let mut helper = <LazyHelper<_, _, Record>>::new(|| { computed.set(computed.get() + 1); 41 });
+        assert!(!helper.is_initialized());
+        assert_eq!(computed.get(), 0);
+        assert_eq!(*helper, 41);
+        *helper += 1;
+        assert_eq!(helper.get(), Some(&42));
+        assert_eq!(computed.get(), 1);
+        core::mem::drop(helper);
+        assert_eq!(DROPPED.with(Cell::get), Some(42));
+    }
+
+    #[test]
+    fn never_initialized() {
+        let helper = <LazyHelper<_, _, Record>>::new(|| -> u32 { panic!("must not be computed") });
+        assert!(helper.get().is_none());
+        core::mem::drop(helper);
+        assert_eq!(DROPPED.with(Cell::get), None);
+    }
+
+    #[test]
+    fn into_inner() {
+        let helper = <LazyHelper<_, _, Record>>::new(|| 42);
+        assert_eq!(*helper.force(), 42);
+        assert_eq!(helper.into_inner(), Some(42));
+        let helper = <LazyHelper<_, _, Record>>::new(|| 42);
+        assert_eq!(helper.into_inner(), None);
+        assert_eq!(DROPPED.with(Cell::get), None);
+    }
+
+    #[test]
+    fn poisoned() {
+        let helper = <LazyHelper<_, _, Record>>::new(|| -> u32 { panic!("computation failed") });
+        assert!(std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| *helper)).is_err());
+        let error = std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| *helper)).unwrap_err();
+        assert_eq!(error.downcast_ref::<&str>(), Some(&"LazyHelper instance has previously been poisoned"));
+        core::mem::drop(helper);
+        assert_eq!(DROPPED.with(Cell::get), None);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unwinding() {
+        use crate::unwind::tests::{Record, SkipOnPanic, drop_while_panicking};
+
+        let helper = <LazyHelper<_, _, Record>>::new(|| 42);
+        helper.force();
+        assert_eq!(drop_while_panicking(helper), ["drop_panicking"]);
+        let helper = <LazyHelper<_, _, SkipOnPanic>>::new(|| 42);
+        helper.force();
+        assert!(drop_while_panicking(helper).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn drop_in_place() {
+        use crate::unwind::tests::{InPlaceRecord, called, in_place_address};
+
+        let address = {
+            let helper = <LazyHelper<_, _, InPlaceRecord>>::new(|| 42);
+            helper.force() as *const u32
+        };
+        assert_eq!(called(), ["drop_in_place"]);
+        assert_eq!(in_place_address(), Some(address));
+    }
+}
@@ -0,0 +1,175 @@
+//! Tracking of leaked helpers, enabled by the `checked-build` feature in debug builds.
+//!
+//! Every `IntoInnerHelper` registers itself when created and unregisters when it's dropped or
+//! `into_inner` is called. Helpers that were leaked (e.g. using `mem::forget`) stay registered and
+//! are reported to stderr when the thread that created them exits. The registry is global, so
+//! helpers sent to other threads are tracked correctly.
+//!
+//! The id is stored in the helper, so the helper is not `repr(transparent)` while tracking is
+//! enabled.
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread::ThreadId;
+use std::vec::Vec;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+static LIVE: Mutex<BTreeMap<u64, LiveGuard>> = Mutex::new(BTreeMap::new());
+
+std::thread_local! {
+    static REPORTER: Reporter = Reporter { thread: std::thread::current().id() };
+}
+
+fn live() -> MutexGuard<'static, BTreeMap<u64, LiveGuard>> {
+    LIVE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Information about a helper that is still alive.
+#[derive(Debug, Clone)]
+pub struct LiveGuard {
+    id: u64,
+    type_name: &'static str,
+    thread: ThreadId,
+}
+
+impl LiveGuard {
+    /// Returns the unique id of the helper.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns the name of the helper type.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Returns the id of the thread that created the helper.
+    pub fn thread(&self) -> ThreadId {
+        self.thread
+    }
+}
+
+/// Returns all helpers that were neither dropped nor consumed by `into_inner` yet.
+pub fn live_guards() -> Vec<LiveGuard> {
+    live().values().cloned().collect()
+}
+
+/// Registration of a single helper, unregisters it when dropped.
+pub(crate) struct Tracked {
+    id: u64,
+}
+
+impl Tracked {
+    pub(crate) fn new<T>() -> Self {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        // Thread locals may be already destroyed if a helper is created by another destructor.
+        let thread = REPORTER.try_with(|reporter| reporter.thread).unwrap_or_else(|_| std::thread::current().id());
+        let guard = LiveGuard {
+            id,
+            type_name: core::any::type_name::<T>(),
+            thread,
+        };
+        live().insert(id, guard);
+        Tracked { id, }
+    }
+}
+
+impl Tracked {
+    /// Creates a registration that's not tracked, for helpers created in const context.
+    pub(crate) const fn untracked() -> Self {
+        Tracked { id: UNTRACKED, }
+    }
+}
+
+/// The id of untracked helpers, never assigned by `Tracked::new`.
+const UNTRACKED: u64 = u64::MAX;
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        live().remove(&self.id);
+    }
+}
+
+struct Reporter {
+    thread: ThreadId,
+}
+
+impl Drop for Reporter {
+    fn drop(&mut self) {
+        use std::io::Write;
+
+        let leaked = live().values().filter(|guard| guard.thread == self.thread).cloned().collect::<Vec<_>>();
+        let mut stderr = std::io::stderr();
+        for guard in leaked {
+            let _ = writeln!(stderr, "leaked {} (id {}): neither dropped nor consumed by into_inner", guard.type_name, guard.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::live_guards;
+    use crate::{IntoInnerHelper, DetachedDrop};
+
+    enum Dummy {}
+
+    impl DetachedDrop for Dummy {
+        type Implementor = u32;
+
+        fn drop(_: Self::Implementor) {}
+    }
+
+    fn is_live(id: u64) -> bool {
+        live_guards().iter().any(|guard| guard.id() == id)
+    }
+
+    #[test]
+    fn drop_unregisters() {
+        let helper = <IntoInnerHelper<_, Dummy>>::new(42);
+        let id = helper.tracked.id;
+        assert!(is_live(id));
+        core::mem::drop(helper);
+        assert!(!is_live(id));
+    }
+
+    #[test]
+    fn into_inner_unregisters() {
+        let helper = <IntoInnerHelper<_, Dummy>>::new(42);
+        let id = helper.tracked.id;
+        assert_eq!(helper.into_inner(), 42);
+        assert!(!is_live(id));
+    }
+
+    #[test]
+    fn forget_stays_registered() {
+        let helper = <IntoInnerHelper<_, Dummy>>::new(42);
+        let id = helper.tracked.id;
+        core::mem::forget(helper);
+        let guard = live_guards().into_iter().find(|guard| guard.id() == id).unwrap();
+        assert!(guard.type_name().ends_with("IntoInnerHelper<u32, into_inner_drop::leak_check::tests::Dummy>"));
+        assert_eq!(guard.thread(), std::thread::current().id());
+    }
+
+    #[test]
+    fn const_untracked() {
+        const HELPER: IntoInnerHelper<u32, Dummy> = IntoInnerHelper::new_const(42);
+
+        let helper = HELPER;
+        assert!(!is_live(helper.tracked.id));
+        assert_eq!(helper.defuse(), 42);
+    }
+
+    #[test]
+    fn layout_changed() {
+        assert!(core::mem::size_of::<IntoInnerHelper<u32, Dummy>>() > core::mem::size_of::<u32>());
+    }
+
+    #[test]
+    fn moved_to_other_thread() {
+        let helper = <IntoInnerHelper<_, Dummy>>::new(42);
+        let id = helper.tracked.id;
+        std::thread::spawn(move || core::mem::drop(helper)).join().unwrap();
+        assert!(!is_live(id));
+    }
+}
@@ -11,7 +11,27 @@
 //! responsibility for ensuring that the drop impl is sound. More eyes, less bugs. The performace
 //! overhead of this crate is zero.
 //!
-//! This crate is `no_std`.
+//! This crate is `no_std`. Optional features:
+//!
+//! * `alloc` - helpers that need heap allocation, such as `BoxedHelper`
+//! * `std` - integration with the standard library, implies `alloc`
+//! * `release-bomb` - makes `DropBomb` panic in release builds too
+//! * `log` - log records emitted by `LabeledHelper` and errors ignored by `FlushOnDrop` and `PersistOnDrop`
+//! * `ops` - forwarding of arithmetic and bitwise operators to the inner value
+//! * `scopeguard` - conversions between `ClosureHelper` and `scopeguard::ScopeGuard`
+//! * `checked-build` - tracking of leaked helpers in debug builds, implies `std`; **changes the layout of `IntoInnerHelper`**, see below
+//! * `derive` - `#[derive(GuardWrapper)]` forwarding traits of newtypes to the guarded value
+//! * `serde` - `PersistOnDrop` serializing the inner value on drop, implies `std`
+//! * `reentrancy-check` - warnings logged when a detached drop drops a helper with the same marker, implies `std` and `log`
+//! * `journal` - `no_std` ring buffer of recent `LabeledHelper` events for postmortem debugging
+//! * `tracing` - `SpanHelper` running the detached drop inside an entered `tracing` span
+//! * `heapless` - `Clear` for `heapless::Vec` and `heapless::String`, so `ClearOnDrop` works with them without allocation
+//!
+//! **Warning:** in debug builds with `checked-build` every `IntoInnerHelper` stores an id used to
+//! track leaks next to the inner value. The helper is then larger than `T` and is **not**
+//! `repr(transparent)`, so code relying on the helper having the same layout as `T` (e.g. transmutes,
+//! pointer casts or FFI) must not be compiled with the feature enabled. The id can't be replaced by
+//! the address of the helper since helpers are moved freely. Release builds are unaffected.
 //!
 //! # Example
 //!
@@ -76,12 +96,173 @@
 
 #![no_std]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(any(test, feature = "std"))]
+extern crate std;
+
 use core::mem::ManuallyDrop;
 
+#[macro_use]
+mod macros;
+mod in_place;
+mod forward;
+mod tuple;
+mod emptiable;
+mod adapt;
+mod disarm;
+mod finalize;
+mod fallible;
+mod closure;
+mod markers;
+mod bomb;
+mod labeled;
+mod projected;
+mod cell;
+mod async_drop;
+mod batch;
+mod lazy;
+mod once;
+mod iter;
+mod steps;
+mod restore;
+mod within;
+mod handle;
+mod uses;
+mod unwind;
+mod maybe_init;
+mod dirty;
+mod fields;
+mod read_only;
+#[cfg(feature = "ops")]
+mod ops;
+#[cfg(all(feature = "checked-build", debug_assertions))]
+mod leak_check;
+#[cfg(feature = "serde")]
+mod persist;
+#[cfg(feature = "reentrancy-check")]
+mod reentrancy_check;
+#[cfg(feature = "journal")]
+mod journal;
+#[cfg(feature = "heapless")]
+mod heapless_collections;
+
+#[cfg(feature = "alloc")]
+mod boxed;
+#[cfg(feature = "alloc")]
+mod collections;
+#[cfg(feature = "alloc")]
+mod pool;
+#[cfg(feature = "alloc")]
+mod shared;
+#[cfg(feature = "alloc")]
+mod raw;
+#[cfg(feature = "alloc")]
+mod reentrant;
+#[cfg(feature = "alloc")]
+mod finalizer_batch;
+#[cfg(feature = "std")]
+mod at_exit;
+#[cfg(feature = "std")]
+mod mutex;
+#[cfg(feature = "std")]
+mod panic_context;
+#[cfg(feature = "std")]
+mod poison;
+
+pub use in_place::InPlace;
+#[doc(hidden)]
+pub use macros::{GuardedNewtype, NewtypeDrop};
+pub use emptiable::EmptiableHelper;
+pub use adapt::AdaptedHelper;
+pub use disarm::{DisarmableHelper, DisarmHandle, FreezeGuard};
+pub use finalize::{Finalize, DetachedDropWithOutput, finalize_all};
+pub use closure::{ClosureHelper, CancelHandle, FnPtrHelper, guard, fn_guard};
+#[cfg(feature = "alloc")]
+pub use closure::{DynHelper, dyn_guard};
+pub use markers::{ModalDrop, ModeDrop, Clear, ClearOnDrop, ResetOnDrop};
+#[cfg(feature = "std")]
+pub use markers::FlushOnDrop;
+pub use bomb::{DropBomb, RecoverHook, set_bomb_recover_hook};
+pub use labeled::LabeledHelper;
+pub use projected::{DetachedView, ProjectedHelper};
+pub use cell::{RefCellHelper, UnsafeCellHelper};
+pub use async_drop::{AsyncDetachedDrop, AsyncHelper};
+pub use batch::{drop_each, DropEach};
+pub use lazy::LazyHelper;
+pub use once::OnceGuard;
+pub use iter::FinalizingIter;
+pub use steps::{DropSteps, Steps};
+pub use restore::OverrideGuard;
+pub use within::WithinHelper;
+pub use handle::HandleHelper;
+pub use uses::UsesGuard;
+pub use maybe_init::MaybeInitHelper;
+pub use dirty::DirtyHelper;
+pub use fields::{DetachedFields, FieldsMut};
+pub use read_only::ReadOnlyHelper;
+pub use fallible::{TryDetachedDrop, TryIntoInnerHelper, FinalizeError};
+#[cfg(feature = "alloc")]
+pub use boxed::BoxedHelper;
+#[cfg(feature = "alloc")]
+pub use batch::GuardVec;
+#[cfg(feature = "alloc")]
+pub use pool::{GuardPool, PoolGuard};
+#[cfg(feature = "alloc")]
+pub use shared::SharedArmHelper;
+#[cfg(feature = "alloc")]
+pub use raw::{RawFinalizer, RawCallback};
+#[cfg(feature = "alloc")]
+pub use reentrant::{ReentrantHelper, ReentrancyToken};
+#[cfg(feature = "alloc")]
+pub use finalizer_batch::FinalizerBatch;
+#[cfg(feature = "std")]
+pub use mutex::MutexHelper;
+#[cfg(feature = "std")]
+pub use panic_context::{PanicContext, HookToken, install_panic_context_hook, clear_panic_context};
+#[cfg(feature = "std")]
+pub use poison::{PoisonableHelper, PoisonGuard};
+#[cfg(feature = "derive")]
+pub use into_inner_drop_derive::GuardWrapper;
+#[cfg(feature = "serde")]
+pub use persist::{PersistOnDrop, PersistFormat};
+#[cfg(all(feature = "checked-build", debug_assertions))]
+pub use leak_check::{LiveGuard, live_guards};
+#[cfg(feature = "tracing")]
+pub use within::SpanHelper;
+#[cfg(feature = "journal")]
+pub use journal::{DropJournal, DropEvent, DropEventKind, drop_journal, JOURNAL_CAPACITY};
+
 /// A replacement trait for providing Drop implementation.
 ///
 /// Since `self` is not used, it's recommended to create an empty enum and implement this trait for
 /// it.
+///
+/// Tuples of markers (up to 12 elements) implement this trait too. They drop a tuple of values
+/// element by element, in order, each using its own marker.
+///
+/// Implementing the trait is the whole point of the crate, so it can't be sealed as a whole.
+/// Instead, the optional hooks are sealed: the unwinding hooks take a `HookToken` and
+/// `drop_in_place` takes an `InPlace`, neither of which can be created outside of this crate. The
+/// hooks can be overridden, but only the helpers can call them, so downstream code can't come to
+/// depend on calling them and new hooks can be added with default implementations without
+/// breaking existing markers. The crate never provides blanket impls of this trait for foreign
+/// types, except for tuples of markers.
+///
+/// ```compile_fail
+/// # use into_inner_drop::{DetachedDrop, HookToken};
+/// enum Noop {}
+///
+/// impl DetachedDrop for Noop {
+///     type Implementor = ();
+///
+///     fn drop(_: Self::Implementor) {}
+/// }
+///
+/// // The token can't be created outside of the crate.
+/// Noop::drop_panicking((), HookToken(()));
+/// ```
 pub trait DetachedDrop {
     /// The inner type you want to implement Drop for.
     type Implementor;
@@ -90,13 +271,78 @@ pub trait DetachedDrop {
     ///
     /// This function will only be called if `into_inner` was NOT called.
     fn drop(value: Self::Implementor);
+
+    /// Whether the detached drop should run if the helper is dropped while the thread is
+    /// panicking.
+    ///
+    /// If this is `false` the value is dropped normally during unwinding, skipping both
+    /// `drop_panicking` and `drop_in_place`. This is a simpler alternative to overriding
+    /// `drop_panicking` for cleanup that must not run during a panic. All helpers of this crate
+    /// respect it, including the ones wrapping the value, such as `RefCellHelper`.
+    #[cfg(feature = "std")]
+    const RUN_ON_PANIC: bool = true;
+
+    /// The drop implementation called instead of `drop` if the helper is dropped while the thread
+    /// is panicking.
+    ///
+    /// The default calls `drop`. Override this to behave differently during unwinding, e.g. to
+    /// skip expensive logging.
+    #[cfg(feature = "std")]
+    fn drop_panicking(value: Self::Implementor, token: HookToken) {
+        let _ = token;
+        Self::drop(value)
+    }
+
+    /// The drop implementation called by the default `drop_in_place` while the thread is
+    /// panicking, receiving information about the panic.
+    ///
+    /// The default calls `drop_panicking`. The context is only filled in if the hook installed by
+    /// `install_panic_context_hook` captured it, e.g. to log which resource was open when the
+    /// panic occurred.
+    #[cfg(feature = "std")]
+    fn drop_during_panic(value: Self::Implementor, context: &PanicContext, token: HookToken) {
+        let _ = context;
+        Self::drop_panicking(value, token)
+    }
+
+    /// The drop implementation operating on the value without moving it out of the helper.
+    ///
+    /// The default moves the value out and calls `drop` (or `drop_during_panic` with `std` if the
+    /// thread is panicking). Override this to avoid the copy when the inner value is large. The
+    /// value is dropped in place afterwards unless it was moved out using `InPlace::take`. Helpers
+    /// wrapping the value, such as `RefCellHelper` or `DisarmableHelper`, call it too, except
+    /// `MutexHelper` and the helpers dropping many values.
+    fn drop_in_place(value: InPlace<'_, Self::Implementor>) {
+        #[cfg(feature = "std")]
+        {
+            if std::thread::panicking() {
+                let value = value.take();
+                return PanicContext::with_current(|context| Self::drop_during_panic(value, context, HookToken::new()));
+            }
+        }
+        Self::drop(value.take())
+    }
 }
 
 /// The helper which allows you to implement `Drop` for your type while still allowing to take it
 /// apart by moving out.
+///
+/// Auto traits like `Send`, `Sync`, `Unpin`, `UnwindSafe` and `RefUnwindSafe` depend only on `T`,
+/// never on the marker `D`.
+///
+/// The helper has the same layout as `T`, in particular it's zero-sized if `T` is zero-sized.
+/// The only exception are debug builds with the `checked-build` feature, where each helper stores
+/// an id used to track leaks. The helper is then larger than `T` and not `repr(transparent)`.
+///
+/// The helper is marked as having a significant drop, so Clippy's `significant_drop_in_scrutinee`
+/// and `significant_drop_tightening` lints flag guards living longer than intended.
+#[clippy::has_significant_drop]
+#[cfg_attr(not(all(feature = "checked-build", debug_assertions)), repr(transparent))]
 pub struct IntoInnerHelper<T, D> where D: DetachedDrop<Implementor=T> {
     inner: ManuallyDrop<T>,
-    _phantom: core::marker::PhantomData<D>,
+    _phantom: core::marker::PhantomData<fn() -> D>,
+    #[cfg(all(feature = "checked-build", debug_assertions))]
+    tracked: leak_check::Tracked,
 }
 
 impl<T, D> IntoInnerHelper<T, D> where D: DetachedDrop<Implementor=T> {
@@ -105,37 +351,433 @@ impl<T, D> IntoInnerHelper<T, D> where D: DetachedDrop<Implementor=T> {
         IntoInnerHelper {
             inner: ManuallyDrop::new(inner),
             _phantom: Default::default(),
+            #[cfg(all(feature = "checked-build", debug_assertions))]
+            tracked: leak_check::Tracked::new::<Self>(),
+        }
+    }
+
+    /// Creates the helper in const context, e.g. to initialize a `static`.
+    ///
+    /// This is the same as `new` except that helpers created this way are not tracked by the
+    /// `checked-build` feature. The detached drop can't run in const context, so a helper created
+    /// in a `const` has to be defused (or used at runtime) before the end of the evaluation.
+    pub const fn new_const(inner: T) -> Self {
+        IntoInnerHelper {
+            inner: ManuallyDrop::new(inner),
+            _phantom: core::marker::PhantomData,
+            #[cfg(all(feature = "checked-build", debug_assertions))]
+            tracked: leak_check::Tracked::untracked(),
         }
     }
 
+    /// Creates the helper guarding a clone of `value`.
+    ///
+    /// The original value is not guarded, only the clone is passed to the detached drop.
+    pub fn from_ref(value: &T) -> Self where T: Clone {
+        IntoInnerHelper::new(value.clone())
+    }
+
     /// Accesses the inner value.
+    ///
+    /// The value is always present: `into_inner` consumes the helper, so the inner value can't be
+    /// accessed afterwards.
+    ///
+    /// ```compile_fail
+    /// use into_inner_drop::{IntoInnerHelper, DetachedDrop};
+    ///
+    /// enum NoopDrop {}
+    ///
+    /// impl DetachedDrop for NoopDrop {
+    ///     type Implementor = u32;
+    ///
+    ///     fn drop(_: Self::Implementor) {}
+    /// }
+    ///
+    /// let helper = <IntoInnerHelper<_, NoopDrop>>::new(42);
+    /// let value = helper.into_inner();
+    /// assert_eq!(*helper.inner(), value);
+    /// ```
     pub fn inner(&self) -> &T {
-        &*self.inner
+        &self.inner
     }
 
     /// Accesses the inner value mutably.
     pub fn inner_mut(&mut self) -> &mut T {
-        &mut *self.inner
+        &mut self.inner
+    }
+
+    /// Clones the inner value, keeping the helper intact.
+    ///
+    /// The clone is not guarded, only the original value is passed to the detached drop.
+    pub fn cloned(&self) -> T where T: Clone {
+        self.inner().clone()
+    }
+
+    /// Clones the inner value into `target`, reusing its resources.
+    ///
+    /// This uses `Clone::clone_from`, so e.g. the allocation of a `String` or `Vec` is reused if
+    /// it's large enough. Like with `cloned`, `target` is not guarded.
+    pub fn clone_into_existing(&self, target: &mut T) where T: Clone {
+        target.clone_from(self.inner())
+    }
+
+    /// Accesses the inner value mutably.
+    ///
+    /// This is the same as `inner_mut`, named after `Mutex::get_mut` and `RefCell::get_mut`.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner_mut()
+    }
+
+    /// Accesses a guarded reader or iterator for partial consumption.
+    ///
+    /// This is the same as `inner_mut`, named for streaming use: the returned reference can be
+    /// consumed by `Iterator::by_ref` or `Read::take` adapters across several calls, while the
+    /// detached drop stays attached to the helper and runs once it's dropped.
+    pub fn stream(&mut self) -> &mut T {
+        self.inner_mut()
+    }
+
+    /// Takes a part out of the inner value, such as an element of a guarded collection.
+    ///
+    /// The closure only receives a mutable reference, so the inner value stays valid and the
+    /// detached drop later runs once over whatever remains, even if the closure panics.
+    pub fn try_take_part<P, F>(&mut self, f: F) -> Option<P> where F: FnOnce(&mut T) -> Option<P> {
+        f(self.inner_mut())
+    }
+
+    /// Returns the address of the inner value.
+    ///
+    /// The address is stable until the helper is moved, dropped or `into_inner` is called, so it
+    /// can be used to compare identity of guards.
+    pub fn inner_addr(&self) -> *const T {
+        self.inner()
+    }
+
+    /// Returns `true` if the inner value equals `other`.
+    pub fn inner_eq(&self, other: &T) -> bool where T: PartialEq {
+        self.inner() == other
+    }
+
+    /// Returns `true` if both helpers are the same object.
+    ///
+    /// Equal values in distinct helpers are not identical. Note that distinct helpers over
+    /// zero-sized values may share an address and compare as identical.
+    pub fn inner_ptr_eq(&self, other: &Self) -> bool {
+        core::ptr::eq(self.inner(), other.inner())
+    }
+
+    /// Borrows the inner value as `B`.
+    ///
+    /// Handy for inners like `Cow<'_, B>` or `String` where you want the borrowed view directly.
+    pub fn as_borrowed<B>(&self) -> &B where T: core::borrow::Borrow<B>, B: ?Sized {
+        self.inner().borrow()
+    }
+
+    /// Views the inner value as a string slice.
+    ///
+    /// This matches `String::as_str` for guards over `String`, `Box<str>` and similar types.
+    pub fn as_str(&self) -> &str where T: AsRef<str> {
+        self.inner().as_ref()
+    }
+
+    /// Returns the type-erased inner value if it's of type `U`.
+    ///
+    /// This is useful for guards over type-erased resources, such as `Box<dyn Any>`. The type
+    /// checked is the one of the erased value, not of `T` itself. The helper is only borrowed, so
+    /// the detached drop is not affected.
+    pub fn downcast_inner<U: core::any::Any>(&self) -> Option<&U> where T: AsRef<dyn core::any::Any> {
+        (*self.inner().as_ref()).downcast_ref()
+    }
+
+    /// Projects the inner value to a part of it, borrowed for as long as the helper.
+    ///
+    /// This is handy for exposing a single field of the inner value in a public API. The projected
+    /// reference can't outlive the helper:
+    ///
+    /// ```compile_fail
+    /// use into_inner_drop::{IntoInnerHelper, DetachedDrop};
+    ///
+    /// enum NoopDrop {}
+    ///
+    /// impl DetachedDrop for NoopDrop {
+    ///     type Implementor = (u32, u32);
+    ///
+    ///     fn drop(_: Self::Implementor) {}
+    /// }
+    ///
+    /// let helper = <IntoInnerHelper<_, NoopDrop>>::new((1, 2));
+    /// let first = helper.project(|pair| &pair.0);
+    /// drop(helper);
+    /// assert_eq!(*first, 1);
+    /// ```
+    pub fn project<'a, U, F>(&'a self, f: F) -> &'a U where F: FnOnce(&'a T) -> &'a U, U: ?Sized {
+        f(self.inner())
+    }
+
+    /// Projects the inner value to a part of it mutably, borrowed for as long as the helper.
+    pub fn project_mut<'a, U, F>(&'a mut self, f: F) -> &'a mut U where F: FnOnce(&'a mut T) -> &'a mut U, U: ?Sized {
+        f(self.inner_mut())
     }
 
     /// Moves out the inner value.
+    ///
+    /// The crate can't provide `From<IntoInnerHelper<T, D>> for T` (nor the `Into` equivalent)
+    /// since coherence rules forbid implementing a foreign trait for a bare type parameter. You
+    /// can still extract the value using `.into()` by implementing `From` for your own type, the
+    /// detached drop doesn't run then:
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use into_inner_drop::{IntoInnerHelper, DetachedDrop};
+    ///
+    /// static CLOSED: AtomicBool = AtomicBool::new(false);
+    ///
+    /// struct Connection(u32);
+    ///
+    /// enum CloseConnection {}
+    ///
+    /// impl DetachedDrop for CloseConnection {
+    ///     type Implementor = Connection;
+    ///
+    ///     fn drop(_: Self::Implementor) {
+    ///         CLOSED.store(true, Ordering::Relaxed);
+    ///     }
+    /// }
+    ///
+    /// impl From<IntoInnerHelper<Connection, CloseConnection>> for Connection {
+    ///     fn from(helper: IntoInnerHelper<Connection, CloseConnection>) -> Self {
+    ///         helper.into_inner()
+    ///     }
+    /// }
+    ///
+    /// let helper = <IntoInnerHelper<_, CloseConnection>>::new(Connection(42));
+    /// let connection: Connection = helper.into();
+    /// assert_eq!(connection.0, 42);
+    /// drop(connection);
+    /// assert!(!CLOSED.load(Ordering::Relaxed));
+    /// ```
     pub fn into_inner(self) -> T {
         unsafe {
             let inner = core::ptr::read(&*self.inner);
+            #[cfg(all(feature = "checked-build", debug_assertions))]
+            core::mem::drop(core::ptr::read(&self.tracked));
             core::mem::forget(self);
             inner
         }
     }
+
+    /// Moves the inner value into `target`, transferring the cleanup to its marker.
+    ///
+    /// The detached drop of `D` doesn't run, the value is later cleaned up by `D2` when `target` is
+    /// dropped. The value previously held by `target`, if any, is returned without running any
+    /// detached drop on it.
+    pub fn defuse_into<D2>(self, target: &mut IntoInnerHelper<Option<T>, D2>) -> Option<T> where D2: DetachedDrop<Implementor=Option<T>> {
+        target.reset(Some(self.into_inner()))
+    }
+
+    /// Moves out the inner value and transforms it using `f`.
+    ///
+    /// Unlike `map` the result isn't guarded. The detached drop doesn't run, so if `f` panics the
+    /// value is dropped without running it.
+    pub fn into_inner_map<U, F>(self, f: F) -> U where F: FnOnce(T) -> U {
+        f(self.into_inner())
+    }
+
+    /// Moves out the inner value like `into_inner`, recording why the detached drop was skipped.
+    ///
+    /// With the `log` feature the reason is logged at info level together with the type of the
+    /// inner value, which helps when investigating leaked resources. Without it this is the same
+    /// as `into_inner`.
+    #[cfg_attr(not(feature = "log"), allow(unused_variables))]
+    pub fn into_inner_with_reason(self, reason: &'static str) -> T {
+        #[cfg(feature = "log")]
+        log::info!(target: "into_inner_drop", "{}: extracted inner value: {}", core::any::type_name::<T>(), reason);
+        self.into_inner()
+    }
+
+    /// Moves out the inner value in const context.
+    ///
+    /// This is the same as `into_inner` but usable in `const fn`. With `checked-build` the helper
+    /// is reported as leaked unless it was created using `new_const`, so prefer `into_inner` at
+    /// runtime.
+    pub const fn defuse(self) -> T {
+        let this = ManuallyDrop::new(self);
+        // `ManuallyDrop` can't be dereferenced in const context, so go through a raw pointer.
+        let this = &this as *const ManuallyDrop<Self> as *const Self;
+        // SAFETY: `this` is never dropped so the value is moved out exactly once.
+        unsafe {
+            ManuallyDrop::into_inner(core::ptr::read(&(*this).inner))
+        }
+    }
+
+    /// Replaces the inner value with `value`, returning the previous one.
+    ///
+    /// The detached drop doesn't run on the returned value, it's up to the caller to handle it.
+    /// This is useful for reusing guards e.g. in object pools.
+    pub fn reset(&mut self, value: T) -> T {
+        core::mem::replace(self.inner_mut(), value)
+    }
+
+    /// Swaps the inner value with `value`, returning the previous one.
+    ///
+    /// This is the same as `reset`, the detached drop doesn't run on the returned value.
+    pub fn exchange(&mut self, value: T) -> T {
+        self.reset(value)
+    }
+
+    /// Swaps the inner value with the one returned by `f`, returning the previous one.
+    ///
+    /// The replacement is only constructed when needed, the detached drop doesn't run on the
+    /// returned value. If `f` panics the helper keeps the previous value.
+    pub fn exchange_with<F>(&mut self, f: F) -> T where F: FnOnce() -> T {
+        self.reset(f())
+    }
+
+    /// Splits the inner value, returning the head and keeping the rest guarded by `D2`.
+    ///
+    /// The detached drop of `D` doesn't run, so this is useful for incremental teardown, where
+    /// some parts of the resource are released early. If `f` panics the value is dropped without
+    /// running any detached drop.
+    pub fn split_off<H, R, D2, F>(self, f: F) -> (H, IntoInnerHelper<R, D2>) where F: FnOnce(T) -> (H, R), D2: DetachedDrop<Implementor=R> {
+        let (head, rest) = f(self.into_inner());
+        (head, IntoInnerHelper::new(rest))
+    }
+
+    /// Transforms the inner value, guarding the result by `D2`.
+    ///
+    /// The detached drop of `D` doesn't run. If `f` panics the value is dropped without running
+    /// any detached drop.
+    pub fn map<U, D2, F>(self, f: F) -> IntoInnerHelper<U, D2> where F: FnOnce(T) -> U, D2: DetachedDrop<Implementor=U> {
+        IntoInnerHelper::new(f(self.into_inner()))
+    }
+
+    /// Transitions the guarded resource to a new state, guarding the result by `D2`.
+    ///
+    /// This is the same as `map`, intended for state machines. It's guaranteed that the detached
+    /// drop of `D` never runs: the value is moved out of the helper before `f` is called, so if
+    /// `f` panics the value is only dropped normally (by `f`) and neither `D` nor `D2` sees it.
+    pub fn transition<U, D2, F>(self, f: F) -> IntoInnerHelper<U, D2> where F: FnOnce(T) -> U, D2: DetachedDrop<Implementor=U> {
+        self.map(f)
+    }
+
+    /// Transforms the inner value fallibly, guarding the result by `D2`.
+    ///
+    /// On failure `f` has to give the original value back along with the error. The value is then
+    /// guarded by `D` again and returned, so the detached drop of `D` runs only if the caller drops
+    /// it. If `f` panics the value is dropped without running any detached drop.
+    pub fn try_map<U, D2, E, F>(self, f: F) -> Result<IntoInnerHelper<U, D2>, (E, Self)> where F: FnOnce(T) -> Result<U, (T, E)>, D2: DetachedDrop<Implementor=U> {
+        match f(self.into_inner()) {
+            Ok(value) => Ok(IntoInnerHelper::new(value)),
+            Err((value, error)) => Err((error, IntoInnerHelper::new(value))),
+        }
+    }
+
+    /// Switches the drop behavior to `D2`, without running the detached drop of `D`.
+    ///
+    /// This is useful for state transitions, e.g. from rollback to commit.
+    pub fn set_drop<D2>(self) -> IntoInnerHelper<T, D2> where D2: DetachedDrop<Implementor=T> {
+        IntoInnerHelper::new(self.into_inner())
+    }
+
+    /// Switches the drop behavior to `D2` if `pred` returns `true` for the inner value.
+    ///
+    /// Otherwise the helper is returned unchanged.
+    pub fn set_drop_if<D2, F>(self, pred: F) -> Result<IntoInnerHelper<T, D2>, Self> where F: FnOnce(&T) -> bool, D2: DetachedDrop<Implementor=T> {
+        if pred(self.inner()) {
+            Ok(self.set_drop())
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Runs the detached drop now, consuming the helper.
+    ///
+    /// This is equivalent to dropping the helper but makes the intent explicit.
+    pub fn finalize(self) {
+        core::mem::drop(self)
+    }
+
+    /// Runs the detached drop and then `after`, returning its result.
+    ///
+    /// `after` doesn't run if the detached drop panics.
+    pub fn finalize_then<R, F>(self, after: F) -> R where F: FnOnce() -> R {
+        self.finalize();
+        after()
+    }
 }
 
 impl<T, D> Drop for IntoInnerHelper<T, D> where D: DetachedDrop<Implementor=T> {
     fn drop(&mut self) {
+        // `self.inner` is handed over to `InPlace` and never touched again, so the value is
+        // dropped exactly once: either by `D::drop` after `InPlace::take` moves it out or in place
+        // by `InPlace`. Neither a panic in the detached drop nor it dropping another helper (even
+        // of the same type) can cause a second drop: `ManuallyDrop` never drops its content and
+        // the detached drop has no way to reach `self`.
+        #[cfg(feature = "std")]
+        {
+            if !D::RUN_ON_PANIC && std::thread::panicking() {
+                unsafe {
+                    ManuallyDrop::drop(&mut self.inner);
+                }
+                return;
+            }
+        }
+        #[cfg(feature = "reentrancy-check")]
+        let _active = reentrancy_check::ActiveDrop::enter::<D>();
         unsafe {
-            D::drop(core::ptr::read(&*self.inner));
+            D::drop_in_place(InPlace::new(&mut self.inner));
         }
     }
 }
 
+impl<T, D> From<T> for IntoInnerHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    fn from(value: T) -> Self {
+        IntoInnerHelper::new(value)
+    }
+}
+
+/// Clones the value, see `from_ref`.
+impl<T, D> From<&T> for IntoInnerHelper<T, D> where T: Clone, D: DetachedDrop<Implementor=T> {
+    fn from(value: &T) -> Self {
+        IntoInnerHelper::from_ref(value)
+    }
+}
+
+/// Guards `value` using the marker `D`.
+///
+/// This is the same as `IntoInnerHelper::new` but reads better in pipelines, e.g.
+/// `values.map(with_drop_guard::<_, Flush>)`.
+pub fn with_drop_guard<T, D>(value: T) -> IntoInnerHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    IntoInnerHelper::new(value)
+}
+
+/// Attaches a detached drop to any value in method chains.
+pub trait WithDropGuard: Sized {
+    /// Guards `self` using the marker `D`.
+    fn with_drop_guard<D>(self) -> IntoInnerHelper<Self, D> where D: DetachedDrop<Implementor=Self> {
+        IntoInnerHelper::new(self)
+    }
+}
+
+impl<T> WithDropGuard for T {}
+
+/// Sorts guards by their inner values.
+///
+/// The guards are only swapped around, no detached drop runs. The sort is unstable so that it
+/// doesn't allocate.
+pub fn sort_guards<T, D>(slice: &mut [IntoInnerHelper<T, D>]) where T: Ord, D: DetachedDrop<Implementor=T> {
+    slice.sort_unstable_by(|a, b| a.inner().cmp(b.inner()))
+}
+
+/// Swaps the inner values of two guards with possibly different markers.
+///
+/// Each guard keeps its own marker, so the detached drop of `a` later runs on the value previously
+/// held by `b` and vice versa. This is useful for moving data between e.g. a commit guard and a
+/// rollback guard.
+pub fn swap_inner<T, D1, D2>(a: &mut IntoInnerHelper<T, D1>, b: &mut IntoInnerHelper<T, D2>) where D1: DetachedDrop<Implementor=T>, D2: DetachedDrop<Implementor=T> {
+    core::mem::swap(a.inner_mut(), b.inner_mut())
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -179,4 +821,1202 @@ mod tests {
         core::mem::drop(inner);
         assert!(drop_state.is_dropped());
     }
+
+    struct Node {
+        _token: dropcheck::DropToken,
+        next: Option<std::boxed::Box<super::IntoInnerHelper<Node, NodeDrop>>>,
+    }
+
+    enum NodeDrop {}
+
+    std::thread_local! {
+        static NODES_DROPPED: core::cell::Cell<usize> = const { core::cell::Cell::new(0) };
+    }
+
+    impl super::DetachedDrop for NodeDrop {
+        type Implementor = Node;
+
+        fn drop(node: Self::Implementor) {
+            NODES_DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+            // Dropping `next` re-enters the drop of a helper with the same type.
+            core::mem::drop(node.next);
+        }
+    }
+
+    #[test]
+    fn reentrant_drop() {
+        use super::IntoInnerHelper;
+
+        let check = dropcheck::DropCheck::new();
+        let mut next = None;
+        for _ in 0..3 {
+            let node = Node { _token: check.token(), next: next.take(), };
+            next = Some(std::boxed::Box::new(<IntoInnerHelper<_, NodeDrop>>::new(node)));
+        }
+        core::mem::drop(next);
+        assert!(check.all_dropped());
+        assert_eq!(NODES_DROPPED.with(|dropped| dropped.get()), 3);
+    }
+
+    #[test]
+    fn panicking_drop() {
+        use super::{IntoInnerHelper, DetachedDrop};
+
+        enum Panicking {}
+
+        impl DetachedDrop for Panicking {
+            type Implementor = dropcheck::DropToken;
+
+            fn drop(_token: Self::Implementor) {
+                panic!("drop failed");
+            }
+        }
+
+        let check = dropcheck::DropCheck::new();
+        let (drop_token, drop_state) = check.pair();
+        let helper = <IntoInnerHelper<_, Panicking>>::new(drop_token);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || core::mem::drop(helper)));
+        assert!(result.is_err());
+        // dropcheck would have panicked if the token was dropped twice
+        assert!(drop_state.is_dropped());
+    }
+
+    #[test]
+    fn unpin_tracks_inner() {
+        use super::{IntoInnerHelper, DetachedDrop};
+        use core::marker::PhantomPinned;
+
+        enum StringDrop {}
+
+        impl DetachedDrop for StringDrop {
+            type Implementor = std::string::String;
+
+            fn drop(_: Self::Implementor) {}
+        }
+
+        enum PinnedDrop {}
+
+        impl DetachedDrop for PinnedDrop {
+            type Implementor = PhantomPinned;
+
+            fn drop(_: Self::Implementor) {}
+        }
+
+        fn assert_unpin<T: Unpin>() {}
+
+        // Resolving `some_item` is ambiguous if `T: Unpin`, so this only compiles for `!Unpin`.
+        trait AmbiguousIfUnpin<A> {
+            fn some_item() {}
+        }
+
+        impl<T: ?Sized> AmbiguousIfUnpin<()> for T {}
+        impl<T: ?Sized + Unpin> AmbiguousIfUnpin<u8> for T {}
+
+        assert_unpin::<IntoInnerHelper<std::string::String, StringDrop>>();
+        <IntoInnerHelper<PhantomPinned, PinnedDrop> as AmbiguousIfUnpin<_>>::some_item();
+    }
+
+    #[test]
+    fn unwind_safe_tracks_inner() {
+        use super::{IntoInnerHelper, DetachedDrop};
+        use core::cell::Cell;
+        use core::marker::PhantomData;
+        use core::panic::{UnwindSafe, RefUnwindSafe};
+
+        // The marker itself is neither `UnwindSafe` nor `RefUnwindSafe`.
+        struct StringDrop(core::convert::Infallible, PhantomData<&'static mut Cell<()>>);
+
+        impl DetachedDrop for StringDrop {
+            type Implementor = std::string::String;
+
+            fn drop(_: Self::Implementor) {}
+        }
+
+        enum CellDrop {}
+
+        impl DetachedDrop for CellDrop {
+            type Implementor = Cell<u8>;
+
+            fn drop(_: Self::Implementor) {}
+        }
+
+        enum MutRefDrop {}
+
+        impl DetachedDrop for MutRefDrop {
+            type Implementor = &'static mut u8;
+
+            fn drop(_: Self::Implementor) {}
+        }
+
+        fn assert_unwind_safe<T: UnwindSafe + RefUnwindSafe>() {}
+
+        // Resolving `some_item` is ambiguous if the bound holds, so these only compile if it doesn't.
+        trait AmbiguousIfUnwindSafe<A> {
+            fn some_item() {}
+        }
+
+        impl<T: ?Sized> AmbiguousIfUnwindSafe<()> for T {}
+        impl<T: ?Sized + UnwindSafe> AmbiguousIfUnwindSafe<u8> for T {}
+
+        trait AmbiguousIfRefUnwindSafe<A> {
+            fn some_item() {}
+        }
+
+        impl<T: ?Sized> AmbiguousIfRefUnwindSafe<()> for T {}
+        impl<T: ?Sized + RefUnwindSafe> AmbiguousIfRefUnwindSafe<u8> for T {}
+
+        assert_unwind_safe::<IntoInnerHelper<std::string::String, StringDrop>>();
+        <IntoInnerHelper<Cell<u8>, CellDrop> as AmbiguousIfRefUnwindSafe<_>>::some_item();
+        <IntoInnerHelper<&'static mut u8, MutRefDrop> as AmbiguousIfUnwindSafe<_>>::some_item();
+
+        let helper = <IntoInnerHelper<_, StringDrop>>::new(std::string::String::from("guarded"));
+        let length = std::panic::catch_unwind(|| helper.inner().len()).unwrap();
+        assert_eq!(length, 7);
+    }
+
+    #[test]
+    fn inner_addr_is_stable() {
+        use super::{IntoInnerHelper, DetachedDrop};
+
+        enum Dummy {}
+
+        impl DetachedDrop for Dummy {
+            type Implementor = [u32; 4];
+
+            fn drop(_: Self::Implementor) {}
+        }
+
+        let mut helper = <IntoInnerHelper<_, Dummy>>::new([0; 4]);
+        let addr = helper.inner_addr();
+        helper.inner_mut()[2] = 42;
+        assert_eq!(helper.inner_addr(), addr);
+        assert_eq!(helper.inner() as *const _, addr);
+    }
+
+    #[test]
+    fn inner_eq() {
+        use super::{IntoInnerHelper, DetachedDrop};
+        use std::string::String;
+
+        enum Noop {}
+
+        impl DetachedDrop for Noop {
+            type Implementor = String;
+
+            fn drop(_: Self::Implementor) {}
+        }
+
+        let helper = <IntoInnerHelper<_, Noop>>::new(String::from("cached"));
+        let clone = <IntoInnerHelper<_, Noop>>::from_ref(helper.inner());
+        let other = <IntoInnerHelper<_, Noop>>::new(String::from("other"));
+        assert!(helper.inner_eq(&String::from("cached")));
+        assert!(!helper.inner_eq(other.inner()));
+        assert!(clone.inner_eq(helper.inner()));
+        assert!(helper.inner_ptr_eq(&helper));
+        assert!(!helper.inner_ptr_eq(&clone));
+        assert!(!helper.inner_ptr_eq(&other));
+    }
+
+    #[test]
+    fn get_mut() {
+        use super::{IntoInnerHelper, DetachedDrop};
+
+        std::thread_local! {
+            static LAST_DROPPED: core::cell::Cell<u32> = const { core::cell::Cell::new(0) };
+        }
+
+        enum Record {}
+
+        impl DetachedDrop for Record {
+            type Implementor = u32;
+
+            fn drop(value: Self::Implementor) {
+                LAST_DROPPED.with(|last| last.set(value));
+            }
+        }
+
+        let mut helper = <IntoInnerHelper<_, Record>>::new(1);
+        *helper.get_mut() += 41;
+        core::mem::drop(helper);
+        assert_eq!(LAST_DROPPED.with(|last| last.get()), 42);
+    }
+
+    #[test]
+    fn into_via_from_impl() {
+        use super::{IntoInnerHelper, DetachedDrop};
+
+        struct Token(dropcheck::DropToken);
+
+        enum Dummy {}
+
+        impl DetachedDrop for Dummy {
+            type Implementor = Token;
+
+            fn drop(_: Self::Implementor) {
+                panic!("the detached drop must not run");
+            }
+        }
+
+        impl From<IntoInnerHelper<Token, Dummy>> for Token {
+            fn from(helper: IntoInnerHelper<Token, Dummy>) -> Self {
+                helper.into_inner()
+            }
+        }
+
+        let check = dropcheck::DropCheck::new();
+        let (drop_token, drop_state) = check.pair();
+        let helper = <IntoInnerHelper<_, Dummy>>::new(Token(drop_token));
+        let Token(token) = helper.into();
+        assert!(drop_state.is_not_dropped());
+        core::mem::drop(token);
+        assert!(drop_state.is_dropped());
+    }
+
+    #[test]
+    fn split_off() {
+        use super::{IntoInnerHelper, DetachedDrop};
+
+        std::thread_local! {
+            static DROPPED: core::cell::Cell<(usize, usize)> = const { core::cell::Cell::new((0, 0)) };
+        }
+
+        enum PairDrop {}
+
+        impl DetachedDrop for PairDrop {
+            type Implementor = (dropcheck::DropToken, dropcheck::DropToken);
+
+            fn drop(_: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.set((dropped.get().0 + 1, dropped.get().1)));
+            }
+        }
+
+        enum TailDrop {}
+
+        impl DetachedDrop for TailDrop {
+            type Implementor = dropcheck::DropToken;
+
+            fn drop(_: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.set((dropped.get().0, dropped.get().1 + 1)));
+            }
+        }
+
+        let check = dropcheck::DropCheck::new();
+        let (head_token, head_state) = check.pair();
+        let (tail_token, tail_state) = check.pair();
+        let helper = <IntoInnerHelper<_, PairDrop>>::new((head_token, tail_token));
+        let (head, tail) = helper.split_off::<_, _, TailDrop, _>(|pair| pair);
+        core::mem::drop(head);
+        assert!(head_state.is_dropped());
+        assert!(tail_state.is_not_dropped());
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), (0, 0));
+        core::mem::drop(tail);
+        assert!(tail_state.is_dropped());
+        assert_eq!(DROPPED.with(|dropped| dropped.get()), (0, 1));
+    }
+
+    #[test]
+    fn reset() {
+        use super::{IntoInnerHelper, DetachedDrop};
+
+        std::thread_local! {
+            static LAST_DROPPED: core::cell::Cell<Option<u32>> = const { core::cell::Cell::new(None) };
+        }
+
+        enum Record {}
+
+        impl DetachedDrop for Record {
+            type Implementor = u32;
+
+            fn drop(value: Self::Implementor) {
+                LAST_DROPPED.with(|last| last.set(Some(value)));
+            }
+        }
+
+        let mut helper = <IntoInnerHelper<_, Record>>::new(1);
+        assert_eq!(helper.reset(2), 1);
+        assert_eq!(LAST_DROPPED.with(|last| last.get()), None);
+        core::mem::drop(helper);
+        assert_eq!(LAST_DROPPED.with(|last| last.get()), Some(2));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn drop_panicking() {
+        use super::{IntoInnerHelper, DetachedDrop};
+        use core::cell::RefCell;
+
+        std::thread_local! {
+            static CALLED: RefCell<std::vec::Vec<&'static str>> = const { RefCell::new(std::vec::Vec::new()) };
+        }
+
+        fn called() -> std::vec::Vec<&'static str> {
+            CALLED.with(RefCell::take)
+        }
+
+        enum Record {}
+
+        impl DetachedDrop for Record {
+            type Implementor = &'static str;
+
+            fn drop(value: Self::Implementor) {
+                CALLED.with(|called| called.borrow_mut().push(value));
+            }
+
+            fn drop_panicking(_: Self::Implementor, _: super::HookToken) {
+                CALLED.with(|called| called.borrow_mut().push("panicking"));
+            }
+        }
+
+        enum Plain {}
+
+        impl DetachedDrop for Plain {
+            type Implementor = &'static str;
+
+            fn drop(value: Self::Implementor) {
+                CALLED.with(|called| called.borrow_mut().push(value));
+            }
+        }
+
+        core::mem::drop(<IntoInnerHelper<_, Record>>::new("normal"));
+        assert_eq!(called(), ["normal"]);
+
+        let helper = <IntoInnerHelper<_, Record>>::new("normal");
+        let result = std::panic::catch_unwind(move || {
+            let _helper = helper;
+            panic!("unwinding");
+        });
+        assert!(result.is_err());
+        assert_eq!(called(), ["panicking"]);
+
+        let helper = <IntoInnerHelper<_, (Plain, Record)>>::new(("plain", "normal"));
+        let result = std::panic::catch_unwind(move || {
+            let _helper = helper;
+            panic!("unwinding");
+        });
+        assert!(result.is_err());
+        assert_eq!(called(), ["plain", "panicking"]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn skip_on_panic() {
+        use super::{IntoInnerHelper, DetachedDrop};
+        use core::cell::Cell;
+
+        std::thread_local! {
+            static COMMITTED: Cell<usize> = const { Cell::new(0) };
+        }
+
+        enum Commit {}
+
+        impl DetachedDrop for Commit {
+            type Implementor = dropcheck::DropToken;
+
+            const RUN_ON_PANIC: bool = false;
+
+            fn drop(_: Self::Implementor) {
+                COMMITTED.with(|committed| committed.set(committed.get() + 1));
+            }
+        }
+
+        enum Dummy {}
+
+        impl DetachedDrop for Dummy {
+            type Implementor = dropcheck::DropToken;
+
+            fn drop(_: Self::Implementor) {}
+        }
+
+        let check = dropcheck::DropCheck::new();
+        core::mem::drop(<IntoInnerHelper<_, Commit>>::new(check.token()));
+        assert_eq!(COMMITTED.with(Cell::get), 1);
+
+        let (drop_token, drop_state) = check.pair();
+        let helper = <IntoInnerHelper<_, Commit>>::new(drop_token);
+        let result = std::panic::catch_unwind(move || {
+            let _helper = helper;
+            panic!("unwinding");
+        });
+        assert!(result.is_err());
+        assert_eq!(COMMITTED.with(Cell::get), 1);
+        assert!(drop_state.is_dropped());
+
+        let helper = <IntoInnerHelper<_, (Dummy, Commit)>>::new((check.token(), check.token()));
+        let result = std::panic::catch_unwind(move || {
+            let _helper = helper;
+            panic!("unwinding");
+        });
+        assert!(result.is_err());
+        assert_eq!(COMMITTED.with(Cell::get), 1);
+        assert!(check.all_dropped());
+    }
+
+    #[test]
+    fn downcast_inner() {
+        use super::{IntoInnerHelper, DetachedDrop};
+
+        use core::any::Any;
+        use std::boxed::Box;
+
+        enum Dummy {}
+
+        impl DetachedDrop for Dummy {
+            type Implementor = Box<dyn Any>;
+
+            fn drop(_: Self::Implementor) {}
+        }
+
+        fn is_token<T: AsRef<dyn Any>, D: DetachedDrop<Implementor=T>>(helper: &IntoInnerHelper<T, D>) -> bool {
+            helper.downcast_inner::<dropcheck::DropToken>().is_some()
+        }
+
+        let check = dropcheck::DropCheck::new();
+        let (drop_token, drop_state) = check.pair();
+        let helper = <IntoInnerHelper<_, Dummy>>::new(Box::new(drop_token) as Box<dyn Any>);
+        assert!(is_token(&helper));
+        assert!(helper.downcast_inner::<u32>().is_none());
+        assert!(helper.downcast_inner::<Box<dyn Any>>().is_none());
+        assert!(drop_state.is_not_dropped());
+        core::mem::drop(helper);
+        assert!(drop_state.is_dropped());
+
+        let helper = <IntoInnerHelper<_, Dummy>>::new(Box::new(42u32) as Box<dyn Any>);
+        assert_eq!(helper.downcast_inner::<u32>(), Some(&42));
+        assert_eq!(helper.downcast_inner::<i32>(), None);
+    }
+
+    #[test]
+    fn exchange() {
+        use super::{IntoInnerHelper, DetachedDrop};
+
+        std::thread_local! {
+            static DROPPED: core::cell::RefCell<std::vec::Vec<u32>> = const { core::cell::RefCell::new(std::vec::Vec::new()) };
+        }
+
+        enum Record {}
+
+        impl DetachedDrop for Record {
+            type Implementor = u32;
+
+            fn drop(value: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.borrow_mut().push(value));
+            }
+        }
+
+        let mut helper = <IntoInnerHelper<_, Record>>::new(1);
+        assert_eq!(helper.exchange(2), 1);
+        let mut built = false;
+        assert_eq!(helper.exchange_with(|| { built = true; 3 }), 2);
+        assert!(built);
+        assert_eq!(*helper.inner(), 3);
+        assert!(DROPPED.with(|dropped| dropped.borrow().is_empty()));
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| helper.exchange_with(|| panic!("expensive construction failed"))));
+        assert!(result.is_err());
+        assert_eq!(*helper.inner(), 3);
+        core::mem::drop(helper);
+        DROPPED.with(|dropped| assert_eq!(*dropped.borrow(), [3]));
+    }
+
+    #[test]
+    fn transition() {
+        use super::{IntoInnerHelper, DetachedDrop};
+        use core::cell::Cell;
+
+        std::thread_local! {
+            static DROPPED: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+        }
+
+        enum Open {}
+
+        impl DetachedDrop for Open {
+            type Implementor = dropcheck::DropToken;
+
+            fn drop(_: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.set((dropped.get().0 + 1, dropped.get().1)));
+            }
+        }
+
+        enum Closing {}
+
+        impl DetachedDrop for Closing {
+            type Implementor = (dropcheck::DropToken, u32);
+
+            fn drop(_: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.set((dropped.get().0, dropped.get().1 + 1)));
+            }
+        }
+
+        let check = dropcheck::DropCheck::new();
+        let (drop_token, drop_state) = check.pair();
+        let helper = <IntoInnerHelper<_, Open>>::new(drop_token);
+        let closing = helper.transition::<_, Closing, _>(|token| (token, 42));
+        assert_eq!(closing.inner().1, 42);
+        assert_eq!(DROPPED.with(Cell::get), (0, 0));
+        core::mem::drop(closing);
+        assert_eq!(DROPPED.with(Cell::get), (0, 1));
+        assert!(drop_state.is_dropped());
+
+        let (drop_token, drop_state) = check.pair();
+        let helper = <IntoInnerHelper<_, Open>>::new(drop_token);
+        let result = std::panic::catch_unwind(move || helper.transition::<_, Closing, _>(|_| panic!("transition failed")));
+        assert!(result.is_err());
+        assert!(drop_state.is_dropped());
+        assert_eq!(DROPPED.with(Cell::get), (0, 1));
+    }
+
+    #[test]
+    fn try_map() {
+        use super::{IntoInnerHelper, DetachedDrop};
+        use core::cell::Cell;
+
+        std::thread_local! {
+            static DROPPED: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+        }
+
+        enum StringDrop {}
+
+        impl DetachedDrop for StringDrop {
+            type Implementor = std::string::String;
+
+            fn drop(_: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.set((dropped.get().0 + 1, dropped.get().1)));
+            }
+        }
+
+        enum NumberDrop {}
+
+        impl DetachedDrop for NumberDrop {
+            type Implementor = u32;
+
+            fn drop(_: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.set((dropped.get().0, dropped.get().1 + 1)));
+            }
+        }
+
+        fn parse(string: std::string::String) -> Result<u32, (std::string::String, core::num::ParseIntError)> {
+            match string.parse() {
+                Ok(number) => Ok(number),
+                Err(error) => Err((string, error)),
+            }
+        }
+
+        let helper = <IntoInnerHelper<_, StringDrop>>::new("42".into());
+        let number = helper.try_map::<_, NumberDrop, _, _>(parse).unwrap();
+        assert_eq!(*number.inner(), 42);
+        assert_eq!(DROPPED.with(Cell::get), (0, 0));
+        core::mem::drop(number);
+        assert_eq!(DROPPED.with(Cell::get), (0, 1));
+
+        let helper = <IntoInnerHelper<_, StringDrop>>::new("foo".into());
+        let (_, helper) = helper.try_map::<_, NumberDrop, _, _>(parse).unwrap_err();
+        assert_eq!(helper.inner(), "foo");
+        assert_eq!(DROPPED.with(Cell::get), (0, 1));
+        core::mem::drop(helper);
+        assert_eq!(DROPPED.with(Cell::get), (1, 1));
+
+        let helper = <IntoInnerHelper<_, StringDrop>>::new("foo".into());
+        let helper = helper.map::<_, NumberDrop, _>(|string| string.len() as u32);
+        assert_eq!(*helper.inner(), 3);
+        core::mem::drop(helper);
+        assert_eq!(DROPPED.with(Cell::get), (1, 2));
+    }
+
+    #[test]
+    fn project() {
+        use super::{IntoInnerHelper, DetachedDrop};
+
+        struct Config {
+            name: std::string::String,
+            retries: u32,
+        }
+
+        enum ConfigDrop {}
+
+        impl DetachedDrop for ConfigDrop {
+            type Implementor = Config;
+
+            fn drop(_: Self::Implementor) {}
+        }
+
+        fn name(helper: &IntoInnerHelper<Config, ConfigDrop>) -> &str {
+            helper.project(|config| &*config.name)
+        }
+
+        let mut helper = <IntoInnerHelper<_, ConfigDrop>>::new(Config { name: "foo".into(), retries: 0 });
+        *helper.project_mut(|config| &mut config.retries) += 3;
+        helper.project_mut(|config| &mut config.name).push_str("bar");
+        assert_eq!(name(&helper), "foobar");
+        assert_eq!(*helper.project(|config| &config.retries), 3);
+    }
+
+    #[test]
+    fn with_drop_guard() {
+        use super::{IntoInnerHelper, DetachedDrop, WithDropGuard};
+        use core::cell::RefCell;
+
+        std::thread_local! {
+            static DROPPED: RefCell<std::vec::Vec<u32>> = const { RefCell::new(std::vec::Vec::new()) };
+        }
+
+        enum Record {}
+
+        impl DetachedDrop for Record {
+            type Implementor = u32;
+
+            fn drop(value: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.borrow_mut().push(value));
+            }
+        }
+
+        let guards = (1..4).map(|value| value * 10).map(super::with_drop_guard::<_, Record>).collect::<std::vec::Vec<_>>();
+        assert_eq!(*guards[1], 20);
+        assert!(DROPPED.with(|dropped| dropped.borrow().is_empty()));
+        core::mem::drop(guards);
+        DROPPED.with(|dropped| assert_eq!(dropped.take(), [10, 20, 30]));
+
+        let guard: IntoInnerHelper<_, Record> = 21u32.pow(1).wrapping_mul(2).with_drop_guard();
+        assert_eq!(guard.into_inner(), 42);
+        assert!(DROPPED.with(|dropped| dropped.borrow().is_empty()));
+    }
+
+    #[test]
+    fn const_defuse() {
+        use super::{IntoInnerHelper, DetachedDrop};
+        use std::sync::Mutex;
+
+        enum PanicDrop {}
+
+        impl DetachedDrop for PanicDrop {
+            type Implementor = u32;
+
+            fn drop(_: Self::Implementor) {
+                panic!("defused guard dropped");
+            }
+        }
+
+        const DEFUSED: u32 = IntoInnerHelper::<_, PanicDrop>::new_const(42).defuse();
+        static GUARD: Mutex<Option<IntoInnerHelper<u32, PanicDrop>>> = Mutex::new(Some(IntoInnerHelper::new_const(21)));
+
+        assert_eq!(DEFUSED, 42);
+        let guard = GUARD.lock().unwrap().take().unwrap();
+        assert_eq!(*guard, 21);
+        assert_eq!(guard.defuse(), 21);
+    }
+
+    #[test]
+    fn cloned() {
+        use super::{IntoInnerHelper, DetachedDrop};
+        use core::cell::RefCell;
+        use std::string::String;
+        use std::vec::Vec;
+
+        std::thread_local! {
+            static DROPPED: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+        }
+
+        enum Record {}
+
+        impl DetachedDrop for Record {
+            type Implementor = String;
+
+            fn drop(value: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.borrow_mut().push(value));
+            }
+        }
+
+        let mut helper = <IntoInnerHelper<_, Record>>::new(String::from("foo"));
+        let mut snapshot = helper.cloned();
+        snapshot.push_str("bar");
+        helper.push_str("baz");
+        core::mem::drop(snapshot);
+        assert!(DROPPED.with(|dropped| dropped.borrow().is_empty()));
+        core::mem::drop(helper);
+        DROPPED.with(|dropped| assert_eq!(*dropped.borrow(), ["foobaz"]));
+    }
+
+    #[test]
+    fn sort_guards() {
+        use super::{IntoInnerHelper, DetachedDrop};
+        use core::cell::Cell;
+
+        std::thread_local! {
+            static DROPPED: Cell<usize> = const { Cell::new(0) };
+        }
+
+        enum CountDrop {}
+
+        impl DetachedDrop for CountDrop {
+            type Implementor = (u32, &'static str);
+
+            fn drop(_: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+            }
+        }
+
+        let mut connections = [(30, "c"), (10, "a"), (20, "b"), (15, "d")].map(<IntoInnerHelper<_, CountDrop>>::new);
+        super::sort_guards(&mut connections);
+        assert_eq!(connections.each_ref().map(|connection| connection.1), ["a", "d", "b", "c"]);
+        assert!(connections[0] < connections[2]);
+        assert_eq!(connections.iter().max().map(|max| max.1), Some("c"));
+        assert_eq!(DROPPED.with(Cell::get), 0);
+        core::mem::drop(connections);
+        assert_eq!(DROPPED.with(Cell::get), 4);
+    }
+
+    #[test]
+    fn swap_inner() {
+        use super::{IntoInnerHelper, DetachedDrop};
+        use core::cell::RefCell;
+        use std::vec::Vec;
+
+        std::thread_local! {
+            static EVENTS: RefCell<Vec<(&'static str, u32)>> = const { RefCell::new(Vec::new()) };
+        }
+
+        enum Commit {}
+
+        impl DetachedDrop for Commit {
+            type Implementor = u32;
+
+            fn drop(value: Self::Implementor) {
+                EVENTS.with(|events| events.borrow_mut().push(("commit", value)));
+            }
+        }
+
+        enum Rollback {}
+
+        impl DetachedDrop for Rollback {
+            type Implementor = u32;
+
+            fn drop(value: Self::Implementor) {
+                EVENTS.with(|events| events.borrow_mut().push(("rollback", value)));
+            }
+        }
+
+        let mut commit = <IntoInnerHelper<_, Commit>>::new(1);
+        let mut rollback = <IntoInnerHelper<_, Rollback>>::new(2);
+        super::swap_inner(&mut commit, &mut rollback);
+        assert_eq!((*commit.inner(), *rollback.inner()), (2, 1));
+        assert!(EVENTS.with(|events| events.borrow().is_empty()));
+        core::mem::drop(commit);
+        core::mem::drop(rollback);
+        assert_eq!(EVENTS.with(RefCell::take), [("commit", 2), ("rollback", 1)]);
+    }
+
+    #[test]
+    fn clone_into_existing() {
+        use super::{IntoInnerHelper, DetachedDrop};
+        use core::cell::Cell;
+        use std::string::String;
+        use std::vec::Vec;
+
+        std::thread_local! {
+            static DROPPED: Cell<usize> = const { Cell::new(0) };
+        }
+
+        struct CountDrop<T>(core::convert::Infallible, core::marker::PhantomData<T>);
+
+        impl<T> DetachedDrop for CountDrop<T> {
+            type Implementor = T;
+
+            fn drop(_: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+            }
+        }
+
+        let helper = <IntoInnerHelper<_, CountDrop<_>>>::new(String::from("Hello world!"));
+        let mut target = String::with_capacity(64);
+        let address = target.as_ptr();
+        helper.clone_into_existing(&mut target);
+        assert_eq!(target, "Hello world!");
+        assert_eq!(target.as_ptr(), address);
+
+        let helper_vec = <IntoInnerHelper<_, CountDrop<_>>>::new(std::vec![1u32, 2, 3]);
+        let mut target_vec = Vec::with_capacity(16);
+        target_vec.push(42);
+        let address = target_vec.as_ptr();
+        helper_vec.clone_into_existing(&mut target_vec);
+        assert_eq!(target_vec, [1, 2, 3]);
+        assert_eq!(target_vec.as_ptr(), address);
+
+        assert_eq!(DROPPED.with(Cell::get), 0);
+        core::mem::drop((helper, helper_vec));
+        assert_eq!(DROPPED.with(Cell::get), 2);
+    }
+
+    #[test]
+    fn from_ref() {
+        use super::{IntoInnerHelper, DetachedDrop};
+        use core::cell::RefCell;
+        use std::string::String;
+        use std::vec::Vec;
+
+        std::thread_local! {
+            static DROPPED: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+        }
+
+        enum Record {}
+
+        impl DetachedDrop for Record {
+            type Implementor = String;
+
+            fn drop(value: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.borrow_mut().push(value));
+            }
+        }
+
+        let name = String::from("foo");
+        let mut helper = <IntoInnerHelper<_, Record>>::from_ref(&name);
+        helper.push_str("bar");
+        let other: IntoInnerHelper<_, Record> = (&name).into();
+        let owned: IntoInnerHelper<_, Record> = String::from("baz").into();
+        core::mem::drop((helper, other, owned));
+        assert_eq!(name, "foo");
+        assert_eq!(DROPPED.with(|dropped| dropped.take()), ["foobar", "foo", "baz"]);
+    }
+
+    #[test]
+    fn set_drop_if() {
+        use super::{IntoInnerHelper, DetachedDrop};
+        use core::cell::Cell;
+
+        std::thread_local! {
+            static LAST: Cell<Option<(&'static str, u32)>> = const { Cell::new(None) };
+        }
+
+        enum Commit {}
+
+        impl DetachedDrop for Commit {
+            type Implementor = u32;
+
+            fn drop(value: Self::Implementor) {
+                assert!(LAST.with(|last| last.replace(Some(("commit", value)))).is_none());
+            }
+        }
+
+        enum Rollback {}
+
+        impl DetachedDrop for Rollback {
+            type Implementor = u32;
+
+            fn drop(value: Self::Implementor) {
+                assert!(LAST.with(|last| last.replace(Some(("rollback", value)))).is_none());
+            }
+        }
+
+        let transaction = <IntoInnerHelper<_, Rollback>>::new(3);
+        let transaction = transaction.set_drop_if::<Commit, _>(|changes| *changes == 0).unwrap_err();
+        assert_eq!(LAST.with(Cell::get), None);
+        core::mem::drop(transaction);
+        assert_eq!(LAST.with(|last| last.take()), Some(("rollback", 3)));
+
+        let transaction = <IntoInnerHelper<_, Rollback>>::new(3);
+        let transaction = transaction.set_drop_if::<Commit, _>(|changes| *changes > 0).unwrap();
+        assert_eq!(LAST.with(Cell::get), None);
+        core::mem::drop(transaction);
+        assert_eq!(LAST.with(|last| last.take()), Some(("commit", 3)));
+    }
+
+    #[test]
+    fn as_str() {
+        use super::{IntoInnerHelper, DetachedDrop};
+
+        enum NoopDrop {}
+
+        impl DetachedDrop for NoopDrop {
+            type Implementor = std::string::String;
+
+            fn drop(_: Self::Implementor) {}
+        }
+
+        let helper = <IntoInnerHelper<_, NoopDrop>>::new("Hello world!".into());
+        assert_eq!(helper.as_str(), "Hello world!");
+        assert_eq!(&helper.as_str()[6..], "world!");
+    }
+
+    #[test]
+    fn finalize_then() {
+        use super::{IntoInnerHelper, DetachedDrop};
+        use core::cell::RefCell;
+        use std::vec::Vec;
+
+        std::thread_local! {
+            static EVENTS: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+        }
+
+        enum Record {}
+
+        impl DetachedDrop for Record {
+            type Implementor = &'static str;
+
+            fn drop(event: Self::Implementor) {
+                EVENTS.with(|events| events.borrow_mut().push(event));
+            }
+        }
+
+        let helper = <IntoInnerHelper<_, Record>>::new("closed");
+        let result = helper.finalize_then(|| {
+            EVENTS.with(|events| events.borrow_mut().push("reconnected"));
+            42
+        });
+        assert_eq!(result, 42);
+        assert_eq!(EVENTS.with(|events| events.take()), ["closed", "reconnected"]);
+    }
+
+    #[test]
+    fn try_take_part() {
+        use super::{IntoInnerHelper, DetachedDrop};
+        use core::cell::RefCell;
+        use std::vec::Vec;
+
+        std::thread_local! {
+            static REMAINING: RefCell<Option<Vec<u32>>> = const { RefCell::new(None) };
+        }
+
+        enum Record {}
+
+        impl DetachedDrop for Record {
+            type Implementor = Vec<u32>;
+
+            fn drop(remaining: Self::Implementor) {
+                REMAINING.with(|stored| assert!(stored.replace(Some(remaining)).is_none()));
+            }
+        }
+
+        let mut helper = <IntoInnerHelper<_, Record>>::new(std::vec![1, 2, 3, 4]);
+        assert_eq!(helper.try_take_part(Vec::pop), Some(4));
+        assert_eq!(helper.try_take_part(|items| items.first().copied().map(|_| items.remove(0))), Some(1));
+        assert_eq!(helper.try_take_part(|items| items.iter().position(|&item| item > 10).map(|i| items.remove(i))), None);
+        let result = std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| helper.try_take_part(|_| -> Option<u32> { panic!("failed") })));
+        assert!(result.is_err());
+        assert_eq!(REMAINING.with(|stored| stored.borrow().clone()), None);
+        core::mem::drop(helper);
+        assert_eq!(REMAINING.with(RefCell::take), Some(std::vec![2, 3]));
+    }
+
+    #[test]
+    fn defuse_into() {
+        use super::{IntoInnerHelper, DetachedDrop};
+        use core::cell::RefCell;
+        use std::vec::Vec;
+
+        std::thread_local! {
+            static EVENTS: RefCell<Vec<(&'static str, Option<u32>)>> = const { RefCell::new(Vec::new()) };
+        }
+
+        enum Local {}
+
+        impl DetachedDrop for Local {
+            type Implementor = u32;
+
+            fn drop(value: Self::Implementor) {
+                EVENTS.with(|events| events.borrow_mut().push(("local", Some(value))));
+            }
+        }
+
+        enum Outer {}
+
+        impl DetachedDrop for Outer {
+            type Implementor = Option<u32>;
+
+            fn drop(value: Self::Implementor) {
+                EVENTS.with(|events| events.borrow_mut().push(("outer", value)));
+            }
+        }
+
+        let mut target = <IntoInnerHelper<_, Outer>>::new(None);
+        {
+            let local = <IntoInnerHelper<_, Local>>::new(1);
+            assert_eq!(local.defuse_into(&mut target), None);
+        }
+        assert_eq!(*target.inner(), Some(1));
+        assert_eq!(<IntoInnerHelper<_, Local>>::new(2).defuse_into(&mut target), Some(1));
+        assert!(EVENTS.with(|events| events.borrow().is_empty()));
+        core::mem::drop(target);
+        assert_eq!(EVENTS.with(RefCell::take), [("outer", Some(2))]);
+    }
+
+    #[test]
+    fn into_inner_map() {
+        use super::{IntoInnerHelper, DetachedDrop};
+        use std::string::{String, ToString};
+
+        enum Forbidden {}
+
+        impl DetachedDrop for Forbidden {
+            type Implementor = u32;
+
+            fn drop(_: Self::Implementor) {
+                panic!("detached drop must not run");
+            }
+        }
+
+        let helper = <IntoInnerHelper<_, Forbidden>>::new(42);
+        assert_eq!(helper.into_inner_map(|value| value.to_string()), String::from("42"));
+        let helper = <IntoInnerHelper<_, Forbidden>>::new(42);
+        let result = std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| helper.into_inner_map(|_| -> u32 { panic!("conversion failed") })));
+        assert_eq!(result.unwrap_err().downcast_ref::<&str>(), Some(&"conversion failed"));
+    }
+
+    #[test]
+    fn into_inner_with_reason() {
+        use super::{IntoInnerHelper, DetachedDrop};
+
+        enum Forbidden {}
+
+        impl DetachedDrop for Forbidden {
+            type Implementor = u32;
+
+            fn drop(_: Self::Implementor) {
+                panic!("detached drop must not run");
+            }
+        }
+
+        struct Extracted(u32);
+
+        impl DetachedDrop for Extracted {
+            type Implementor = Self;
+
+            fn drop(_: Self::Implementor) {
+                panic!("detached drop must not run");
+            }
+        }
+
+        let helper = <IntoInnerHelper<_, Forbidden>>::new(42);
+        assert_eq!(helper.into_inner_with_reason("handed over to the pool"), 42);
+        let helper = <IntoInnerHelper<_, Extracted>>::new(Extracted(42));
+        assert_eq!(helper.into_inner_with_reason("connection reused by the caller").0, 42);
+        #[cfg(feature = "log")]
+        {
+            let records = crate::labeled::tests::logger::records("connection reused by the caller");
+            assert_eq!(records.len(), 1);
+            assert!(records[0].ends_with("::Extracted: extracted inner value: connection reused by the caller"));
+        }
+    }
+
+    #[cfg(not(all(feature = "checked-build", debug_assertions)))]
+    mod zst {
+        use core::cell::Cell;
+        use core::mem::{size_of, align_of};
+        use crate::{IntoInnerHelper, DetachedDrop};
+
+        std::thread_local! {
+            static DROPPED: Cell<usize> = const { Cell::new(0) };
+        }
+
+        fn dropped() -> usize {
+            DROPPED.with(Cell::get)
+        }
+
+        struct Marker;
+
+        enum MarkerDrop {}
+
+        impl DetachedDrop for MarkerDrop {
+            type Implementor = Marker;
+
+            fn drop(_: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+            }
+        }
+
+        enum UnitDrop {}
+
+        impl DetachedDrop for UnitDrop {
+            type Implementor = ();
+
+            fn drop(_: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+            }
+        }
+
+        enum U64Drop {}
+
+        impl DetachedDrop for U64Drop {
+            type Implementor = u64;
+
+            fn drop(_: Self::Implementor) {}
+        }
+
+        #[test]
+        fn zero_sized() {
+            assert_eq!(size_of::<IntoInnerHelper<(), UnitDrop>>(), 0);
+            assert_eq!(size_of::<IntoInnerHelper<Marker, MarkerDrop>>(), 0);
+            assert_eq!(align_of::<IntoInnerHelper<Marker, MarkerDrop>>(), 1);
+        }
+
+        #[test]
+        fn same_layout_as_inner() {
+            assert_eq!(size_of::<IntoInnerHelper<u64, U64Drop>>(), size_of::<u64>());
+            assert_eq!(align_of::<IntoInnerHelper<u64, U64Drop>>(), align_of::<u64>());
+        }
+
+        #[test]
+        fn unit_drop() {
+            let mut helper = <IntoInnerHelper<_, UnitDrop>>::new(());
+            let () = *helper.inner();
+            let () = *helper.inner_mut();
+            core::mem::drop(helper);
+            assert_eq!(dropped(), 1);
+        }
+
+        #[test]
+        fn unit_into_inner() {
+            let helper = <IntoInnerHelper<_, UnitDrop>>::new(());
+            let () = helper.into_inner();
+            assert_eq!(dropped(), 0);
+        }
+
+        #[test]
+        fn marker_drop() {
+            let helpers = [
+                <IntoInnerHelper<_, MarkerDrop>>::new(Marker),
+                <IntoInnerHelper<_, MarkerDrop>>::new(Marker),
+                <IntoInnerHelper<_, MarkerDrop>>::new(Marker),
+            ];
+            assert_eq!(core::mem::size_of_val(&helpers), 0);
+            let [first, second, third] = helpers;
+            let Marker = first.into_inner();
+            core::mem::drop(second);
+            third.finalize();
+            assert_eq!(dropped(), 2);
+        }
+    }
+
+    // These only check anything when run through clippy: an unfulfilled expectation is a clippy
+    // warning, so they fail the lint pass if the helper is no longer treated as significant.
+    mod significant_drop {
+        use crate::{IntoInnerHelper, DetachedDrop};
+
+        enum Flush {}
+
+        impl DetachedDrop for Flush {
+            type Implementor = u32;
+
+            fn drop(_: Self::Implementor) {}
+        }
+
+        fn guard(value: u32) -> IntoInnerHelper<u32, Flush> {
+            IntoInnerHelper::new(value)
+        }
+
+        #[test]
+        #[expect(clippy::significant_drop_in_scrutinee)]
+        fn in_scrutinee() {
+            match *guard(42).inner() {
+                42 => (),
+                _ => panic!("unexpected value"),
+            }
+        }
+
+        #[test]
+        #[expect(clippy::significant_drop_tightening)]
+        fn tightening() {
+            let helper = guard(42);
+            let value = *helper.inner();
+            assert_eq!(value * 2, 84);
+            assert_eq!(value + 1, 43);
+        }
+    }
 }
@@ -0,0 +1,650 @@
+//! Macros generating forwarding impls for public newtypes over helpers.
+
+/// Implements `PartialEq` and `Eq` for a newtype over a helper by comparing the inner values.
+///
+/// `forward_eq!(Newtype => Inner)` requires `Newtype` to be a tuple struct wrapping a helper of
+/// `Inner` as its first field. Apart from `PartialEq<Newtype>` it also implements
+/// `PartialEq<Inner>` so the newtype can be compared with a raw value.
+///
+/// ```
+/// use into_inner_drop::{IntoInnerHelper, DetachedDrop, forward_eq};
+///
+/// enum NoopDrop {}
+///
+/// impl DetachedDrop for NoopDrop {
+///     type Implementor = u32;
+///
+///     fn drop(_: u32) {}
+/// }
+///
+/// pub struct Id(IntoInnerHelper<u32, NoopDrop>);
+///
+/// forward_eq!(Id => u32);
+///
+/// assert!(Id(IntoInnerHelper::new(42)) == 42);
+/// ```
+#[macro_export]
+macro_rules! forward_eq {
+    ($newtype:ty => $inner:ty) => {
+        impl ::core::cmp::PartialEq for $newtype {
+            fn eq(&self, other: &Self) -> bool {
+                <$inner as ::core::cmp::PartialEq>::eq(self.0.inner(), other.0.inner())
+            }
+        }
+
+        impl ::core::cmp::Eq for $newtype {}
+
+        impl ::core::cmp::PartialEq<$inner> for $newtype {
+            fn eq(&self, other: &$inner) -> bool {
+                <$inner as ::core::cmp::PartialEq>::eq(self.0.inner(), other)
+            }
+        }
+    };
+}
+
+/// Implements `PartialEq` between two different newtypes over helpers by comparing the inner values.
+///
+/// `forward_cross_eq!(Left => LeftInner, Right => RightInner)` has the same requirements as
+/// `forward_eq!` for both newtypes and additionally requires `LeftInner: PartialEq<RightInner>`
+/// and vice versa. It implements both `PartialEq<Right> for Left` and `PartialEq<Left> for Right`.
+/// Comparing only borrows the helpers, so no detached drop runs.
+#[macro_export]
+macro_rules! forward_cross_eq {
+    ($left:ty => $left_inner:ty, $right:ty => $right_inner:ty) => {
+        impl ::core::cmp::PartialEq<$right> for $left {
+            fn eq(&self, other: &$right) -> bool {
+                <$left_inner as ::core::cmp::PartialEq<$right_inner>>::eq(self.0.inner(), other.0.inner())
+            }
+        }
+
+        impl ::core::cmp::PartialEq<$left> for $right {
+            fn eq(&self, other: &$left) -> bool {
+                <$right_inner as ::core::cmp::PartialEq<$left_inner>>::eq(self.0.inner(), other.0.inner())
+            }
+        }
+    };
+}
+
+/// Implements `PartialOrd` and `Ord` for a newtype over a helper by comparing the inner values.
+///
+/// The requirements are the same as for `forward_eq!`, which has to be invoked too because `Ord`
+/// requires `Eq`. `PartialOrd<Inner>` is implemented as well.
+#[macro_export]
+macro_rules! forward_ord {
+    ($newtype:ty => $inner:ty) => {
+        impl ::core::cmp::PartialOrd for $newtype {
+            fn partial_cmp(&self, other: &Self) -> ::core::option::Option<::core::cmp::Ordering> {
+                ::core::option::Option::Some(<Self as ::core::cmp::Ord>::cmp(self, other))
+            }
+        }
+
+        impl ::core::cmp::Ord for $newtype {
+            fn cmp(&self, other: &Self) -> ::core::cmp::Ordering {
+                <$inner as ::core::cmp::Ord>::cmp(self.0.inner(), other.0.inner())
+            }
+        }
+
+        impl ::core::cmp::PartialOrd<$inner> for $newtype {
+            fn partial_cmp(&self, other: &$inner) -> ::core::option::Option<::core::cmp::Ordering> {
+                <$inner as ::core::cmp::PartialOrd>::partial_cmp(self.0.inner(), other)
+            }
+        }
+    };
+}
+
+/// Implements `Hash` for a newtype over a helper by hashing the inner value.
+///
+/// The hash is consistent with the impls generated by `forward_eq!`.
+#[macro_export]
+macro_rules! forward_hash {
+    ($newtype:ty => $inner:ty) => {
+        impl ::core::hash::Hash for $newtype {
+            fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+                <$inner as ::core::hash::Hash>::hash(self.0.inner(), state)
+            }
+        }
+    };
+}
+
+/// Declares a struct whose fields are dropped in the annotated order.
+///
+/// Every field has to be annotated with `#[drop_order(n)]`, fields with lower `n` are dropped
+/// first, regardless of declaration order. Using the same order twice is a compile-time error.
+/// The fields are stored in `ManuallyDrop`, which dereferences to the field type, and the struct
+/// gets a `new` constructor taking the fields in declaration order and an `into_parts` method
+/// returning them without dropping.
+///
+/// The generated code uses `unsafe` internally so it can't be used in crates forbidding it. If
+/// dropping a field panics, the fields that should be dropped after it are leaked.
+///
+/// ```
+/// use into_inner_drop::ordered_drop;
+///
+/// ordered_drop! {
+///     /// The connection has to be closed after the transaction is rolled back.
+///     pub struct Session {
+///         #[drop_order(1)]
+///         connection: String,
+///         #[drop_order(0)]
+///         transaction: Vec<u8>,
+///     }
+/// }
+///
+/// let session = Session::new("db".to_owned(), Vec::new());
+/// assert_eq!(session.connection.len(), 2);
+/// let (connection, _transaction) = session.into_parts();
+/// assert_eq!(connection, "db");
+/// ```
+///
+/// ```compile_fail
+/// into_inner_drop::ordered_drop! {
+///     struct Duplicate {
+///         #[drop_order(0)]
+///         first: String,
+///         #[drop_order(0)]
+///         second: String,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! ordered_drop {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[doc = $doc:literal])*
+                #[drop_order($order:literal)]
+                $field_vis:vis $field:ident: $ty:ty
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $(
+                $(#[doc = $doc])*
+                $field_vis $field: ::core::mem::ManuallyDrop<$ty>,
+            )*
+        }
+
+        const _: () = {
+            let orders: &[usize] = &[$($order),*];
+            let mut i = 0;
+            while i < orders.len() {
+                let mut j = i + 1;
+                while j < orders.len() {
+                    assert!(orders[i] != orders[j], "duplicate drop order");
+                    j += 1;
+                }
+                i += 1;
+            }
+        };
+
+        impl $name {
+            /// Creates the value from its fields in declaration order.
+            #[allow(clippy::too_many_arguments)]
+            $vis fn new($($field: $ty),*) -> Self {
+                $name {
+                    $($field: ::core::mem::ManuallyDrop::new($field),)*
+                }
+            }
+
+            /// Returns the fields in declaration order without dropping them.
+            $vis fn into_parts(self) -> ($($ty,)*) {
+                let mut this = ::core::mem::ManuallyDrop::new(self);
+                // SAFETY: each field is taken exactly once and `this` is never dropped.
+                unsafe {
+                    ($(::core::mem::ManuallyDrop::take(&mut this.$field),)*)
+                }
+            }
+        }
+
+        impl ::core::ops::Drop for $name {
+            fn drop(&mut self) {
+                let mut orders = [$($order),*];
+                orders.sort_unstable();
+                for order in orders {
+                    $(
+                        if order == $order {
+                            // SAFETY: the orders are unique (checked above), so each field is
+                            // dropped exactly once.
+                            unsafe {
+                                ::core::mem::ManuallyDrop::drop(&mut self.$field);
+                            }
+                        }
+                    )*
+                }
+            }
+        }
+    };
+}
+
+/// Creates a closure guard living until the end of the enclosing block.
+///
+/// Binding the guard by the macro avoids the common mistake of dropping it immediately by writing
+/// `let _ = guard(..)`. There are three modes:
+///
+/// * `guard_scope!(name = expr, on_drop = closure)` binds the guard to `name`, so it can be
+///   cancelled by calling `into_inner`.
+/// * `guard_scope!(no cancel, name = expr, on_drop = closure)` binds a mutable reference to the
+///   guarded value to `name`, so it can't be cancelled at all.
+/// * `guard_scope!(cancel as handle, name = expr, on_drop = closure)` binds the guard to `name`
+///   and a `CancelHandle` to `handle`. Calling `handle.cancel()` prevents calling the closure.
+///
+/// ```
+/// use into_inner_drop::guard_scope;
+///
+/// let mut log = Vec::new();
+/// {
+///     guard_scope!(no cancel, entries = &mut log, on_drop = |entries: &mut Vec<_>| entries.push("end"));
+///     entries.push("start");
+/// }
+/// assert_eq!(log, ["start", "end"]);
+/// ```
+#[macro_export]
+macro_rules! guard_scope {
+    (no cancel, $name:ident = $inner:expr, on_drop = $on_drop:expr $(,)?) => {
+        let mut guard = $crate::guard($inner, $on_drop);
+        #[allow(unused_variables)]
+        let $name = guard.inner_mut();
+    };
+    (cancel as $handle:ident, $name:ident = $inner:expr, on_drop = $on_drop:expr $(,)?) => {
+        let $handle = $crate::CancelHandle::new();
+        #[allow(unused_variables)]
+        let $name = {
+            let on_drop = $on_drop;
+            let handle = &$handle;
+            $crate::guard($inner, move |inner| if !handle.is_cancelled() {
+                on_drop(inner)
+            })
+        };
+    };
+    ($name:ident = $inner:expr, on_drop = $on_drop:expr $(,)?) => {
+        #[allow(unused_variables)]
+        let $name = $crate::guard($inner, $on_drop);
+    };
+}
+
+/// Declares a newtype over a helper together with its drop behavior.
+///
+/// The newtype gets `new`, `inner`, `inner_mut` and `into_inner` methods with the visibility of
+/// the struct. Optionally, `test name = sample;` generates a unit test module `name` checking
+/// that dropping `sample` runs the drop body exactly once and that `into_inner` suppresses it.
+/// The test and the instrumentation it needs only exist in `cfg(test)` builds and require `std`.
+///
+/// ```
+/// use into_inner_drop::guarded_newtype;
+///
+/// guarded_newtype! {
+///     /// A connection closed on drop.
+///     pub struct Connection(String);
+///
+///     fn drop(address) {
+///         println!("closing connection to {}", address);
+///     }
+///
+///     test connection_guard = String::from("localhost");
+/// }
+///
+/// let connection = Connection::new(String::from("localhost"));
+/// assert_eq!(connection.inner(), "localhost");
+/// // prints: closing connection to localhost
+/// ```
+#[macro_export]
+macro_rules! guarded_newtype {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident($inner:ty);
+
+        fn drop($value:ident) $body:block
+
+        $(test $test:ident = $sample:expr;)?
+    ) => {
+        $(#[$meta])*
+        $vis struct $name($crate::IntoInnerHelper<$inner, $crate::NewtypeDrop<$name>>);
+
+        impl $crate::GuardedNewtype for $name {
+            type Inner = $inner;
+
+            fn drop_inner($value: Self::Inner) {
+                $(
+                    #[cfg(test)]
+                    $test::DROPS.with(|drops| drops.set(drops.get() + 1));
+                )?
+                $body
+            }
+        }
+
+        impl $name {
+            /// Creates the guard.
+            $vis fn new(inner: $inner) -> Self {
+                $name($crate::IntoInnerHelper::new(inner))
+            }
+
+            /// Accesses the inner value.
+            #[allow(dead_code)]
+            $vis fn inner(&self) -> &$inner {
+                self.0.inner()
+            }
+
+            /// Accesses the inner value mutably.
+            #[allow(dead_code)]
+            $vis fn inner_mut(&mut self) -> &mut $inner {
+                self.0.inner_mut()
+            }
+
+            /// Moves out the inner value without running the drop body.
+            #[allow(dead_code)]
+            $vis fn into_inner(self) -> $inner {
+                self.0.into_inner()
+            }
+        }
+
+        $(
+            #[cfg(test)]
+            mod $test {
+                ::std::thread_local! {
+                    pub(super) static DROPS: ::core::cell::Cell<usize> = const { ::core::cell::Cell::new(0) };
+                }
+
+                fn drops() -> usize {
+                    DROPS.with(::core::cell::Cell::take)
+                }
+
+                #[test]
+                fn drop_once() {
+                    ::core::mem::drop(super::$name::new($sample));
+                    assert_eq!(drops(), 1, "the drop body must run exactly once");
+                }
+
+                #[test]
+                fn into_inner_suppresses_drop() {
+                    let _inner = super::$name::new($sample).into_inner();
+                    assert_eq!(drops(), 0, "into_inner must not run the drop body");
+                }
+            }
+        )?
+    };
+}
+
+/// Implemented by newtypes declared using `guarded_newtype!`.
+#[doc(hidden)]
+pub trait GuardedNewtype {
+    type Inner;
+
+    fn drop_inner(value: Self::Inner);
+}
+
+/// The marker of newtypes declared using `guarded_newtype!`.
+#[doc(hidden)]
+pub struct NewtypeDrop<N>(core::convert::Infallible, core::marker::PhantomData<N>);
+
+impl<N> crate::DetachedDrop for NewtypeDrop<N> where N: GuardedNewtype {
+    type Implementor = N::Inner;
+
+    fn drop(value: Self::Implementor) {
+        N::drop_inner(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::String;
+    use std::borrow::ToOwned;
+    use std::collections::HashSet;
+    use core::cell::Cell;
+    use crate::{IntoInnerHelper, DetachedDrop};
+
+    std::thread_local! {
+        static PRINTED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    mod inner {
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub(super) struct PrintOnDrop(pub(super) super::String);
+
+        pub(super) enum PrintOnDropImpl {}
+    }
+
+    impl DetachedDrop for inner::PrintOnDropImpl {
+        type Implementor = inner::PrintOnDrop;
+
+        fn drop(_: Self::Implementor) {
+            PRINTED.with(|printed| printed.set(printed.get() + 1));
+        }
+    }
+
+    struct PrintOnDrop(IntoInnerHelper<inner::PrintOnDrop, inner::PrintOnDropImpl>);
+
+    impl PrintOnDrop {
+        fn new(string: &str) -> Self {
+            PrintOnDrop(IntoInnerHelper::new(inner::PrintOnDrop(string.to_owned())))
+        }
+    }
+
+    forward_eq!(PrintOnDrop => inner::PrintOnDrop);
+    forward_ord!(PrintOnDrop => inner::PrintOnDrop);
+    forward_hash!(PrintOnDrop => inner::PrintOnDrop);
+
+    #[test]
+    fn eq() {
+        let hello = PrintOnDrop::new("hello");
+        assert!(hello == PrintOnDrop::new("hello"));
+        assert!(hello != PrintOnDrop::new("world"));
+        assert!(hello == inner::PrintOnDrop("hello".to_owned()));
+        assert_eq!(PRINTED.with(Cell::get), 2);
+        core::mem::drop(hello);
+        assert_eq!(PRINTED.with(Cell::get), 3);
+    }
+
+    #[test]
+    fn ord() {
+        let mut strings = [PrintOnDrop::new("b"), PrintOnDrop::new("c"), PrintOnDrop::new("a")];
+        strings.sort();
+        assert!(strings[0] == inner::PrintOnDrop("a".to_owned()));
+        assert!(strings[2] > inner::PrintOnDrop("b".to_owned()));
+        assert_eq!(strings.iter().max().map(|max| &max.0.inner().0[..]), Some("c"));
+    }
+
+    #[test]
+    fn hash() {
+        let mut set = HashSet::new();
+        assert!(set.insert(PrintOnDrop::new("hello")));
+        assert!(!set.insert(PrintOnDrop::new("hello")));
+        assert!(set.contains(&PrintOnDrop::new("hello")));
+        assert_eq!(set.len(), 1);
+    }
+
+    mod cross_eq {
+        use std::string::String;
+        use std::vec::Vec;
+        use core::cell::RefCell;
+        use crate::{IntoInnerHelper, DetachedDrop};
+
+        std::thread_local! {
+            static DROPPED: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+        }
+
+        enum PrintString {}
+
+        impl DetachedDrop for PrintString {
+            type Implementor = String;
+
+            fn drop(_: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.borrow_mut().push("print"));
+            }
+        }
+
+        enum LogStr {}
+
+        impl DetachedDrop for LogStr {
+            type Implementor = &'static str;
+
+            fn drop(_: Self::Implementor) {
+                DROPPED.with(|dropped| dropped.borrow_mut().push("log"));
+            }
+        }
+
+        struct PrintOnDrop(IntoInnerHelper<String, PrintString>);
+        struct LogOnDrop(IntoInnerHelper<&'static str, LogStr>);
+
+        forward_cross_eq!(PrintOnDrop => String, LogOnDrop => &'static str);
+
+        #[test]
+        fn compare_different_guards() {
+            let printed = PrintOnDrop(IntoInnerHelper::new(String::from("hello")));
+            let logged = LogOnDrop(IntoInnerHelper::new("hello"));
+            let other = LogOnDrop(IntoInnerHelper::new("world"));
+            assert!(printed == logged);
+            assert!(logged == printed);
+            assert!(printed != other);
+            assert!(other != printed);
+            assert!(DROPPED.with(|dropped| dropped.borrow().is_empty()));
+            core::mem::drop((printed, logged));
+            DROPPED.with(|dropped| assert_eq!(*dropped.borrow(), ["print", "log"]));
+        }
+    }
+
+    mod newtype {
+        use std::vec::Vec;
+        use core::cell::RefCell;
+
+        std::thread_local! {
+            static CLOSED: RefCell<Vec<u16>> = const { RefCell::new(Vec::new()) };
+        }
+
+        guarded_newtype! {
+            /// A fake port closed on drop.
+            pub(super) struct Port(u16);
+
+            fn drop(port) {
+                CLOSED.with(|closed| closed.borrow_mut().push(port));
+            }
+
+            test port_guard = 8080;
+        }
+
+        guarded_newtype! {
+            struct Untested(u16);
+
+            fn drop(port) {
+                CLOSED.with(|closed| closed.borrow_mut().push(port + 1));
+            }
+        }
+
+        #[test]
+        fn generated_methods() {
+            let mut port = Port::new(80);
+            *port.inner_mut() += 363;
+            assert_eq!(*port.inner(), 443);
+            assert_eq!(Untested::new(1000).into_inner(), 1000);
+            core::mem::drop((port, Untested::new(1000)));
+            CLOSED.with(|closed| assert_eq!(*closed.borrow(), [443, 1001]));
+        }
+    }
+
+    mod ordered {
+        use std::vec::Vec;
+        use core::cell::RefCell;
+        use crate::{IntoInnerHelper, DetachedDrop};
+
+        std::thread_local! {
+            static ORDER: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+        }
+
+        enum Record {}
+
+        impl DetachedDrop for Record {
+            type Implementor = &'static str;
+
+            fn drop(name: Self::Implementor) {
+                ORDER.with(|order| order.borrow_mut().push(name));
+            }
+        }
+
+        type Helper = IntoInnerHelper<&'static str, Record>;
+
+        ordered_drop! {
+            struct Resources {
+                #[drop_order(2)]
+                connection: Helper,
+                /// Must go first.
+                #[drop_order(0)]
+                transaction: Helper,
+                #[drop_order(1)]
+                pub(super) cache: Helper,
+            }
+        }
+
+        fn resources() -> Resources {
+            Resources::new(Helper::new("connection"), Helper::new("transaction"), Helper::new("cache"))
+        }
+
+        #[test]
+        fn drop_in_annotated_order() {
+            let resources = resources();
+            assert_eq!(*resources.cache.inner(), "cache");
+            core::mem::drop(resources);
+            assert_eq!(ORDER.with(|order| order.take()), ["transaction", "cache", "connection"]);
+        }
+
+        #[test]
+        fn into_parts() {
+            let (connection, transaction, cache) = resources().into_parts();
+            assert!(ORDER.with(|order| order.borrow().is_empty()));
+            assert_eq!(transaction.into_inner(), "transaction");
+            core::mem::drop((cache, connection));
+            assert_eq!(ORDER.with(|order| order.take()), ["cache", "connection"]);
+        }
+    }
+
+    mod scope {
+        use core::cell::Cell;
+
+        #[test]
+        fn runs_at_scope_end() {
+            let called = Cell::new(None);
+            {
+                guard_scope!(value = 21, on_drop = |value| called.set(Some(value * 2)));
+                assert_eq!(*value.inner(), 21);
+                assert_eq!(called.get(), None);
+            }
+            assert_eq!(called.get(), Some(42));
+        }
+
+        #[test]
+        fn into_inner_cancels() {
+            let called = Cell::new(false);
+            {
+                guard_scope!(value = 21, on_drop = |_| called.set(true));
+                assert_eq!(value.into_inner(), 21);
+            }
+            assert!(!called.get());
+        }
+
+        #[test]
+        fn no_cancel() {
+            let called = Cell::new(None);
+            {
+                guard_scope!(no cancel, value = 20, on_drop = |value| called.set(Some(value)));
+                *value += 22;
+            }
+            assert_eq!(called.get(), Some(42));
+        }
+
+        #[test]
+        fn cancel_handle() {
+            let called = Cell::new(0);
+            {
+                guard_scope!(cancel as cancel, value = 1, on_drop = |value| called.set(called.get() + value));
+                assert_eq!(*value.inner(), 1);
+                assert!(!cancel.is_cancelled());
+            }
+            assert_eq!(called.get(), 1);
+            {
+                guard_scope!(cancel as cancel, _value = 1, on_drop = |value| called.set(called.get() + value));
+                cancel.cancel();
+            }
+            assert_eq!(called.get(), 1);
+        }
+    }
+}
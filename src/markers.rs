@@ -0,0 +1,387 @@
+//! Ready-made drop markers.
+
+use core::convert::Infallible;
+use core::marker::PhantomData;
+use core::ops::DerefMut;
+use crate::DetachedDrop;
+
+/// A type with several drop behaviors, selected at compile time using `ModeDrop`.
+pub trait ModalDrop: Sized {
+    /// Runs the drop behavior identified by `mode`.
+    ///
+    /// `mode` is a constant in each instantiation of `ModeDrop`, so matching on it is optimized
+    /// away.
+    fn drop_in_mode(self, mode: u8);
+}
+
+/// A marker selecting the drop behavior of `T` by `MODE`.
+///
+/// This allows a single inner type to pick e.g. commit or rollback cleanup at the type level.
+///
+/// # Example
+///
+/// ```
+/// use into_inner_drop::{IntoInnerHelper, ModalDrop, ModeDrop};
+///
+/// const COMMIT: u8 = 0;
+/// const ROLLBACK: u8 = 1;
+///
+/// struct Transaction(Vec<&'static str>);
+///
+/// impl ModalDrop for Transaction {
+///     fn drop_in_mode(self, mode: u8) {
+///         match mode {
+///             COMMIT => println!("committing {:?}", self.0),
+///             ROLLBACK => println!("rolling back {:?}", self.0),
+///             _ => unreachable!("unknown mode"),
+///         }
+///     }
+/// }
+///
+/// type Committing = IntoInnerHelper<Transaction, ModeDrop<Transaction, COMMIT>>;
+/// type RollingBack = IntoInnerHelper<Transaction, ModeDrop<Transaction, ROLLBACK>>;
+///
+/// let mut transaction = RollingBack::new(Transaction(Vec::new()));
+/// transaction.inner_mut().0.push("insert");
+/// // Everything went fine, switch to commit
+/// let transaction = Committing::new(transaction.into_inner());
+/// // prints: committing ["insert"]
+/// ```
+pub struct ModeDrop<T, const MODE: u8>(Infallible, PhantomData<fn(T)>);
+
+impl<T, const MODE: u8> DetachedDrop for ModeDrop<T, MODE> where T: ModalDrop {
+    type Implementor = T;
+
+    fn drop(value: Self::Implementor) {
+        value.drop_in_mode(MODE);
+    }
+}
+
+/// A container that can be emptied in place, keeping its allocated capacity.
+///
+/// This is implemented for the `alloc` collections and, with the `heapless` feature, for the
+/// fixed-capacity `heapless::Vec` and `heapless::String`, which work without allocation.
+pub trait Clear {
+    /// Removes all elements.
+    fn clear(&mut self);
+}
+
+impl<C> Clear for &mut C where C: Clear + ?Sized {
+    fn clear(&mut self) {
+        (**self).clear()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Clear for alloc::vec::Vec<T> {
+    fn clear(&mut self) {
+        alloc::vec::Vec::clear(self)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Clear for alloc::string::String {
+    fn clear(&mut self) {
+        alloc::string::String::clear(self)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Clear for alloc::collections::VecDeque<T> {
+    fn clear(&mut self) {
+        alloc::collections::VecDeque::clear(self)
+    }
+}
+
+#[cfg(feature = "heapless")]
+impl<T, const N: usize, L> Clear for heapless::Vec<T, N, L> where L: heapless::LenType {
+    fn clear(&mut self) {
+        heapless::Vec::clear(self)
+    }
+}
+
+#[cfg(feature = "heapless")]
+impl<const N: usize, L> Clear for heapless::String<N, L> where L: heapless::LenType {
+    fn clear(&mut self) {
+        heapless::String::clear(self)
+    }
+}
+
+/// A marker clearing the container on drop.
+///
+/// This is mostly useful with mutable references to containers, e.g. buffers borrowed from a
+/// pool, which get cleared but keep their capacity when the guard is dropped. `into_inner`
+/// returns the container with its contents.
+pub struct ClearOnDrop<C>(Infallible, PhantomData<fn(C)>);
+
+impl<C> DetachedDrop for ClearOnDrop<C> where C: Clear {
+    type Implementor = C;
+
+    fn drop(mut value: Self::Implementor) {
+        value.clear();
+    }
+}
+
+/// A marker resetting the referenced value to its default on drop.
+///
+/// This is meant for scoped temporary overrides: guard a mutable reference (or any other
+/// `DerefMut` pointer) to a field, set the field through the guard and it reverts to
+/// `Default::default()` when the guard is dropped, dropping the overridden value. `into_inner`
+/// returns the reference, keeping the override in place.
+pub struct ResetOnDrop<R>(Infallible, PhantomData<fn(R)>);
+
+impl<R> DetachedDrop for ResetOnDrop<R> where R: DerefMut, R::Target: Default {
+    type Implementor = R;
+
+    fn drop(mut value: Self::Implementor) {
+        core::mem::drop(core::mem::take(&mut *value));
+    }
+}
+
+/// A marker flushing the writer on drop.
+///
+/// Errors can't be reported from drop, so they are ignored, or logged with the `log` feature.
+/// Call `flush` explicitly before dropping to handle them. `into_inner` returns the writer without
+/// flushing it.
+#[cfg(feature = "std")]
+pub struct FlushOnDrop<W>(Infallible, PhantomData<fn(W)>);
+
+#[cfg(feature = "std")]
+impl<W> DetachedDrop for FlushOnDrop<W> where W: std::io::Write {
+    type Implementor = W;
+
+    fn drop(mut writer: Self::Implementor) {
+        #[cfg_attr(not(feature = "log"), allow(unused_variables))]
+        if let Err(error) = writer.flush() {
+            #[cfg(feature = "log")]
+            log::warn!(target: "into_inner_drop", "failed to flush writer on drop: {}", error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use super::{ModalDrop, ModeDrop};
+    use crate::IntoInnerHelper;
+
+    const COMMIT: u8 = 0;
+    const ROLLBACK: u8 = 1;
+    const ABORT: u8 = 2;
+
+    std::thread_local! {
+        static LAST_MODE: Cell<Option<u8>> = const { Cell::new(None) };
+    }
+
+    fn last_mode() -> Option<u8> {
+        LAST_MODE.with(Cell::get)
+    }
+
+    struct Transaction;
+
+    impl ModalDrop for Transaction {
+        fn drop_in_mode(self, mode: u8) {
+            assert!(last_mode().is_none());
+            LAST_MODE.with(|last| last.set(Some(mode)));
+        }
+    }
+
+    #[test]
+    fn commit() {
+        core::mem::drop(<IntoInnerHelper<_, ModeDrop<_, COMMIT>>>::new(Transaction));
+        assert_eq!(last_mode(), Some(COMMIT));
+    }
+
+    #[test]
+    fn rollback() {
+        core::mem::drop(<IntoInnerHelper<_, ModeDrop<_, ROLLBACK>>>::new(Transaction));
+        assert_eq!(last_mode(), Some(ROLLBACK));
+    }
+
+    #[test]
+    fn abort() {
+        core::mem::drop(<IntoInnerHelper<_, ModeDrop<_, ABORT>>>::new(Transaction));
+        assert_eq!(last_mode(), Some(ABORT));
+    }
+
+    #[test]
+    fn into_inner() {
+        let Transaction = <IntoInnerHelper<_, ModeDrop<_, ABORT>>>::new(Transaction).into_inner();
+        assert_eq!(last_mode(), None);
+    }
+
+    mod reset {
+        use core::cell::RefCell;
+        use crate::{IntoInnerHelper, ResetOnDrop};
+
+        #[derive(Default)]
+        struct Config {
+            verbose: bool,
+            _token: Option<dropcheck::DropToken>,
+        }
+
+        #[test]
+        fn reverts_on_drop() {
+            let check = dropcheck::DropCheck::new();
+            let (drop_token, drop_state) = check.pair();
+            let mut config = Config::default();
+            let mut guard = <IntoInnerHelper<_, ResetOnDrop<_>>>::new(&mut config);
+            **guard.inner_mut() = Config { verbose: true, _token: Some(drop_token) };
+            assert!(guard.inner().verbose);
+            core::mem::drop(guard);
+            assert!(!config.verbose);
+            assert!(drop_state.is_dropped());
+
+            let cell = RefCell::new(42);
+            let mut guard = <IntoInnerHelper<_, ResetOnDrop<_>>>::new(cell.borrow_mut());
+            **guard.inner_mut() += 1;
+            core::mem::drop(guard);
+            assert_eq!(*cell.borrow(), 0);
+        }
+
+        #[test]
+        fn into_inner_keeps_override() {
+            let check = dropcheck::DropCheck::new();
+            let (drop_token, drop_state) = check.pair();
+            let mut config = Config::default();
+            let mut guard = <IntoInnerHelper<_, ResetOnDrop<_>>>::new(&mut config);
+            **guard.inner_mut() = Config { verbose: true, _token: Some(drop_token) };
+            assert!(guard.into_inner().verbose);
+            assert!(config.verbose);
+            assert!(drop_state.is_not_dropped());
+        }
+    }
+
+    #[cfg(feature = "std")]
+    mod flush {
+        use std::io::{self, Write};
+        use std::vec::Vec;
+        use crate::{IntoInnerHelper, FlushOnDrop};
+
+        #[derive(Default)]
+        struct MockWriter {
+            buffered: Vec<u8>,
+            flushed: Vec<u8>,
+            flushes: usize,
+            fail: bool,
+        }
+
+        impl Write for MockWriter {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.buffered.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                self.flushes += 1;
+                if self.fail {
+                    return Err(io::Error::other("disconnected"));
+                }
+                self.flushed.append(&mut self.buffered);
+                Ok(())
+            }
+        }
+
+        #[test]
+        fn flushes_on_drop() {
+            let mut writer = MockWriter::default();
+            let mut guard = <IntoInnerHelper<_, FlushOnDrop<_>>>::new(&mut writer);
+            write!(guard, "hello {}", 42).unwrap();
+            assert_eq!(guard.inner().flushes, 0);
+            core::mem::drop(guard);
+            assert_eq!(writer.flushes, 1);
+            assert_eq!(writer.flushed, b"hello 42");
+        }
+
+        #[test]
+        fn flush_error_ignored() {
+            let mut writer = MockWriter { fail: true, ..Default::default() };
+            let mut guard = <IntoInnerHelper<_, FlushOnDrop<_>>>::new(&mut writer);
+            guard.write_all(b"hello").unwrap();
+            core::mem::drop(guard);
+            assert_eq!(writer.flushes, 1);
+            assert_eq!(writer.buffered, b"hello");
+        }
+
+        #[test]
+        fn into_inner_doesnt_flush() {
+            let mut guard = <IntoInnerHelper<_, FlushOnDrop<_>>>::new(MockWriter::default());
+            guard.write_all(b"hello").unwrap();
+            guard.flush().unwrap();
+            guard.write_all(b" world").unwrap();
+            let writer = guard.into_inner();
+            assert_eq!(writer.flushes, 1);
+            assert_eq!(writer.flushed, b"hello");
+            assert_eq!(writer.buffered, b" world");
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    mod clear {
+        use alloc::vec::Vec;
+        use alloc::string::String;
+        use alloc::collections::VecDeque;
+        use crate::{IntoInnerHelper, ClearOnDrop};
+
+        #[test]
+        fn clear_on_drop_keeps_capacity() {
+            let mut buffer = Vec::with_capacity(16);
+            let mut guard = <IntoInnerHelper<_, ClearOnDrop<_>>>::new(&mut buffer);
+            guard.inner_mut().extend_from_slice(b"hello");
+            core::mem::drop(guard);
+            assert!(buffer.is_empty());
+            assert!(buffer.capacity() >= 16);
+
+            let mut string = String::from("hello");
+            core::mem::drop(<IntoInnerHelper<_, ClearOnDrop<_>>>::new(&mut string));
+            assert!(string.is_empty());
+            assert!(string.capacity() >= 5);
+
+            let mut queue = VecDeque::from([1, 2, 3]);
+            core::mem::drop(<IntoInnerHelper<_, ClearOnDrop<_>>>::new(&mut queue));
+            assert!(queue.is_empty());
+        }
+
+        #[test]
+        fn into_inner_keeps_contents() {
+            let mut buffer = Vec::new();
+            let mut guard = <IntoInnerHelper<_, ClearOnDrop<_>>>::new(&mut buffer);
+            guard.inner_mut().extend_from_slice(b"hello");
+            guard.into_inner().push(b'!');
+            assert_eq!(buffer, b"hello!");
+        }
+    }
+
+    #[cfg(feature = "heapless")]
+    mod clear_heapless {
+        use heapless::{Vec, String};
+        use crate::{IntoInnerHelper, ClearOnDrop};
+
+        #[test]
+        fn clear_on_drop() {
+            let mut buffer = Vec::<u8, 16>::new();
+            let mut guard = <IntoInnerHelper<_, ClearOnDrop<_>>>::new(&mut buffer);
+            guard.inner_mut().extend_from_slice(b"hello").unwrap();
+            core::mem::drop(guard);
+            assert!(buffer.is_empty());
+
+            let mut string = String::<16>::new();
+            string.push_str("hello").unwrap();
+            core::mem::drop(<IntoInnerHelper<_, ClearOnDrop<_>>>::new(&mut string));
+            assert!(string.is_empty());
+        }
+
+        #[test]
+        fn into_inner_keeps_contents() {
+            let mut buffer = Vec::<u8, 16>::new();
+            let mut guard = <IntoInnerHelper<_, ClearOnDrop<_>>>::new(&mut buffer);
+            guard.inner_mut().extend_from_slice(b"hello").unwrap();
+            guard.into_inner().push(b'!').unwrap();
+            assert_eq!(buffer, b"hello!");
+
+            let mut guard = <IntoInnerHelper<_, ClearOnDrop<String<16>>>>::new(String::new());
+            guard.inner_mut().push_str("hello").unwrap();
+            assert_eq!(guard.into_inner(), "hello");
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use core::mem::MaybeUninit;
+use crate::DetachedDrop;
+use crate::unwind::detached_drop;
+
+/// A helper holding a possibly uninitialized value.
+///
+/// The detached drop runs only if the value was initialized, either by `write` or by writing
+/// through `as_mut_ptr` and calling `set_initialized`. This is intended for staged construction,
+/// e.g. values filled by FFI functions that may fail before the value is fully built.
+pub struct MaybeInitHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    value: MaybeUninit<T>,
+    initialized: bool,
+    _phantom: core::marker::PhantomData<fn() -> D>,
+}
+
+impl<T, D> MaybeInitHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    /// Creates an uninitialized helper.
+    pub const fn new() -> Self {
+        MaybeInitHelper {
+            value: MaybeUninit::uninit(),
+            initialized: false,
+            _phantom: core::marker::PhantomData,
+        }
+    }
+
+    /// Returns `true` if the value was initialized.
+    pub fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+
+    /// Initializes the value, returning a reference to it.
+    ///
+    /// If the helper was already initialized the previous value is passed to the detached drop.
+    pub fn write(&mut self, value: T) -> &mut T {
+        if let Some(previous) = self.take() {
+            detached_drop::<D>(previous);
+        }
+        self.initialized = true;
+        self.value.write(value)
+    }
+
+    /// Returns a pointer to the possibly uninitialized value, e.g. to pass it to FFI.
+    ///
+    /// Call `set_initialized` once the value was fully initialized through the pointer.
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.value.as_mut_ptr()
+    }
+
+    /// Marks the value as initialized.
+    ///
+    /// # Safety
+    ///
+    /// The value must have been fully initialized through `as_mut_ptr` and not moved out since.
+    pub unsafe fn set_initialized(&mut self) {
+        self.initialized = true;
+    }
+
+    /// Accesses the value, returning `None` if it's not initialized.
+    pub fn inner(&self) -> Option<&T> {
+        if self.initialized {
+            // SAFETY: the flag is only set when the value is initialized.
+            Some(unsafe { self.value.assume_init_ref() })
+        } else {
+            None
+        }
+    }
+
+    /// Accesses the value mutably, returning `None` if it's not initialized.
+    pub fn inner_mut(&mut self) -> Option<&mut T> {
+        if self.initialized {
+            // SAFETY: the flag is only set when the value is initialized.
+            Some(unsafe { self.value.assume_init_mut() })
+        } else {
+            None
+        }
+    }
+
+    /// Moves out the value, preventing the detached drop.
+    ///
+    /// Returns `None` if the value is not initialized.
+    pub fn into_inner(mut self) -> Option<T> {
+        self.take()
+    }
+
+    /// Moves out the value, preventing the detached drop.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is not initialized.
+    #[track_caller]
+    pub fn assume_init_inner(self) -> T {
+        self.into_inner().expect("the value of MaybeInitHelper was not initialized")
+    }
+
+    fn take(&mut self) -> Option<T> {
+        if core::mem::replace(&mut self.initialized, false) {
+            // SAFETY: the value was initialized and the flag was cleared, so it's read only once.
+            Some(unsafe { self.value.assume_init_read() })
+        } else {
+            None
+        }
+    }
+}
+
+impl<T, D> Default for MaybeInitHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    fn default() -> Self {
+        MaybeInitHelper::new()
+    }
+}
+
+impl<T, D> Drop for MaybeInitHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    fn drop(&mut self) {
+        if let Some(value) = self.take() {
+            detached_drop::<D>(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use super::MaybeInitHelper;
+    use crate::DetachedDrop;
+
+    std::thread_local! {
+        static DROPPED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    fn dropped() -> usize {
+        DROPPED.with(Cell::get)
+    }
+
+    enum CountDrop {}
+
+    impl DetachedDrop for CountDrop {
+        type Implementor = dropcheck::DropToken;
+
+        fn drop(_: Self::Implementor) {
+            DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+        }
+    }
+
+    #[test]
+    fn initialized_drop() {
+        let check = dropcheck::DropCheck::new();
+        let mut helper = <MaybeInitHelper<_, CountDrop>>::new();
+        helper.write(check.token());
+        assert!(helper.is_initialized());
+        helper.write(check.token());
+        assert_eq!(dropped(), 1);
+        core::mem::drop(helper);
+        assert_eq!(dropped(), 2);
+        assert!(check.all_dropped());
+    }
+
+    #[test]
+    fn never_initialized() {
+        let helper = <MaybeInitHelper<_, CountDrop>>::new();
+        assert!(helper.inner().is_none());
+        core::mem::drop(helper);
+        assert_eq!(dropped(), 0);
+        assert!(<MaybeInitHelper<_, CountDrop>>::new().into_inner().is_none());
+    }
+
+    #[test]
+    fn initialized_through_pointer() {
+        let check = dropcheck::DropCheck::new();
+        let mut helper = <MaybeInitHelper<_, CountDrop>>::new();
+        unsafe {
+            helper.as_mut_ptr().write(check.token());
+            helper.set_initialized();
+        }
+        assert!(helper.inner_mut().is_some());
+        core::mem::drop(helper);
+        assert_eq!(dropped(), 1);
+    }
+
+    #[test]
+    fn extract() {
+        let check = dropcheck::DropCheck::new();
+        let (drop_token, drop_state) = check.pair();
+        let mut helper = <MaybeInitHelper<_, CountDrop>>::new();
+        helper.write(drop_token);
+        let token = helper.assume_init_inner();
+        assert!(drop_state.is_not_dropped());
+        core::mem::drop(token);
+        assert!(drop_state.is_dropped());
+        assert_eq!(dropped(), 0);
+    }
+
+    #[test]
+    #[should_panic = "not initialized"]
+    fn assume_init_uninitialized() {
+        <MaybeInitHelper<_, CountDrop>>::new().assume_init_inner();
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unwinding() {
+        use crate::unwind::tests::{Record, SkipOnPanic, drop_while_panicking};
+
+        let mut helper = <MaybeInitHelper<_, Record>>::new();
+        helper.write(42);
+        assert_eq!(drop_while_panicking(helper), ["drop_panicking"]);
+        let mut helper = <MaybeInitHelper<_, SkipOnPanic>>::new();
+        helper.write(42);
+        assert!(drop_while_panicking(helper).is_empty());
+    }
+}
@@ -0,0 +1,158 @@
+use std::sync::{Mutex, MutexGuard, LockResult, TryLockResult, PoisonError};
+use crate::{IntoInnerHelper, DetachedDrop};
+use crate::unwind::detached_drop;
+
+/// A thread-safe interior-mutable helper backed by `Mutex`.
+///
+/// This is the `Sync` counterpart of `RefCellHelper`, useful for shared resources, e.g. stored in
+/// an `Arc`, that need custom teardown. The detached drop receives the inner value unwrapped from
+/// the mutex when the helper is dropped. It runs even if the mutex was poisoned, since the
+/// resource still needs to be released; extraction reports poisoning like `Mutex::into_inner`.
+pub struct MutexHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    inner: IntoInnerHelper<Mutex<T>, MutexDrop<D>>,
+}
+
+struct MutexDrop<D>(core::convert::Infallible, core::marker::PhantomData<D>);
+
+impl<D> DetachedDrop for MutexDrop<D> where D: DetachedDrop {
+    type Implementor = Mutex<D::Implementor>;
+
+    #[cfg(feature = "std")]
+    const RUN_ON_PANIC: bool = D::RUN_ON_PANIC;
+
+    fn drop(value: Self::Implementor) {
+        detached_drop::<D>(value.into_inner().unwrap_or_else(PoisonError::into_inner));
+    }
+
+    #[cfg(feature = "std")]
+    fn drop_during_panic(value: Self::Implementor, context: &crate::PanicContext, token: crate::HookToken) {
+        crate::unwind::detached_drop_during_panic::<D>(value.into_inner().unwrap_or_else(PoisonError::into_inner), context, token);
+    }
+}
+
+impl<T, D> MutexHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    /// Creates the helper.
+    pub fn new(inner: T) -> Self {
+        MutexHelper {
+            inner: IntoInnerHelper::new(Mutex::new(inner)),
+        }
+    }
+
+    /// Locks the mutex, blocking the current thread until it's available.
+    pub fn lock(&self) -> LockResult<MutexGuard<'_, T>> {
+        self.inner.inner().lock()
+    }
+
+    /// Attempts to lock the mutex without blocking.
+    pub fn try_lock(&self) -> TryLockResult<MutexGuard<'_, T>> {
+        self.inner.inner().try_lock()
+    }
+
+    /// Returns `true` if a thread panicked while holding the lock.
+    pub fn is_poisoned(&self) -> bool {
+        self.inner.inner().is_poisoned()
+    }
+
+    /// Accesses the inner value mutably without locking, this is safe because the borrow is unique.
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        self.inner.inner_mut().get_mut()
+    }
+
+    /// Moves out the inner value, preventing the detached drop.
+    ///
+    /// If the mutex was poisoned the value is still available through the returned error.
+    pub fn into_inner(self) -> LockResult<T> {
+        self.inner.into_inner().into_inner()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::vec::Vec;
+    use super::MutexHelper;
+    use crate::DetachedDrop;
+
+    enum SumDrop {}
+
+    impl DetachedDrop for SumDrop {
+        type Implementor = (Vec<usize>, &'static AtomicUsize);
+
+        fn drop((values, sum): Self::Implementor) {
+            sum.fetch_add(values.iter().sum(), Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn shared_drop() {
+        static SUM: AtomicUsize = AtomicUsize::new(0);
+
+        let helper = Arc::new(<MutexHelper<_, SumDrop>>::new((Vec::new(), &SUM)));
+        let threads = (1..=4).map(|value| {
+            let helper = Arc::clone(&helper);
+            std::thread::spawn(move || helper.lock().unwrap().0.push(value))
+        }).collect::<Vec<_>>();
+        threads.into_iter().for_each(|thread| thread.join().unwrap());
+        assert_eq!(SUM.load(Ordering::Relaxed), 0);
+        core::mem::drop(helper);
+        assert_eq!(SUM.load(Ordering::Relaxed), 10);
+    }
+
+    #[test]
+    fn into_inner() {
+        static SUM: AtomicUsize = AtomicUsize::new(0);
+
+        let mut helper = <MutexHelper<_, SumDrop>>::new((Vec::new(), &SUM));
+        helper.get_mut().unwrap().0.push(40);
+        helper.try_lock().unwrap().0.push(2);
+        assert_eq!(helper.into_inner().unwrap().0, [40, 2]);
+        assert_eq!(SUM.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn poisoned() {
+        static SUM: AtomicUsize = AtomicUsize::new(0);
+
+        let poison = |helper: &MutexHelper<_, SumDrop>| {
+            let result = std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| {
+                let mut guard = helper.lock().unwrap();
+                guard.0.push(21);
+                panic!("failed while locked");
+            }));
+            assert!(result.is_err());
+            assert!(helper.is_poisoned());
+        };
+
+        let helper = <MutexHelper<_, SumDrop>>::new((Vec::new(), &SUM));
+        poison(&helper);
+        assert_eq!(helper.into_inner().unwrap_err().into_inner().0, [21]);
+        assert_eq!(SUM.load(Ordering::Relaxed), 0);
+
+        let helper = <MutexHelper<_, SumDrop>>::new((Vec::new(), &SUM));
+        poison(&helper);
+        assert!(helper.lock().is_err());
+        core::mem::drop(helper);
+        assert_eq!(SUM.load(Ordering::Relaxed), 21);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unwinding() {
+        use crate::unwind::tests::{Record, SkipOnPanic, drop_while_panicking};
+
+        assert_eq!(drop_while_panicking(<MutexHelper<_, Record>>::new(42)), ["drop_panicking"]);
+        assert!(drop_while_panicking(<MutexHelper<_, SkipOnPanic>>::new(42)).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unwinding_context() {
+        use crate::unwind::tests::{ContextRecord, drop_while_panicking, messages};
+
+        crate::install_panic_context_hook();
+        let helper = <MutexHelper<_, (ContextRecord, ContextRecord)>>::new((1, 2));
+        assert!(drop_while_panicking(helper).is_empty());
+        assert_eq!(messages(), std::vec![Some("unwinding".into()); 2]);
+    }
+}
@@ -0,0 +1,183 @@
+use core::mem::ManuallyDrop;
+use core::sync::atomic::{AtomicBool, Ordering};
+use crate::DetachedDrop;
+use crate::unwind::detached_drop;
+
+/// A helper running the detached drop at most once per flag.
+///
+/// The flag lives outside of the guard, so all guards sharing it, including duplicates created by
+/// copying the guard using `unsafe` code, agree on a single winner: the first guard to be dropped,
+/// finalized or consumed by `into_inner` sets the flag and all others become inert. An inert
+/// guard neither runs the detached drop nor drops the inner value. This is intended for FFI
+/// handoff, where it's not always clear which side owns a handle.
+pub struct OnceGuard<'a, T, D> where D: DetachedDrop<Implementor=T> {
+    value: ManuallyDrop<T>,
+    done: &'a AtomicBool,
+    _phantom: core::marker::PhantomData<fn() -> D>,
+}
+
+impl<'a, T, D> OnceGuard<'a, T, D> where D: DetachedDrop<Implementor=T> {
+    /// Creates the guard coordinated by `done`.
+    ///
+    /// If `done` is already set the guard is inert from the start.
+    pub fn new(inner: T, done: &'a AtomicBool) -> Self {
+        OnceGuard {
+            value: ManuallyDrop::new(inner),
+            done,
+            _phantom: Default::default(),
+        }
+    }
+
+    /// Accesses the inner value.
+    pub fn inner(&self) -> &T {
+        &self.value
+    }
+
+    /// Returns `true` if some guard sharing the flag already ran the cleanup or extracted the
+    /// value.
+    pub fn is_done(&self) -> bool {
+        self.done.load(Ordering::Acquire)
+    }
+
+    /// Runs the detached drop now unless some guard sharing the flag won already.
+    ///
+    /// Returns `true` if the detached drop ran.
+    pub fn finalize(self) -> bool {
+        let mut this = ManuallyDrop::new(self);
+        this.release().map(detached_drop::<D>).is_some()
+    }
+
+    /// Moves out the inner value unless some guard sharing the flag won already.
+    ///
+    /// In both cases the detached drop doesn't run.
+    pub fn into_inner(self) -> Option<T> {
+        let mut this = ManuallyDrop::new(self);
+        this.release()
+    }
+
+    /// Sets the flag, returning the value if this guard won.
+    ///
+    /// Must be called at most once, `self.value` must not be used afterwards.
+    fn release(&mut self) -> Option<T> {
+        if self.done.swap(true, Ordering::AcqRel) {
+            None
+        } else {
+            // SAFETY: the value is initialized and `release` is called at most once.
+            Some(unsafe { ManuallyDrop::take(&mut self.value) })
+        }
+    }
+}
+
+impl<T, D> Drop for OnceGuard<'_, T, D> where D: DetachedDrop<Implementor=T> {
+    fn drop(&mut self) {
+        if let Some(value) = self.release() {
+            detached_drop::<D>(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use super::OnceGuard;
+    use crate::DetachedDrop;
+
+    struct Handle {
+        closed: &'static AtomicUsize,
+    }
+
+    enum Close {}
+
+    impl DetachedDrop for Close {
+        type Implementor = Handle;
+
+        fn drop(handle: Self::Implementor) {
+            handle.closed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn leak<T>(value: T) -> &'static T {
+        std::boxed::Box::leak(std::boxed::Box::new(value))
+    }
+
+    #[test]
+    fn drop_once() {
+        let closed = leak(AtomicUsize::new(0));
+        let done = AtomicBool::new(false);
+        let guard = <OnceGuard<_, Close>>::new(Handle { closed }, &done);
+        assert!(!guard.is_done());
+        let second = <OnceGuard<_, Close>>::new(Handle { closed }, &done);
+        core::mem::drop(guard);
+        assert!(second.is_done());
+        assert!(!second.finalize());
+        assert_eq!(closed.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn into_inner_wins() {
+        let closed = leak(AtomicUsize::new(0));
+        let done = AtomicBool::new(false);
+        let guard = <OnceGuard<_, Close>>::new(Handle { closed }, &done);
+        let second = <OnceGuard<_, Close>>::new(Handle { closed }, &done);
+        assert!(guard.into_inner().is_some());
+        assert!(second.into_inner().is_none());
+        assert!(!<OnceGuard<_, Close>>::new(Handle { closed }, &done).finalize());
+        assert_eq!(closed.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn duplicated_by_unsafe_copy() {
+        let closed = leak(AtomicUsize::new(0));
+        let done = AtomicBool::new(false);
+        let guard = <OnceGuard<_, Close>>::new(Handle { closed }, &done);
+        // Simulates ownership of the same handle being passed to both sides of an FFI boundary.
+        let duplicate = unsafe { core::ptr::read(&guard) };
+        assert!(guard.finalize());
+        core::mem::drop(duplicate);
+        assert_eq!(closed.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn concurrent_combinations() {
+        for round in 0..50 {
+            let closed = leak(AtomicUsize::new(0));
+            let extracted = leak(AtomicUsize::new(0));
+            let done = leak(AtomicBool::new(false));
+            let threads = (0..6).map(|i| std::thread::spawn(move || {
+                let guard = <OnceGuard<_, Close>>::new(Handle { closed }, done);
+                match (i + round) % 3 {
+                    0 => core::mem::drop(guard),
+                    1 => { guard.finalize(); },
+                    _ => if guard.into_inner().is_some() {
+                        extracted.fetch_add(1, Ordering::Relaxed);
+                    },
+                }
+            })).collect::<std::vec::Vec<_>>();
+            threads.into_iter().for_each(|thread| thread.join().unwrap());
+            assert_eq!(closed.load(Ordering::Relaxed) + extracted.load(Ordering::Relaxed), 1);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unwinding() {
+        use crate::unwind::tests::{Record, SkipOnPanic, drop_while_panicking};
+
+        let done = AtomicBool::new(false);
+        assert_eq!(drop_while_panicking(<OnceGuard<_, Record>>::new(42, &done)), ["drop_panicking"]);
+        let done = AtomicBool::new(false);
+        assert!(drop_while_panicking(<OnceGuard<_, SkipOnPanic>>::new(42, &done)).is_empty());
+        assert!(done.load(Ordering::Relaxed));
+
+        struct Finalize<'a>(Option<OnceGuard<'a, u32, SkipOnPanic>>);
+
+        impl Drop for Finalize<'_> {
+            fn drop(&mut self) {
+                assert!(self.0.take().unwrap().finalize());
+            }
+        }
+
+        let done = AtomicBool::new(false);
+        assert!(drop_while_panicking(Finalize(Some(OnceGuard::new(42, &done)))).is_empty());
+    }
+}
@@ -0,0 +1,132 @@
+//! Operator forwarding, enabled by the `ops` feature.
+//!
+//! Binary operators are implemented for references to helpers and produce the raw, unguarded
+//! result of applying the operator to a reference to the inner value. This way the guard is never
+//! consumed and the result never silently inherits (or loses) the drop behavior. Compound
+//! assignment operators modify the inner value in place, so the guard stays intact. Unary
+//! operators work the same way as binary ones.
+
+use core::ops::{Not, Neg};
+use core::ops::{Add, Sub, Mul, Div, Rem, BitAnd, BitOr, BitXor, Shl, Shr};
+use core::ops::{AddAssign, SubAssign, MulAssign, DivAssign, RemAssign, BitAndAssign, BitOrAssign, BitXorAssign, ShlAssign, ShrAssign};
+use crate::{IntoInnerHelper, DetachedDrop};
+
+macro_rules! forward_binary_ops {
+    ($($op:ident $method:ident $op_assign:ident $method_assign:ident),*) => {
+        $(
+            impl<'a, T, D, Rhs> $op<Rhs> for &'a IntoInnerHelper<T, D> where &'a T: $op<Rhs>, D: DetachedDrop<Implementor=T> {
+                type Output = <&'a T as $op<Rhs>>::Output;
+
+                fn $method(self, rhs: Rhs) -> Self::Output {
+                    self.inner().$method(rhs)
+                }
+            }
+
+            impl<T, D, Rhs> $op_assign<Rhs> for IntoInnerHelper<T, D> where T: $op_assign<Rhs>, D: DetachedDrop<Implementor=T> {
+                fn $method_assign(&mut self, rhs: Rhs) {
+                    self.inner_mut().$method_assign(rhs)
+                }
+            }
+        )*
+    }
+}
+
+forward_binary_ops!(
+    Add add AddAssign add_assign,
+    Sub sub SubAssign sub_assign,
+    Mul mul MulAssign mul_assign,
+    Div div DivAssign div_assign,
+    Rem rem RemAssign rem_assign,
+    BitAnd bitand BitAndAssign bitand_assign,
+    BitOr bitor BitOrAssign bitor_assign,
+    BitXor bitxor BitXorAssign bitxor_assign,
+    Shl shl ShlAssign shl_assign,
+    Shr shr ShrAssign shr_assign
+);
+
+macro_rules! forward_unary_ops {
+    ($($op:ident $method:ident),*) => {
+        $(
+            impl<'a, T, D> $op for &'a IntoInnerHelper<T, D> where &'a T: $op, D: DetachedDrop<Implementor=T> {
+                type Output = <&'a T as $op>::Output;
+
+                fn $method(self) -> Self::Output {
+                    self.inner().$method()
+                }
+            }
+        )*
+    }
+}
+
+forward_unary_ops!(Not not, Neg neg);
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use crate::{IntoInnerHelper, DetachedDrop};
+
+    std::thread_local! {
+        static LAST_DROPPED: Cell<Option<i32>> = const { Cell::new(None) };
+    }
+
+    enum Record {}
+
+    impl DetachedDrop for Record {
+        type Implementor = i32;
+
+        fn drop(value: Self::Implementor) {
+            LAST_DROPPED.with(|last| last.set(Some(value)));
+        }
+    }
+
+    struct Counter(IntoInnerHelper<i32, Record>);
+
+    #[test]
+    fn binary_ops_return_raw_result() {
+        let counter = Counter(IntoInnerHelper::new(40));
+        let sum: i32 = &counter.0 + 2;
+        assert_eq!(sum, 42);
+        assert_eq!(&counter.0 - 2, 38);
+        assert_eq!(&counter.0 * 2, 80);
+        assert_eq!(&counter.0 << 1, 80);
+        assert_eq!(LAST_DROPPED.with(Cell::get), None);
+        core::mem::drop(counter);
+        assert_eq!(LAST_DROPPED.with(Cell::get), Some(40));
+    }
+
+    #[test]
+    fn assign_ops_modify_in_place() {
+        let mut counter = Counter(IntoInnerHelper::new(40));
+        counter.0 += 4;
+        counter.0 -= 2;
+        counter.0 |= 1;
+        assert_eq!(*counter.0.inner(), 43);
+        assert_eq!(LAST_DROPPED.with(Cell::get), None);
+        core::mem::drop(counter);
+        assert_eq!(LAST_DROPPED.with(Cell::get), Some(43));
+    }
+
+    enum FlagDrop {}
+
+    impl DetachedDrop for FlagDrop {
+        type Implementor = bool;
+
+        fn drop(value: Self::Implementor) {
+            LAST_DROPPED.with(|last| last.set(Some(value.into())));
+        }
+    }
+
+    #[test]
+    fn unary_ops_return_raw_result() {
+        let flag = <IntoInnerHelper<_, FlagDrop>>::new(true);
+        let negated: bool = !&flag;
+        assert!(!negated);
+        core::mem::drop(flag);
+        assert_eq!(LAST_DROPPED.with(Cell::get), Some(1));
+
+        let counter = Counter(IntoInnerHelper::new(42));
+        assert_eq!(-&counter.0, -42);
+        core::mem::drop(counter);
+        assert_eq!(LAST_DROPPED.with(Cell::get), Some(42));
+    }
+}
@@ -0,0 +1,154 @@
+//! Information about the panic during which a detached drop runs.
+//!
+//! The standard library doesn't expose the payload of the current panic to destructors, so the
+//! context is captured by a panic hook installed using `install_panic_context_hook`. Without the
+//! hook `DetachedDrop::drop_during_panic` still runs, but receives an empty context.
+//!
+//! `std::panic::resume_unwind` doesn't call the panic hook, so detached drops running while such
+//! unwinding receive the context of the last panic on the thread that did call it, even if that
+//! panic was caught long ago. Call `clear_panic_context` after catching a panic to make them
+//! receive an empty context instead.
+
+use core::cell::RefCell;
+use std::string::{String, ToString};
+use std::sync::Once;
+
+std::thread_local! {
+    static CURRENT: RefCell<PanicContext> = const { RefCell::new(PanicContext::empty()) };
+}
+
+/// A token passed to the optional unwinding hooks of `DetachedDrop`.
+///
+/// Only this crate can create the token, so the hooks can be overridden, but only the helpers
+/// can call them. An override that forwards to the hooks of another marker can pass on the token
+/// it received.
+#[derive(Debug, Clone, Copy)]
+pub struct HookToken(());
+
+impl HookToken {
+    pub(crate) const fn new() -> Self {
+        HookToken(())
+    }
+}
+
+/// Information about the panic that is currently unwinding the thread.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PanicContext {
+    message: Option<String>,
+    location: Option<(String, u32)>,
+}
+
+impl PanicContext {
+    const fn empty() -> Self {
+        PanicContext { message: None, location: None, }
+    }
+
+    /// Returns the panic message if the payload is a string.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    /// Returns the file where the panic occurred.
+    pub fn file(&self) -> Option<&str> {
+        self.location.as_ref().map(|(file, _)| &**file)
+    }
+
+    /// Returns the line where the panic occurred.
+    pub fn line(&self) -> Option<u32> {
+        self.location.as_ref().map(|(_, line)| *line)
+    }
+
+    /// Calls `f` with the context of the current panic.
+    pub(crate) fn with_current<R>(f: impl FnOnce(&PanicContext) -> R) -> R {
+        let context = CURRENT.try_with(|current| current.borrow().clone()).unwrap_or(PanicContext::empty());
+        f(&context)
+    }
+}
+
+/// Installs a panic hook capturing the context passed to `DetachedDrop::drop_during_panic`.
+///
+/// The previously installed hook is still called afterwards, so panic messages are printed as
+/// usual. Installing the hook more than once has no effect.
+pub fn install_panic_context_hook() {
+    static INSTALL: Once = Once::new();
+
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(std::boxed::Box::new(move |info| {
+            let payload = info.payload();
+            let message = payload.downcast_ref::<&str>().map(ToString::to_string).or_else(|| payload.downcast_ref::<String>().cloned());
+            let location = info.location().map(|location| (location.file().to_string(), location.line()));
+            let _ = CURRENT.try_with(|current| *current.borrow_mut() = PanicContext { message, location, });
+            previous(info)
+        }));
+    });
+}
+
+/// Forgets the context captured on this thread by the hook installed using
+/// `install_panic_context_hook`.
+///
+/// The context is kept until the next panic, so calling this after catching a panic prevents it
+/// from being reported as the context of a later unwinding started by
+/// `std::panic::resume_unwind`, which doesn't call the hook.
+pub fn clear_panic_context() {
+    let _ = CURRENT.try_with(|current| *current.borrow_mut() = PanicContext::empty());
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+    use super::{PanicContext, HookToken, install_panic_context_hook, clear_panic_context};
+    use crate::{IntoInnerHelper, DetachedDrop};
+
+    std::thread_local! {
+        static RECEIVED: RefCell<Option<(&'static str, PanicContext)>> = const { RefCell::new(None) };
+    }
+
+    enum CloseReporting {}
+
+    impl DetachedDrop for CloseReporting {
+        type Implementor = &'static str;
+
+        fn drop(_: Self::Implementor) {
+            panic!("must not run while panicking");
+        }
+
+        fn drop_during_panic(resource: Self::Implementor, context: &PanicContext, _: HookToken) {
+            RECEIVED.with(|received| *received.borrow_mut() = Some((resource, context.clone())));
+        }
+    }
+
+    #[test]
+    fn hook_receives_context() {
+        install_panic_context_hook();
+        let result = std::panic::catch_unwind(|| {
+            let _guard = <IntoInnerHelper<_, CloseReporting>>::new("database connection");
+            panic!("query failed: {}", 42);
+        });
+        assert!(result.is_err());
+        let (resource, context) = RECEIVED.with(RefCell::take).unwrap();
+        assert_eq!(resource, "database connection");
+        assert_eq!(context.message(), Some("query failed: 42"));
+        assert_eq!(context.file(), Some(file!()));
+        assert!(context.line().is_some());
+    }
+
+    #[test]
+    fn resume_unwind_keeps_stale_context() {
+        install_panic_context_hook();
+        assert!(std::panic::catch_unwind(|| panic!("caught earlier")).is_err());
+
+        let resume = || std::panic::catch_unwind(|| {
+            let _guard = <IntoInnerHelper<_, CloseReporting>>::new("file");
+            std::panic::resume_unwind(std::boxed::Box::new("resumed"));
+        });
+        assert!(resume().is_err());
+        let (_, context) = RECEIVED.with(RefCell::take).unwrap();
+        assert_eq!(context.message(), Some("caught earlier"));
+
+        clear_panic_context();
+        assert!(resume().is_err());
+        let (_, context) = RECEIVED.with(RefCell::take).unwrap();
+        assert_eq!(context, PanicContext::empty());
+    }
+}
@@ -0,0 +1,169 @@
+use std::io::Write;
+use serde::Serialize;
+use crate::{IntoInnerHelper, DetachedDrop, FinalizeError};
+
+/// A serialization format used by `PersistOnDrop`.
+///
+/// This keeps the crate independent of serialization libraries, implementing it is usually a
+/// one-liner calling e.g. `serde_json::to_writer`.
+pub trait PersistFormat {
+    /// The error returned when serialization fails.
+    type Error: core::fmt::Debug;
+
+    /// Serializes `value` into `writer`.
+    fn persist<T, W>(value: &T, writer: W) -> Result<(), Self::Error> where T: Serialize + ?Sized, W: Write;
+}
+
+/// A guard serializing the inner value into a writer on drop.
+///
+/// Errors can't be reported from drop, so they are ignored, or logged with the `log` feature. Use
+/// `persist` to handle them. `into_inner` returns the value without persisting it.
+///
+/// # Example
+///
+/// ```
+/// use into_inner_drop::{PersistOnDrop, PersistFormat};
+///
+/// enum Json {}
+///
+/// impl PersistFormat for Json {
+///     type Error = serde_json::Error;
+///
+///     fn persist<T, W>(value: &T, writer: W) -> Result<(), Self::Error> where T: serde::Serialize + ?Sized, W: std::io::Write {
+///         serde_json::to_writer(writer, value)
+///     }
+/// }
+///
+/// let mut saved = Vec::new();
+/// let mut settings = <PersistOnDrop<_, _, Json>>::new(vec!["dark mode"], &mut saved);
+/// settings.inner_mut().push("large font");
+/// drop(settings);
+/// assert_eq!(saved, br#"["dark mode","large font"]"#);
+/// ```
+pub struct PersistOnDrop<T, W, F> where T: Serialize, W: Write, F: PersistFormat {
+    inner: IntoInnerHelper<Persisted<T, W>, PersistDrop<T, W, F>>,
+}
+
+struct Persisted<T, W> {
+    value: T,
+    writer: W,
+}
+
+struct PersistDrop<T, W, F>(core::convert::Infallible, core::marker::PhantomData<fn(T, W, F)>);
+
+impl<T, W, F> DetachedDrop for PersistDrop<T, W, F> where T: Serialize, W: Write, F: PersistFormat {
+    type Implementor = Persisted<T, W>;
+
+    fn drop(value: Self::Implementor) {
+        #[cfg_attr(not(feature = "log"), allow(unused_variables))]
+        if let Err(error) = F::persist(&value.value, value.writer) {
+            #[cfg(feature = "log")]
+            log::warn!(target: "into_inner_drop", "failed to persist {} on drop: {:?}", core::any::type_name::<T>(), error);
+        }
+    }
+}
+
+impl<T, W, F> PersistOnDrop<T, W, F> where T: Serialize, W: Write, F: PersistFormat {
+    /// Creates the guard persisting `value` into `writer`.
+    pub fn new(value: T, writer: W) -> Self {
+        PersistOnDrop {
+            inner: IntoInnerHelper::new(Persisted { value, writer, }),
+        }
+    }
+
+    /// Accesses the inner value.
+    pub fn inner(&self) -> &T {
+        &self.inner.inner().value
+    }
+
+    /// Accesses the inner value mutably.
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner.inner_mut().value
+    }
+
+    /// Accesses the writer.
+    pub fn writer(&self) -> &W {
+        &self.inner.inner().writer
+    }
+
+    /// Persists the inner value now, returning the error if it failed.
+    pub fn persist(self) -> Result<(), FinalizeError<F::Error>> {
+        let Persisted { value, writer, } = self.inner.into_inner();
+        F::persist(&value, writer).map_err(FinalizeError::new::<T>)
+    }
+
+    /// Moves out the inner value without persisting it.
+    pub fn into_inner(self) -> T {
+        self.into_parts().0
+    }
+
+    /// Moves out the inner value and the writer without persisting.
+    pub fn into_parts(self) -> (T, W) {
+        let Persisted { value, writer, } = self.inner.into_inner();
+        (value, writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Write};
+    use std::vec::Vec;
+    use super::{PersistOnDrop, PersistFormat};
+
+    enum Json {}
+
+    impl PersistFormat for Json {
+        type Error = serde_json::Error;
+
+        fn persist<T, W>(value: &T, writer: W) -> Result<(), Self::Error> where T: serde::Serialize + ?Sized, W: Write {
+            serde_json::to_writer(writer, value)
+        }
+    }
+
+    #[derive(serde::Serialize)]
+    struct Settings {
+        theme: &'static str,
+        font_size: u8,
+    }
+
+    struct Broken;
+
+    impl Write for Broken {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn persists_on_drop() {
+        let mut saved = Vec::new();
+        let mut settings = <PersistOnDrop<_, _, Json>>::new(Settings { theme: "dark", font_size: 12 }, &mut saved);
+        settings.inner_mut().font_size += 2;
+        assert!(settings.writer().is_empty());
+        core::mem::drop(settings);
+        assert_eq!(saved, br#"{"theme":"dark","font_size":14}"#);
+    }
+
+    #[test]
+    fn into_inner_doesnt_persist() {
+        let mut saved = Vec::new();
+        let settings = <PersistOnDrop<_, _, Json>>::new(Settings { theme: "dark", font_size: 12 }, &mut saved);
+        assert_eq!(settings.into_inner().font_size, 12);
+        assert!(saved.is_empty());
+    }
+
+    #[test]
+    fn errors() {
+        let settings = <PersistOnDrop<_, _, Json>>::new(Settings { theme: "dark", font_size: 12 }, Broken);
+        let error = settings.persist().unwrap_err();
+        assert!(error.error().is_io());
+        assert!(error.type_name().ends_with("Settings"));
+
+        // Errors on the drop path are ignored.
+        core::mem::drop(<PersistOnDrop<_, _, Json>>::new(42, Broken));
+    }
+}
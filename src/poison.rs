@@ -0,0 +1,154 @@
+use core::ops::{Deref, DerefMut};
+use std::sync::PoisonError;
+use crate::{IntoInnerHelper, DetachedDrop};
+
+/// A helper tracking whether a panic occurred while the inner value was borrowed mutably.
+///
+/// This mirrors poisoning of `Mutex`: a panic while a guard returned by `borrow_mut` is alive
+/// poisons the helper, so `into_inner` returns an error alerting the caller that the value may be
+/// inconsistent. The detached drop still runs on drop regardless of poisoning.
+pub struct PoisonableHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    inner: IntoInnerHelper<T, D>,
+    poisoned: bool,
+}
+
+impl<T, D> PoisonableHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    /// Creates a helper that is not poisoned.
+    pub fn new(inner: T) -> Self {
+        PoisonableHelper {
+            inner: IntoInnerHelper::new(inner),
+            poisoned: false,
+        }
+    }
+
+    /// Accesses the inner value.
+    pub fn inner(&self) -> &T {
+        self.inner.inner()
+    }
+
+    /// Borrows the inner value mutably, poisoning the helper if a panic occurs before the returned
+    /// guard is dropped.
+    pub fn borrow_mut(&mut self) -> PoisonGuard<'_, T> {
+        PoisonGuard {
+            value: self.inner.inner_mut(),
+            poisoned: &mut self.poisoned,
+            panicking: std::thread::panicking(),
+        }
+    }
+
+    /// Returns `true` if a panic occurred while the inner value was borrowed mutably.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+
+    /// Clears the poisoned state, e.g. after the value was checked or repaired.
+    pub fn clear_poison(&mut self) {
+        self.poisoned = false;
+    }
+
+    /// Moves out the inner value, preventing the detached drop.
+    ///
+    /// If the helper is poisoned the value is still available through the returned error.
+    pub fn into_inner(self) -> Result<T, PoisonError<T>> {
+        let poisoned = self.poisoned;
+        let value = self.inner.into_inner();
+        if poisoned {
+            Err(PoisonError::new(value))
+        } else {
+            Ok(value)
+        }
+    }
+}
+
+/// A mutable borrow of the value of `PoisonableHelper`, returned by `borrow_mut`.
+pub struct PoisonGuard<'a, T> {
+    value: &'a mut T,
+    poisoned: &'a mut bool,
+    panicking: bool,
+}
+
+impl<T> Deref for PoisonGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+impl<T> DerefMut for PoisonGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.value
+    }
+}
+
+impl<T> Drop for PoisonGuard<'_, T> {
+    fn drop(&mut self) {
+        // Like `Mutex`, only panics that started while the value was borrowed poison it.
+        if !self.panicking && std::thread::panicking() {
+            *self.poisoned = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use std::vec::Vec;
+    use super::PoisonableHelper;
+    use crate::DetachedDrop;
+
+    std::thread_local! {
+        static DROPPED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    enum CountDrop {}
+
+    impl DetachedDrop for CountDrop {
+        type Implementor = Vec<u32>;
+
+        fn drop(_: Self::Implementor) {
+            DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+        }
+    }
+
+    fn poison(helper: &mut PoisonableHelper<Vec<u32>, CountDrop>) {
+        let result = std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| {
+            let mut values = helper.borrow_mut();
+            values.push(2);
+            panic!("failed halfway through the update");
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn clean_extract() {
+        let mut helper = <PoisonableHelper<_, CountDrop>>::new(Vec::new());
+        helper.borrow_mut().push(1);
+        assert!(!helper.is_poisoned());
+        assert_eq!(helper.into_inner().unwrap(), [1]);
+        assert_eq!(DROPPED.with(Cell::get), 0);
+    }
+
+    #[test]
+    fn poisoned_extract() {
+        let mut helper = <PoisonableHelper<_, CountDrop>>::new(std::vec![1]);
+        poison(&mut helper);
+        assert!(helper.is_poisoned());
+        assert_eq!(helper.into_inner().unwrap_err().into_inner(), [1, 2]);
+        assert_eq!(DROPPED.with(Cell::get), 0);
+
+        let mut helper = <PoisonableHelper<_, CountDrop>>::new(Vec::new());
+        poison(&mut helper);
+        helper.clear_poison();
+        assert_eq!(helper.inner(), &[2]);
+        assert!(helper.into_inner().is_ok());
+    }
+
+    #[test]
+    fn poisoned_drop() {
+        let mut helper = <PoisonableHelper<_, CountDrop>>::new(Vec::new());
+        poison(&mut helper);
+        core::mem::drop(helper);
+        assert_eq!(DROPPED.with(Cell::get), 1);
+    }
+}
@@ -0,0 +1,165 @@
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::ops::{Deref, DerefMut};
+use crate::{IntoInnerHelper, DetachedDrop, drop_each};
+
+/// A pool recycling guarded values.
+///
+/// Values checked out of the pool return back to it when the returned `PoolGuard` is dropped,
+/// instead of running the detached drop. The detached drop of `D` runs on each pooled value when
+/// the pool itself is dropped.
+pub struct GuardPool<T, D> where D: DetachedDrop<Implementor=T> {
+    items: RefCell<Vec<T>>,
+    _phantom: core::marker::PhantomData<fn() -> D>,
+}
+
+impl<T, D> GuardPool<T, D> where D: DetachedDrop<Implementor=T> {
+    /// Creates an empty pool.
+    pub fn new() -> Self {
+        GuardPool {
+            items: RefCell::new(Vec::new()),
+            _phantom: Default::default(),
+        }
+    }
+
+    /// Adds a value to the pool.
+    pub fn put(&self, value: T) {
+        self.items.borrow_mut().push(value)
+    }
+
+    /// Returns the number of values available in the pool.
+    pub fn available(&self) -> usize {
+        self.items.borrow().len()
+    }
+
+    /// Checks out a value if the pool is not empty.
+    pub fn checkout(&self) -> Option<PoolGuard<'_, T, D>> {
+        let value = self.items.borrow_mut().pop()?;
+        Some(PoolGuard { value: Some(value), pool: self, })
+    }
+
+    /// Checks out a value, creating a new one using `create` if the pool is empty.
+    pub fn checkout_or_else<F>(&self, create: F) -> PoolGuard<'_, T, D> where F: FnOnce() -> T {
+        let value = self.items.borrow_mut().pop().unwrap_or_else(create);
+        PoolGuard { value: Some(value), pool: self, }
+    }
+}
+
+impl<T, D> Default for GuardPool<T, D> where D: DetachedDrop<Implementor=T> {
+    fn default() -> Self {
+        GuardPool::new()
+    }
+}
+
+impl<T, D> Drop for GuardPool<T, D> where D: DetachedDrop<Implementor=T> {
+    fn drop(&mut self) {
+        drop_each::<D, _>(core::mem::take(self.items.get_mut()));
+    }
+}
+
+/// A value checked out of `GuardPool`, returned back to it when dropped.
+pub struct PoolGuard<'a, T, D> where D: DetachedDrop<Implementor=T> {
+    // Always `Some` until dropped or detached.
+    value: Option<T>,
+    pool: &'a GuardPool<T, D>,
+}
+
+impl<'a, T, D> PoolGuard<'a, T, D> where D: DetachedDrop<Implementor=T> {
+    /// Removes the value from the pool, preventing both returning it and the detached drop.
+    pub fn detach(mut self) -> T {
+        self.value.take().expect("value present until dropped or detached")
+    }
+
+    /// Removes the value from the pool, guarding it by `D` instead.
+    pub fn into_guard(self) -> IntoInnerHelper<T, D> {
+        IntoInnerHelper::new(self.detach())
+    }
+}
+
+impl<'a, T, D> Deref for PoolGuard<'a, T, D> where D: DetachedDrop<Implementor=T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.value.as_ref().expect("value present until dropped or detached")
+    }
+}
+
+impl<'a, T, D> DerefMut for PoolGuard<'a, T, D> where D: DetachedDrop<Implementor=T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.value.as_mut().expect("value present until dropped or detached")
+    }
+}
+
+impl<'a, T, D> Drop for PoolGuard<'a, T, D> where D: DetachedDrop<Implementor=T> {
+    fn drop(&mut self) {
+        if let Some(value) = self.value.take() {
+            self.pool.put(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::cell::RefCell;
+    use super::GuardPool;
+    use crate::DetachedDrop;
+
+    std::thread_local! {
+        static CLOSED: RefCell<Vec<u32>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn closed() -> Vec<u32> {
+        CLOSED.with(|closed| closed.take())
+    }
+
+    enum Close {}
+
+    impl DetachedDrop for Close {
+        type Implementor = u32;
+
+        fn drop(id: Self::Implementor) {
+            CLOSED.with(|closed| closed.borrow_mut().push(id));
+        }
+    }
+
+    #[test]
+    fn checkout_returns_to_pool() {
+        let pool = <GuardPool<_, Close>>::new();
+        let next_id = core::cell::Cell::new(0);
+        let create = || {
+            next_id.set(next_id.get() + 1);
+            next_id.get()
+        };
+        {
+            let first = pool.checkout_or_else(create);
+            let second = pool.checkout_or_else(create);
+            assert_eq!((*first, *second), (1, 2));
+            assert_eq!(pool.available(), 0);
+        }
+        assert_eq!(pool.available(), 2);
+        let mut reused = pool.checkout().unwrap();
+        assert_eq!(*reused, 1);
+        *reused = 10;
+        core::mem::drop(reused);
+        assert!(closed().is_empty());
+        core::mem::drop(pool);
+        let mut closed = closed();
+        closed.sort_unstable();
+        assert_eq!(closed, [2, 10]);
+    }
+
+    #[test]
+    fn detach() {
+        let pool = <GuardPool<_, Close>>::new();
+        pool.put(1);
+        pool.put(2);
+        assert_eq!(pool.checkout().unwrap().detach(), 2);
+        let guard = pool.checkout().unwrap().into_guard();
+        assert!(pool.checkout().is_none());
+        core::mem::drop(pool);
+        assert!(closed().is_empty());
+        core::mem::drop(guard);
+        assert_eq!(closed(), [1]);
+    }
+}
@@ -0,0 +1,180 @@
+use core::ops::{Deref, DerefMut};
+use crate::{IntoInnerHelper, DetachedDrop};
+
+/// A marker that additionally declares a narrower view of the inner value.
+///
+/// `DetachedDrop` can't carry this as an associated type defaulting to `Implementor` because
+/// associated type defaults are unstable, so guards opt in by implementing this trait and using
+/// `ProjectedHelper` or `IntoInnerHelper::view`. Markers that don't need a narrower view can
+/// declare `type View = Self::Implementor;` and return the value itself.
+pub trait DetachedView: DetachedDrop {
+    /// The view exposed by `ProjectedHelper` through `Deref`.
+    type View: ?Sized;
+
+    /// Projects the inner value to the view.
+    fn view(value: &Self::Implementor) -> &Self::View;
+
+    /// Projects the inner value to the view mutably.
+    fn view_mut(value: &mut Self::Implementor) -> &mut Self::View;
+}
+
+/// A helper dereferencing to the view declared by `D` rather than to the inner value.
+///
+/// This is useful for guards that should expose only a sub-API of the inner value. The whole
+/// inner value is still accessible using `inner` and `inner_mut`.
+pub struct ProjectedHelper<T, D> where D: DetachedView<Implementor=T> {
+    inner: IntoInnerHelper<T, D>,
+}
+
+impl<T, D> ProjectedHelper<T, D> where D: DetachedView<Implementor=T> {
+    /// Creates the helper.
+    pub fn new(inner: T) -> Self {
+        ProjectedHelper {
+            inner: IntoInnerHelper::new(inner),
+        }
+    }
+
+    /// Accesses the inner value.
+    pub fn inner(&self) -> &T {
+        self.inner.inner()
+    }
+
+    /// Accesses the inner value mutably.
+    pub fn inner_mut(&mut self) -> &mut T {
+        self.inner.inner_mut()
+    }
+
+    /// Accesses the view declared by `D`.
+    ///
+    /// This is the same as dereferencing, but explicit.
+    pub fn view(&self) -> &D::View {
+        self.inner.view()
+    }
+
+    /// Accesses the view declared by `D` mutably.
+    pub fn view_mut(&mut self) -> &mut D::View {
+        self.inner.view_mut()
+    }
+
+    /// Moves out the inner value.
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<T, D> IntoInnerHelper<T, D> where D: DetachedView<Implementor=T> {
+    /// Accesses the view of the inner value declared by `D`.
+    pub fn view(&self) -> &D::View {
+        D::view(self.inner())
+    }
+
+    /// Accesses the view of the inner value declared by `D` mutably.
+    pub fn view_mut(&mut self) -> &mut D::View {
+        D::view_mut(self.inner_mut())
+    }
+}
+
+impl<T, D> Deref for ProjectedHelper<T, D> where D: DetachedView<Implementor=T> {
+    type Target = D::View;
+
+    fn deref(&self) -> &Self::Target {
+        self.view()
+    }
+}
+
+impl<T, D> DerefMut for ProjectedHelper<T, D> where D: DetachedView<Implementor=T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.view_mut()
+    }
+}
+
+impl<T, D> AsRef<D::View> for ProjectedHelper<T, D> where D: DetachedView<Implementor=T> {
+    fn as_ref(&self) -> &D::View {
+        self.view()
+    }
+}
+
+impl<T, D> AsMut<D::View> for ProjectedHelper<T, D> where D: DetachedView<Implementor=T> {
+    fn as_mut(&mut self) -> &mut D::View {
+        self.view_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+    use core::cell::Cell;
+    use super::{ProjectedHelper, DetachedView};
+    use crate::DetachedDrop;
+
+    std::thread_local! {
+        static FLUSHED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    struct Connection {
+        id: u32,
+        buffer: Vec<u8>,
+    }
+
+    enum Flush {}
+
+    impl DetachedDrop for Flush {
+        type Implementor = Connection;
+
+        fn drop(connection: Self::Implementor) {
+            FLUSHED.with(|flushed| flushed.set(flushed.get() + connection.buffer.len()));
+        }
+    }
+
+    impl DetachedView for Flush {
+        type View = Vec<u8>;
+
+        fn view(connection: &Self::Implementor) -> &Self::View {
+            &connection.buffer
+        }
+
+        fn view_mut(connection: &mut Self::Implementor) -> &mut Self::View {
+            &mut connection.buffer
+        }
+    }
+
+    #[test]
+    fn deref_to_view() {
+        let mut helper = <ProjectedHelper<_, Flush>>::new(Connection { id: 42, buffer: Vec::new() });
+        helper.extend_from_slice(b"hello");
+        helper.push(b'!');
+        assert_eq!(helper.len(), 6);
+        assert_eq!(helper.inner().id, 42);
+        core::mem::drop(helper);
+        assert_eq!(FLUSHED.with(Cell::get), 6);
+    }
+
+    #[test]
+    fn explicit_view() {
+        use crate::IntoInnerHelper;
+
+        let mut helper = <IntoInnerHelper<_, Flush>>::new(Connection { id: 42, buffer: Vec::new() });
+        helper.view_mut().extend_from_slice(b"hi");
+        assert_eq!(helper.view(), b"hi");
+        assert_eq!(helper.id, 42);
+        core::mem::drop(helper);
+        assert_eq!(FLUSHED.with(Cell::get), 2);
+
+        let mut helper = <ProjectedHelper<_, Flush>>::new(Connection { id: 42, buffer: Vec::new() });
+        helper.view_mut().push(b'!');
+        AsMut::<Vec<u8>>::as_mut(&mut helper).push(b'?');
+        assert_eq!(AsRef::<Vec<u8>>::as_ref(&helper), b"!?");
+        assert_eq!(helper.view(), b"!?");
+        core::mem::drop(helper);
+        assert_eq!(FLUSHED.with(Cell::get), 4);
+    }
+
+    #[test]
+    fn into_inner() {
+        let mut helper = <ProjectedHelper<_, Flush>>::new(Connection { id: 42, buffer: Vec::new() });
+        helper.push(b'!');
+        let connection = helper.into_inner();
+        assert_eq!(connection.buffer, b"!");
+        assert_eq!(FLUSHED.with(Cell::get), 0);
+    }
+}
@@ -0,0 +1,146 @@
+use alloc::boxed::Box;
+use crate::{IntoInnerHelper, DetachedDrop};
+
+/// The callback of `RawFinalizer`, running the detached drop of the helper behind the context.
+pub type RawCallback = unsafe fn(*mut ());
+
+/// A type-erased, one-shot finalizer of a helper, convertible to a callback and context pair.
+///
+/// This adapts the detached drop to runtimes accepting completion callbacks in the form of a
+/// function pointer and an opaque context pointer. Dropping the finalizer without converting it
+/// runs the detached drop.
+pub struct RawFinalizer {
+    callback: RawCallback,
+    context: *mut (),
+}
+
+/// Runs the detached drop of the helper boxed in `context`.
+///
+/// # Safety
+///
+/// `context` must come from `RawFinalizer::into_parts` of a finalizer created for `IntoInnerHelper<T, D>`
+/// and must not be used afterwards.
+unsafe fn finalize<T, D>(context: *mut ()) where D: DetachedDrop<Implementor=T> {
+    Box::from_raw(context as *mut IntoInnerHelper<T, D>).finalize()
+}
+
+impl RawFinalizer {
+    /// Boxes the helper, deferring its detached drop.
+    pub fn new<T, D>(helper: IntoInnerHelper<T, D>) -> Self where T: 'static, D: DetachedDrop<Implementor=T> + 'static {
+        RawFinalizer {
+            callback: finalize::<T, D>,
+            context: Box::into_raw(Box::new(helper)) as *mut (),
+        }
+    }
+
+    /// Runs the detached drop now.
+    pub fn run(self) {
+        let (callback, context) = self.into_parts();
+        // SAFETY: the pair comes from `into_parts` and is used only once.
+        unsafe { callback(context) }
+    }
+
+    /// Splits the finalizer into the callback and its context.
+    ///
+    /// The detached drop runs when the callback is called with the context. If it's never called
+    /// the helper leaks.
+    pub fn into_parts(self) -> (RawCallback, *mut ()) {
+        let this = core::mem::ManuallyDrop::new(self);
+        (this.callback, this.context)
+    }
+
+    /// Reassembles the finalizer from its parts.
+    ///
+    /// # Safety
+    ///
+    /// The parts must come from a single call to `into_parts` and the callback must not have been
+    /// called.
+    pub unsafe fn from_parts(callback: RawCallback, context: *mut ()) -> Self {
+        RawFinalizer {
+            callback,
+            context,
+        }
+    }
+}
+
+impl Drop for RawFinalizer {
+    fn drop(&mut self) {
+        // SAFETY: the pair comes from the constructor and is used only once since `self` is
+        // going away.
+        unsafe { (self.callback)(self.context) }
+    }
+}
+
+impl<T, D> IntoInnerHelper<T, D> where T: 'static, D: DetachedDrop<Implementor=T> + 'static {
+    /// Converts the helper into a type-erased finalizer.
+    pub fn into_raw_finalizer(self) -> RawFinalizer {
+        RawFinalizer::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use crate::{IntoInnerHelper, DetachedDrop};
+    use super::{RawFinalizer, RawCallback};
+
+    std::thread_local! {
+        static FINALIZED: Cell<u32> = const { Cell::new(0) };
+    }
+
+    fn finalized() -> u32 {
+        FINALIZED.with(Cell::get)
+    }
+
+    enum Record {}
+
+    impl DetachedDrop for Record {
+        type Implementor = u32;
+
+        fn drop(value: Self::Implementor) {
+            FINALIZED.with(|finalized| finalized.set(finalized.get() + value));
+        }
+    }
+
+    // Imitates a runtime calling the registered callbacks once tasks complete.
+    #[derive(Default)]
+    struct Runtime {
+        callbacks: std::vec::Vec<(RawCallback, *mut ())>,
+    }
+
+    impl Runtime {
+        fn register(&mut self, callback: RawCallback, context: *mut ()) {
+            self.callbacks.push((callback, context));
+        }
+
+        fn complete_all(&mut self) {
+            for (callback, context) in self.callbacks.drain(..) {
+                unsafe { callback(context) }
+            }
+        }
+    }
+
+    #[test]
+    fn callback_finalizes_once() {
+        let mut runtime = Runtime::default();
+        let (callback, context) = <IntoInnerHelper<_, Record>>::new(1).into_raw_finalizer().into_parts();
+        runtime.register(callback, context);
+        let (callback, context) = <IntoInnerHelper<_, Record>>::new(10).into_raw_finalizer().into_parts();
+        runtime.register(callback, context);
+        assert_eq!(finalized(), 0);
+        runtime.complete_all();
+        assert_eq!(finalized(), 11);
+        runtime.complete_all();
+        assert_eq!(finalized(), 11);
+    }
+
+    #[test]
+    fn run_and_drop() {
+        let finalizer = RawFinalizer::new(<IntoInnerHelper<_, Record>>::new(1));
+        finalizer.run();
+        assert_eq!(finalized(), 1);
+        let (callback, context) = <IntoInnerHelper<_, Record>>::new(10).into_raw_finalizer().into_parts();
+        core::mem::drop(unsafe { RawFinalizer::from_parts(callback, context) });
+        assert_eq!(finalized(), 11);
+    }
+}
@@ -0,0 +1,118 @@
+use core::ops::Deref;
+use crate::{IntoInnerHelper, DetachedDrop};
+
+/// A helper dereferencing to the inner value immutably only.
+///
+/// Unlike `IntoInnerHelper` this doesn't implement `DerefMut` or provide `inner_mut`, so the inner
+/// value can only be changed by explicitly calling `modify` or `replace`. This is useful for
+/// guards whose detached drop relies on invariants of the inner value: every mutation is visible
+/// at the call site and can't happen by accident through auto-deref.
+///
+/// ```
+/// use into_inner_drop::{ReadOnlyHelper, DetachedDrop};
+///
+/// enum Release {}
+///
+/// impl DetachedDrop for Release {
+///     type Implementor = u32;
+///
+///     fn drop(_: Self::Implementor) {}
+/// }
+///
+/// let mut helper = <ReadOnlyHelper<_, Release>>::new(41);
+/// helper.modify(|value| *value += 1);
+/// assert_eq!(*helper, 42);
+/// ```
+///
+/// Assigning through the helper doesn't compile:
+///
+/// ```compile_fail
+/// use into_inner_drop::{ReadOnlyHelper, DetachedDrop};
+///
+/// enum Release {}
+///
+/// impl DetachedDrop for Release {
+///     type Implementor = u32;
+///
+///     fn drop(_: Self::Implementor) {}
+/// }
+///
+/// let mut helper = <ReadOnlyHelper<_, Release>>::new(41);
+/// *helper = 42;
+/// ```
+pub struct ReadOnlyHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    inner: IntoInnerHelper<T, D>,
+}
+
+impl<T, D> ReadOnlyHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    /// Creates the helper.
+    pub fn new(inner: T) -> Self {
+        ReadOnlyHelper {
+            inner: IntoInnerHelper::new(inner),
+        }
+    }
+
+    /// Accesses the inner value.
+    pub fn inner(&self) -> &T {
+        self.inner.inner()
+    }
+
+    /// Mutates the inner value using `f`, returning its result.
+    pub fn modify<R, F>(&mut self, f: F) -> R where F: FnOnce(&mut T) -> R {
+        f(self.inner.inner_mut())
+    }
+
+    /// Replaces the inner value, returning the previous one without running the detached drop.
+    pub fn replace(&mut self, value: T) -> T {
+        self.inner.reset(value)
+    }
+
+    /// Moves out the inner value, preventing the detached drop.
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<T, D> Deref for ReadOnlyHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.inner()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use super::ReadOnlyHelper;
+    use crate::DetachedDrop;
+
+    std::thread_local! {
+        static RELEASED: Cell<Option<u32>> = const { Cell::new(None) };
+    }
+
+    enum Release {}
+
+    impl DetachedDrop for Release {
+        type Implementor = u32;
+
+        fn drop(value: Self::Implementor) {
+            RELEASED.with(|released| released.set(Some(value)));
+        }
+    }
+
+    #[test]
+    fn mutate_via_methods() {
+        let mut helper = <ReadOnlyHelper<_, Release>>::new(40);
+        assert_eq!(helper.modify(|value| { *value += 1; *value * 2 }), 82);
+        assert_eq!(helper.replace(42), 41);
+        assert_eq!(*helper, 42);
+        assert_eq!(RELEASED.with(Cell::get), None);
+        core::mem::drop(helper);
+        assert_eq!(RELEASED.with(Cell::get), Some(42));
+
+        let helper = <ReadOnlyHelper<_, Release>>::new(1);
+        assert_eq!(helper.into_inner(), 1);
+        assert_eq!(RELEASED.with(Cell::take), Some(42));
+    }
+}
@@ -0,0 +1,113 @@
+//! Detection of reentrant detached drops, enabled by the `reentrancy-check` feature.
+//!
+//! Each thread keeps a stack of the markers whose detached drop is currently running. If a
+//! detached drop drops another helper with the same marker, a warning is logged, since this often
+//! indicates a cycle in teardown dependencies. Markers are compared by their type names because
+//! they are not required to be `'static`.
+
+use core::cell::RefCell;
+use std::vec::Vec;
+
+std::thread_local! {
+    static ACTIVE: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A marker registered as running its detached drop, unregisters it when dropped.
+pub(crate) struct ActiveDrop(());
+
+impl ActiveDrop {
+    /// Registers `D` as running, warning if it's running already.
+    pub(crate) fn enter<D>() -> Self {
+        let marker = core::any::type_name::<D>();
+        // An inaccessible thread-local means the thread is exiting, the check is skipped then.
+        let _ = ACTIVE.try_with(|active| {
+            let mut active = active.borrow_mut();
+            if active.contains(&marker) {
+                log::warn!(target: "into_inner_drop", "detached drop of {} reentered while dropping {}", marker, active.join(" -> "));
+            }
+            active.push(marker);
+        });
+        ActiveDrop(())
+    }
+}
+
+impl Drop for ActiveDrop {
+    fn drop(&mut self) {
+        let _ = ACTIVE.try_with(|active| active.borrow_mut().pop());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::IntoInnerHelper;
+    use crate::labeled::tests::logger;
+
+    mod reentrant_check_inner {
+        use crate::DetachedDrop;
+
+        pub(super) enum Leaf {}
+
+        impl DetachedDrop for Leaf {
+            type Implementor = ();
+
+            fn drop(_: Self::Implementor) {}
+        }
+    }
+
+    mod reentrant_check_outer {
+        use crate::{IntoInnerHelper, DetachedDrop};
+        use super::reentrant_check_inner::Leaf;
+
+        pub(super) enum Parent {}
+
+        impl DetachedDrop for Parent {
+            type Implementor = IntoInnerHelper<(), Leaf>;
+
+            fn drop(child: Self::Implementor) {
+                child.finalize();
+            }
+        }
+    }
+
+    mod reentrant_check_cycle {
+        use crate::{IntoInnerHelper, DetachedDrop};
+
+        pub(super) struct Link(pub(super) Option<std::boxed::Box<IntoInnerHelper<Link, Node>>>);
+
+        pub(super) enum Node {}
+
+        impl DetachedDrop for Node {
+            type Implementor = Link;
+
+            fn drop(link: Self::Implementor) {
+                core::mem::drop(link.0);
+            }
+        }
+    }
+
+    #[test]
+    fn nested_different_markers() {
+        logger::records("");
+        let helper = <IntoInnerHelper<_, reentrant_check_outer::Parent>>::new(IntoInnerHelper::new(()));
+        core::mem::drop(helper);
+        assert!(logger::records("reentrant_check_outer").is_empty());
+        assert!(logger::records("reentrant_check_inner").is_empty());
+    }
+
+    #[test]
+    fn same_marker_reentered() {
+        use reentrant_check_cycle::{Link, Node};
+
+        logger::records("");
+        let tail = <IntoInnerHelper<_, Node>>::new(Link(None));
+        let head = <IntoInnerHelper<_, Node>>::new(Link(Some(std::boxed::Box::new(tail))));
+        core::mem::drop(head);
+        let records = logger::records("reentrant_check_cycle");
+        assert_eq!(records.len(), 1);
+        assert!(records[0].starts_with("detached drop of "));
+        assert!(records[0].contains("reentrant_check_cycle::Node reentered while dropping "));
+        // The stack is unwound correctly, dropping the helper again doesn't warn twice.
+        <IntoInnerHelper<_, Node>>::new(Link(None)).finalize();
+        assert_eq!(logger::records("reentrant_check_cycle").len(), 1);
+    }
+}
@@ -0,0 +1,212 @@
+use alloc::rc::Rc;
+use core::cell::Cell;
+use crate::{IntoInnerHelper, DetachedDrop, InPlace};
+use crate::unwind::{detached_drop, detached_drop_in_place};
+
+/// A token identifying a resource acquired reentrantly, shared by its `ReentrantHelper`s.
+///
+/// The token counts the helpers currently alive, the nesting depth.
+#[derive(Debug, Default)]
+pub struct ReentrancyToken {
+    depth: Cell<usize>,
+}
+
+impl ReentrancyToken {
+    /// Creates a shared token with no helpers alive.
+    pub fn new() -> Rc<Self> {
+        Rc::new(ReentrancyToken::default())
+    }
+
+    /// Returns the number of helpers currently alive.
+    pub fn depth(&self) -> usize {
+        self.depth.get()
+    }
+}
+
+/// A helper running the detached drop only when the outermost of nested helpers is dropped.
+///
+/// Each acquisition of the resource creates a helper sharing a single `ReentrancyToken`. Dropping
+/// the inner helpers just drops their values, the detached drop runs only for the value of the
+/// helper dropped last, normally the outermost one. `into_inner` leaves the nesting without
+/// running the detached drop, even if it's called on the last helper.
+pub struct ReentrantHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    inner: IntoInnerHelper<Reentrant<T>, ReentrantDrop<D>>,
+}
+
+struct Reentrant<T> {
+    value: T,
+    token: Rc<ReentrancyToken>,
+}
+
+impl<T> Reentrant<T> {
+    /// Decrements the depth, returning `true` if this was the last helper.
+    fn leave(&self) -> bool {
+        let depth = self.token.depth.get() - 1;
+        self.token.depth.set(depth);
+        depth == 0
+    }
+}
+
+struct ReentrantDrop<D>(core::convert::Infallible, core::marker::PhantomData<D>);
+
+impl<D> DetachedDrop for ReentrantDrop<D> where D: DetachedDrop {
+    type Implementor = Reentrant<D::Implementor>;
+
+    // `RUN_ON_PANIC` is left `true` since the depth must be decremented during unwinding too,
+    // `detached_drop` respects `D::RUN_ON_PANIC`.
+
+    fn drop(value: Self::Implementor) {
+        if value.leave() {
+            detached_drop::<D>(value.value);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn drop_during_panic(value: Self::Implementor, context: &crate::PanicContext, token: crate::HookToken) {
+        if value.leave() {
+            crate::unwind::detached_drop_during_panic::<D>(value.value, context, token);
+        }
+    }
+
+    fn drop_in_place(value: InPlace<'_, Self::Implementor>) {
+        if value.leave() {
+            // SAFETY: the token is moved out before projecting and never used through `value`
+            // again.
+            let token = unsafe { core::ptr::read(&value.token) };
+            detached_drop_in_place::<D>(unsafe { value.project(|value| &mut value.value) });
+            core::mem::drop(token);
+        }
+    }
+}
+
+impl<T, D> ReentrantHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    /// Creates a helper nested in the helpers sharing `token`.
+    pub fn new(inner: T, token: &Rc<ReentrancyToken>) -> Self {
+        token.depth.set(token.depth.get() + 1);
+        ReentrantHelper {
+            inner: IntoInnerHelper::new(Reentrant { value: inner, token: Rc::clone(token), }),
+        }
+    }
+
+    /// Accesses the inner value.
+    pub fn inner(&self) -> &T {
+        &self.inner.inner().value
+    }
+
+    /// Accesses the inner value mutably.
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner.inner_mut().value
+    }
+
+    /// Returns the shared token.
+    pub fn token(&self) -> &Rc<ReentrancyToken> {
+        &self.inner.inner().token
+    }
+
+    /// Returns `true` if dropping this helper would run the detached drop.
+    pub fn is_last(&self) -> bool {
+        self.token().depth() == 1
+    }
+
+    /// Moves out the inner value, leaving the nesting without running the detached drop.
+    pub fn into_inner(self) -> T {
+        let inner = self.inner.into_inner();
+        inner.leave();
+        inner.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use super::{ReentrantHelper, ReentrancyToken};
+    use crate::DetachedDrop;
+
+    std::thread_local! {
+        static RELEASED: Cell<Option<&'static str>> = const { Cell::new(None) };
+    }
+
+    fn released() -> Option<&'static str> {
+        RELEASED.with(Cell::take)
+    }
+
+    enum Release {}
+
+    impl DetachedDrop for Release {
+        type Implementor = &'static str;
+
+        fn drop(name: Self::Implementor) {
+            assert_eq!(RELEASED.with(|released| released.replace(Some(name))), None);
+        }
+    }
+
+    fn acquire(name: &'static str, token: &std::rc::Rc<ReentrancyToken>) -> ReentrantHelper<&'static str, Release> {
+        ReentrantHelper::new(name, token)
+    }
+
+    #[test]
+    fn single_cleanup_at_outermost() {
+        let token = ReentrancyToken::new();
+        {
+            let outer = acquire("outer", &token);
+            assert!(outer.is_last());
+            {
+                let middle = acquire("middle", &token);
+                {
+                    let inner = acquire("inner", &token);
+                    assert_eq!(inner.token().depth(), 3);
+                    assert!(!inner.is_last());
+                }
+                assert_eq!(token.depth(), 2);
+                core::mem::drop(middle);
+            }
+            assert_eq!(released(), None);
+            assert_eq!(*outer.inner(), "outer");
+        }
+        assert_eq!(released(), Some("outer"));
+        assert_eq!(token.depth(), 0);
+
+        // The token can be reused for another round of acquisitions.
+        core::mem::drop(acquire("again", &token));
+        assert_eq!(released(), Some("again"));
+    }
+
+    #[test]
+    fn into_inner_leaves_nesting() {
+        let token = ReentrancyToken::new();
+        let outer = acquire("outer", &token);
+        let inner = acquire("inner", &token);
+        assert_eq!(outer.into_inner(), "outer");
+        assert!(inner.is_last());
+        core::mem::drop(inner);
+        assert_eq!(released(), Some("inner"));
+
+        assert_eq!(acquire("alone", &token).into_inner(), "alone");
+        assert_eq!(released(), None);
+        assert_eq!(token.depth(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unwinding() {
+        use crate::unwind::tests::{Record, SkipOnPanic, drop_while_panicking};
+
+        let token = ReentrancyToken::new();
+        assert_eq!(drop_while_panicking(<ReentrantHelper<_, Record>>::new(42, &token)), ["drop_panicking"]);
+        assert!(drop_while_panicking(<ReentrantHelper<_, SkipOnPanic>>::new(42, &token)).is_empty());
+        assert_eq!(token.depth(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn drop_in_place() {
+        use crate::unwind::tests::{InPlaceRecord, called, in_place_address};
+
+        let address = {
+            let helper = <ReentrantHelper<_, InPlaceRecord>>::new(42, &ReentrancyToken::new());
+            helper.inner() as *const u32
+        };
+        assert_eq!(called(), ["drop_in_place"]);
+        assert_eq!(in_place_address(), Some(address));
+    }
+}
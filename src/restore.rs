@@ -0,0 +1,107 @@
+use core::ops::{Deref, DerefMut};
+use crate::{IntoInnerHelper, DetachedDrop};
+
+/// A guard temporarily overriding a value, restoring the previous one on drop.
+///
+/// The guard swaps the new value into the location and swaps the previous value back when it's
+/// dropped, dropping the overriding value. `into_inner` keeps the override in place and returns
+/// the previous value instead.
+pub struct OverrideGuard<'a, T> {
+    inner: IntoInnerHelper<Override<'a, T>, RestoreDrop<'a, T>>,
+}
+
+struct Override<'a, T> {
+    location: &'a mut T,
+    previous: T,
+}
+
+struct RestoreDrop<'a, T>(core::convert::Infallible, core::marker::PhantomData<&'a mut T>);
+
+impl<'a, T> DetachedDrop for RestoreDrop<'a, T> {
+    type Implementor = Override<'a, T>;
+
+    fn drop(value: Self::Implementor) {
+        *value.location = value.previous;
+    }
+}
+
+impl<'a, T> OverrideGuard<'a, T> {
+    /// Stores `value` in `location` until the guard is dropped.
+    pub fn new(location: &'a mut T, value: T) -> Self {
+        let previous = core::mem::replace(location, value);
+        OverrideGuard {
+            inner: IntoInnerHelper::new(Override { location, previous, }),
+        }
+    }
+
+    /// Accesses the value that will be restored.
+    pub fn previous(&self) -> &T {
+        &self.inner.inner().previous
+    }
+
+    /// Keeps the override in place, returning the previous value.
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner().previous
+    }
+}
+
+impl<T> Deref for OverrideGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.inner.inner().location
+    }
+}
+
+impl<T> DerefMut for OverrideGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.inner.inner_mut().location
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OverrideGuard;
+
+    #[test]
+    fn restores_on_drop() {
+        let check = dropcheck::DropCheck::new();
+        let (drop_token, drop_state) = check.pair();
+        let mut level = (1, None);
+        let mut guard = OverrideGuard::new(&mut level, (3, Some(drop_token)));
+        assert_eq!(guard.0, 3);
+        assert_eq!(guard.previous().0, 1);
+        guard.0 += 1;
+        core::mem::drop(guard);
+        assert_eq!(level.0, 1);
+        assert!(level.1.is_none());
+        assert!(drop_state.is_dropped());
+    }
+
+    #[test]
+    fn nested() {
+        let mut level = 1;
+        {
+            let mut outer = OverrideGuard::new(&mut level, 2);
+            {
+                let inner = OverrideGuard::new(&mut *outer, 3);
+                assert_eq!(*inner, 3);
+            }
+            assert_eq!(*outer, 2);
+        }
+        assert_eq!(level, 1);
+    }
+
+    #[test]
+    fn into_inner_keeps_override() {
+        let check = dropcheck::DropCheck::new();
+        let (drop_token, drop_state) = check.pair();
+        let mut level = (1, Some(drop_token));
+        let previous = OverrideGuard::new(&mut level, (3, None)).into_inner();
+        assert_eq!(level.0, 3);
+        assert_eq!(previous.0, 1);
+        assert!(drop_state.is_not_dropped());
+        core::mem::drop(previous);
+        assert!(drop_state.is_dropped());
+    }
+}
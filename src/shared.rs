@@ -0,0 +1,179 @@
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, Ordering};
+use crate::{IntoInnerHelper, DetachedDrop, InPlace};
+use crate::unwind::{detached_drop, detached_drop_in_place};
+
+/// A reference-counted helper running the detached drop once for all its clones.
+///
+/// All clones share the inner value and a single armed flag. The detached drop runs when the
+/// last clone is dropped, unless `into_inner` was called on any of the clones before.
+pub struct SharedArmHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    shared: Arc<IntoInnerHelper<SharedArm<T>, SharedArmDrop<D>>>,
+}
+
+struct SharedArm<T> {
+    value: T,
+    armed: AtomicBool,
+}
+
+struct SharedArmDrop<D>(core::convert::Infallible, core::marker::PhantomData<D>);
+
+impl<D> DetachedDrop for SharedArmDrop<D> where D: DetachedDrop {
+    type Implementor = SharedArm<D::Implementor>;
+
+    #[cfg(feature = "std")]
+    const RUN_ON_PANIC: bool = D::RUN_ON_PANIC;
+
+    fn drop(value: Self::Implementor) {
+        if value.armed.into_inner() {
+            detached_drop::<D>(value.value);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn drop_during_panic(value: Self::Implementor, context: &crate::PanicContext, token: crate::HookToken) {
+        if value.armed.into_inner() {
+            crate::unwind::detached_drop_during_panic::<D>(value.value, context, token);
+        }
+    }
+
+    fn drop_in_place(mut value: InPlace<'_, Self::Implementor>) {
+        if *value.armed.get_mut() {
+            // SAFETY: the flag doesn't need to be dropped.
+            detached_drop_in_place::<D>(unsafe { value.project(|value| &mut value.value) });
+        }
+    }
+}
+
+impl<T, D> SharedArmHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    /// Creates an armed helper.
+    pub fn new(inner: T) -> Self {
+        SharedArmHelper {
+            shared: Arc::new(IntoInnerHelper::new(SharedArm { value: inner, armed: AtomicBool::new(true), })),
+        }
+    }
+
+    /// Accesses the shared inner value.
+    pub fn inner(&self) -> &T {
+        &self.shared.value
+    }
+
+    /// Returns `true` if the detached drop will run when the last clone is dropped.
+    pub fn is_armed(&self) -> bool {
+        self.shared.armed.load(Ordering::Relaxed)
+    }
+
+    /// Disarms all clones, moving out the inner value if this is the last one.
+    ///
+    /// If other clones exist `None` is returned and the value is dropped normally, without
+    /// running the detached drop, once the last clone is gone. If multiple clones call this
+    /// concurrently exactly one of them receives the value.
+    pub fn into_inner(self) -> Option<T> {
+        // `Arc` synchronizes dropping of the last clone with dropping of the others, so the
+        // clone running the drop always observes the flag.
+        self.shared.armed.store(false, Ordering::Relaxed);
+        Arc::into_inner(self.shared).map(|shared| shared.into_inner().value)
+    }
+}
+
+impl<T, D> Clone for SharedArmHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    fn clone(&self) -> Self {
+        SharedArmHelper {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use super::SharedArmHelper;
+    use crate::DetachedDrop;
+
+    enum CountDrop {}
+
+    impl DetachedDrop for CountDrop {
+        type Implementor = &'static AtomicUsize;
+
+        fn drop(value: Self::Implementor) {
+            value.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    enum Dummy {}
+
+    impl DetachedDrop for Dummy {
+        type Implementor = dropcheck::DropToken;
+
+        fn drop(_: Self::Implementor) {
+            panic!("detached drop must not run");
+        }
+    }
+
+    #[test]
+    fn drop_once_across_clones() {
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+        let helper = <SharedArmHelper<_, CountDrop>>::new(&DROPPED);
+        let threads = (0..4).map(|_| {
+            let clone = helper.clone();
+            std::thread::spawn(move || core::mem::drop(clone))
+        }).collect::<std::vec::Vec<_>>();
+        threads.into_iter().for_each(|thread| thread.join().unwrap());
+        assert_eq!(DROPPED.load(Ordering::Relaxed), 0);
+        assert!(helper.is_armed());
+        core::mem::drop(helper);
+        assert_eq!(DROPPED.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn into_inner_disarms_all() {
+        let check = dropcheck::DropCheck::new();
+        let (drop_token, drop_state) = check.pair();
+        let helper = <SharedArmHelper<_, Dummy>>::new(drop_token);
+        let first = helper.clone();
+        let second = helper.clone();
+        assert!(first.into_inner().is_none());
+        assert!(!helper.is_armed());
+        core::mem::drop(helper);
+        assert!(drop_state.is_not_dropped());
+        let token = second.into_inner().unwrap();
+        assert!(drop_state.is_not_dropped());
+        core::mem::drop(token);
+        assert!(drop_state.is_dropped());
+    }
+
+    #[test]
+    fn last_clone_drops_disarmed_value() {
+        let check = dropcheck::DropCheck::new();
+        let (drop_token, drop_state) = check.pair();
+        let helper = <SharedArmHelper<_, Dummy>>::new(drop_token);
+        let clone = helper.clone();
+        assert!(helper.into_inner().is_none());
+        assert!(drop_state.is_not_dropped());
+        core::mem::drop(clone);
+        assert!(drop_state.is_dropped());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unwinding() {
+        use crate::unwind::tests::{Record, SkipOnPanic, drop_while_panicking};
+
+        assert_eq!(drop_while_panicking(<SharedArmHelper<_, Record>>::new(42)), ["drop_panicking"]);
+        assert!(drop_while_panicking(<SharedArmHelper<_, SkipOnPanic>>::new(42)).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn drop_in_place() {
+        use crate::unwind::tests::{InPlaceRecord, called, in_place_address};
+
+        let address = {
+            let helper = <SharedArmHelper<_, InPlaceRecord>>::new(42);
+            helper.inner() as *const u32
+        };
+        assert_eq!(called(), ["drop_in_place"]);
+        assert_eq!(in_place_address(), Some(address));
+    }
+}
@@ -0,0 +1,152 @@
+use core::marker::PhantomData;
+
+/// A builder of multi-phase cleanup made of plain functions.
+///
+/// The steps taking `&mut T` run in the order they were added, followed by the consuming final
+/// step passed to `finish`. The steps are stored in nested tuples of function pointers, so
+/// building the sequence doesn't allocate.
+///
+/// # Example
+///
+/// ```
+/// use into_inner_drop::{DropSteps, guard};
+///
+/// fn flush(buffer: &mut Vec<u8>) { buffer.push(b'\n'); }
+/// fn sync(buffer: &mut Vec<u8>) { buffer.push(b'.'); }
+/// fn close(buffer: Vec<u8>) { assert_eq!(buffer, b"data\n."); }
+///
+/// let teardown = DropSteps::new().step(flush).step(sync).finish(close);
+/// let buffer = guard(b"data".to_vec(), teardown);
+/// // flushes, syncs and closes the buffer
+/// ```
+pub struct DropSteps<T, S = ()> {
+    steps: S,
+    _phantom: PhantomData<fn(&mut T)>,
+}
+
+/// The sequence of `&mut` steps stored in `DropSteps`.
+///
+/// This is implemented for `()` and for nested pairs of sequences and function pointers and can't
+/// be implemented outside of this crate.
+pub trait Steps<T>: Copy + private::Sealed {
+    /// Runs all steps in order.
+    fn run(self, value: &mut T);
+}
+
+/// A step taking the value by mutable reference.
+type Step<T> = fn(&mut T);
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for () {}
+    impl<S, T> Sealed for (S, fn(&mut T)) where S: Sealed {}
+}
+
+impl<T> Steps<T> for () {
+    fn run(self, _: &mut T) {}
+}
+
+impl<S, T> Steps<T> for (S, Step<T>) where S: Steps<T> {
+    fn run(self, value: &mut T) {
+        self.0.run(value);
+        (self.1)(value);
+    }
+}
+
+impl<T> DropSteps<T> {
+    /// Creates a builder without any steps.
+    pub const fn new() -> Self {
+        DropSteps {
+            steps: (),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for DropSteps<T> {
+    fn default() -> Self {
+        DropSteps::new()
+    }
+}
+
+impl<T, S> DropSteps<T, S> where S: Steps<T> {
+    /// Appends a step taking the value by mutable reference.
+    pub fn step(self, step: Step<T>) -> DropSteps<T, (S, Step<T>)> {
+        DropSteps {
+            steps: (self.steps, step),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Finishes the sequence with a step consuming the value.
+    ///
+    /// The returned function can be passed to `guard` or stored in `ClosureHelper`. It's `Copy`,
+    /// so a single sequence can be used for any number of guards.
+    pub fn finish(self, last: fn(T)) -> impl FnOnce(T) + Copy {
+        let steps = self.steps;
+        move |mut value| {
+            steps.run(&mut value);
+            last(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+    use super::DropSteps;
+    use crate::guard;
+
+    std::thread_local! {
+        static LOG: RefCell<std::vec::Vec<&'static str>> = const { RefCell::new(std::vec::Vec::new()) };
+    }
+
+    fn log() -> std::vec::Vec<&'static str> {
+        LOG.with(|log| log.take())
+    }
+
+    fn flush(value: &mut u32) {
+        *value += 1;
+        LOG.with(|log| log.borrow_mut().push("flush"));
+    }
+
+    fn sync(value: &mut u32) {
+        *value *= 10;
+        LOG.with(|log| log.borrow_mut().push("sync"));
+    }
+
+    fn close(value: u32) {
+        assert_eq!(value, 50);
+        LOG.with(|log| log.borrow_mut().push("close"));
+    }
+
+    #[test]
+    fn phases_run_in_order_once() {
+        let teardown = DropSteps::new().step(flush).step(sync).finish(close);
+        let helper = guard(4, teardown);
+        assert!(log().is_empty());
+        core::mem::drop(helper);
+        assert_eq!(log(), ["flush", "sync", "close"]);
+        core::mem::drop(guard(4, teardown));
+        assert_eq!(log(), ["flush", "sync", "close"]);
+    }
+
+    #[test]
+    fn into_inner_skips_all_phases() {
+        let helper = guard(4, DropSteps::new().step(flush).finish(close));
+        assert_eq!(helper.into_inner(), 4);
+        assert!(log().is_empty());
+    }
+
+    #[test]
+    fn without_steps() {
+        fn record(value: u32) {
+            assert_eq!(value, 7);
+            LOG.with(|log| log.borrow_mut().push("close"));
+        }
+
+        core::mem::drop(guard(7, DropSteps::new().finish(record)));
+        assert_eq!(log(), ["close"]);
+    }
+}
@@ -0,0 +1,130 @@
+//! `DetachedDrop` implementations for tuples of markers.
+//!
+//! A tuple of markers drops a tuple of values, each element using its own marker. The elements
+//! are dropped in order, so `IntoInnerHelper<(A, B), (DropA, DropB)>` calls `DropA::drop` before
+//! `DropB::drop`. The same holds for `drop_panicking` and `drop_during_panic`, which respect
+//! `RUN_ON_PANIC` of each element. `drop_during_panic` passes the panic context to each element.
+
+use crate::DetachedDrop;
+
+macro_rules! impl_detached_drop_tuple {
+    ($($marker:ident $value:ident),+) => {
+        impl<$($marker),+> DetachedDrop for ($($marker,)+) where $($marker: DetachedDrop),+ {
+            type Implementor = ($($marker::Implementor,)+);
+
+            fn drop(value: Self::Implementor) {
+                let ($($value,)+) = value;
+                $($marker::drop($value);)+
+            }
+
+            #[cfg(feature = "std")]
+            fn drop_panicking(value: Self::Implementor, token: crate::HookToken) {
+                let ($($value,)+) = value;
+                $(
+                    if $marker::RUN_ON_PANIC {
+                        $marker::drop_panicking($value, token);
+                    } else {
+                        core::mem::drop($value);
+                    }
+                )+
+            }
+
+            #[cfg(feature = "std")]
+            fn drop_during_panic(value: Self::Implementor, context: &crate::PanicContext, token: crate::HookToken) {
+                let ($($value,)+) = value;
+                $(crate::unwind::detached_drop_during_panic::<$marker>($value, context, token);)+
+            }
+        }
+    }
+}
+
+impl_detached_drop_tuple!(A a);
+impl_detached_drop_tuple!(A a, B b);
+impl_detached_drop_tuple!(A a, B b, C c);
+impl_detached_drop_tuple!(A a, B b, C c, D d);
+impl_detached_drop_tuple!(A a, B b, C c, D d, E e);
+impl_detached_drop_tuple!(A a, B b, C c, D d, E e, F f);
+impl_detached_drop_tuple!(A a, B b, C c, D d, E e, F f, G g);
+impl_detached_drop_tuple!(A a, B b, C c, D d, E e, F f, G g, H h);
+impl_detached_drop_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i);
+impl_detached_drop_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j);
+impl_detached_drop_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k);
+impl_detached_drop_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l);
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+    use core::cell::RefCell;
+    use crate::{IntoInnerHelper, DetachedDrop};
+
+    std::thread_local! {
+        static ORDER: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+    }
+
+    struct Record<const N: usize>(core::convert::Infallible);
+
+    impl<const N: usize> DetachedDrop for Record<N> {
+        type Implementor = dropcheck::DropToken;
+
+        fn drop(_: Self::Implementor) {
+            ORDER.with(|order| order.borrow_mut().push(N));
+        }
+    }
+
+    fn take_order() -> Vec<usize> {
+        ORDER.with(|order| core::mem::take(&mut *order.borrow_mut()))
+    }
+
+    #[test]
+    fn pair() {
+        let check = dropcheck::DropCheck::new();
+        let helper = <IntoInnerHelper<_, (Record<0>, Record<1>)>>::new((check.token(), check.token()));
+        assert!(check.none_dropped());
+        core::mem::drop(helper);
+        assert!(check.all_dropped());
+        assert_eq!(take_order(), [0, 1]);
+    }
+
+    #[test]
+    fn triple() {
+        let check = dropcheck::DropCheck::new();
+        let helper = <IntoInnerHelper<_, (Record<0>, Record<1>, Record<2>)>>::new((check.token(), check.token(), check.token()));
+        core::mem::drop(helper);
+        assert!(check.all_dropped());
+        assert_eq!(take_order(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn five() {
+        type Markers = (Record<0>, Record<1>, Record<2>, Record<3>, Record<4>);
+
+        let check = dropcheck::DropCheck::new();
+        let tokens = (check.token(), check.token(), check.token(), check.token(), check.token());
+        let helper = <IntoInnerHelper<_, Markers>>::new(tokens);
+        core::mem::drop(helper);
+        assert!(check.all_dropped());
+        assert_eq!(take_order(), [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn into_inner_returns_tuple() {
+        let check = dropcheck::DropCheck::new();
+        let helper = <IntoInnerHelper<_, (Record<0>, Record<1>, Record<2>)>>::new((check.token(), check.token(), check.token()));
+        let (a, b, c) = helper.into_inner();
+        assert!(check.none_dropped());
+        core::mem::drop((a, b, c));
+        assert!(check.all_dropped());
+        assert!(take_order().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unwinding_context() {
+        use crate::unwind::tests::{ContextRecord, SkipOnPanic, drop_while_panicking, messages};
+
+        crate::install_panic_context_hook();
+        let helper = <IntoInnerHelper<_, (ContextRecord, SkipOnPanic, ContextRecord)>>::new((1, 2, 3));
+        assert!(drop_while_panicking(helper).is_empty());
+        assert_eq!(messages(), std::vec![Some("unwinding".into()); 2]);
+    }
+}
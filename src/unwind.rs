@@ -0,0 +1,169 @@
+//! Running detached drops while respecting unwinding.
+//!
+//! Helpers and wrapper markers that run the detached drop of another marker themselves must go
+//! through `detached_drop` instead of calling `DetachedDrop::drop` directly. Otherwise
+//! `RUN_ON_PANIC` and the unwinding hooks of the inner marker would be skipped.
+
+use crate::{DetachedDrop, InPlace};
+#[cfg(feature = "std")]
+use crate::{PanicContext, HookToken};
+
+/// Runs the detached drop of `D` on `value`.
+///
+/// While the thread is panicking this drops the value normally if `D::RUN_ON_PANIC` is `false`
+/// and calls `D::drop_during_panic` otherwise. Without `std` it always calls `D::drop`.
+pub(crate) fn detached_drop<D>(value: D::Implementor) where D: DetachedDrop {
+    #[cfg(feature = "std")]
+    {
+        if std::thread::panicking() {
+            return PanicContext::with_current(|context| detached_drop_during_panic::<D>(value, context, HookToken::new()));
+        }
+    }
+    D::drop(value)
+}
+
+/// Runs the detached drop of `D` on `value` while the thread is panicking.
+///
+/// This is used by markers forwarding `drop_during_panic` to pass on the context they received.
+/// The value is dropped normally if `D::RUN_ON_PANIC` is `false`.
+#[cfg(feature = "std")]
+pub(crate) fn detached_drop_during_panic<D>(value: D::Implementor, context: &PanicContext, token: HookToken) where D: DetachedDrop {
+    if D::RUN_ON_PANIC {
+        D::drop_during_panic(value, context, token);
+    }
+}
+
+/// Runs the detached drop of `D` on `value` without moving it.
+///
+/// Like `detached_drop`, the value is just dropped in place if the thread is panicking and
+/// `D::RUN_ON_PANIC` is `false`. Otherwise `D::drop_in_place` is called.
+pub(crate) fn detached_drop_in_place<D>(value: InPlace<'_, D::Implementor>) where D: DetachedDrop {
+    #[cfg(feature = "std")]
+    {
+        if !D::RUN_ON_PANIC && std::thread::panicking() {
+            return;
+        }
+    }
+    D::drop_in_place(value)
+}
+
+#[cfg(all(test, feature = "std"))]
+pub(crate) mod tests {
+    use core::cell::{Cell, RefCell};
+    use std::string::{String, ToString};
+    use std::vec::Vec;
+    use crate::{DetachedDrop, InPlace, PanicContext, HookToken};
+
+    std::thread_local! {
+        static CALLED: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+        static IN_PLACE: Cell<Option<usize>> = const { Cell::new(None) };
+        static MESSAGES: RefCell<Vec<Option<String>>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Returns the names of the hooks called by `Record` and `SkipOnPanic` since the last call.
+    pub(crate) fn called() -> Vec<&'static str> {
+        CALLED.with(RefCell::take)
+    }
+
+    fn record(name: &'static str) {
+        CALLED.with(|called| called.borrow_mut().push(name));
+    }
+
+    /// Records which of its hooks was called.
+    pub(crate) struct Record<T = u32>(core::convert::Infallible, core::marker::PhantomData<T>);
+
+    impl<T> DetachedDrop for Record<T> {
+        type Implementor = T;
+
+        fn drop(_: Self::Implementor) {
+            record("drop");
+        }
+
+        fn drop_panicking(_: Self::Implementor, _: crate::HookToken) {
+            record("drop_panicking");
+        }
+    }
+
+    /// Records the drop, but doesn't run during unwinding.
+    pub(crate) struct SkipOnPanic<T = u32>(core::convert::Infallible, core::marker::PhantomData<T>);
+
+    impl<T> DetachedDrop for SkipOnPanic<T> {
+        type Implementor = T;
+
+        const RUN_ON_PANIC: bool = false;
+
+        fn drop(_: Self::Implementor) {
+            record("drop");
+        }
+    }
+
+    /// Records the address of the value if it was dropped in place.
+    pub(crate) enum InPlaceRecord {}
+
+    impl DetachedDrop for InPlaceRecord {
+        type Implementor = u32;
+
+        fn drop(_: Self::Implementor) {
+            record("drop");
+        }
+
+        fn drop_in_place(value: InPlace<'_, Self::Implementor>) {
+            IN_PLACE.with(|in_place| in_place.set(Some(&*value as *const u32 as usize)));
+            record("drop_in_place");
+        }
+    }
+
+    /// Returns the address recorded by `InPlaceRecord` since the last call.
+    pub(crate) fn in_place_address() -> Option<*const u32> {
+        IN_PLACE.with(Cell::take).map(|address| address as *const u32)
+    }
+
+    /// Records the panic message passed to `drop_during_panic`.
+    pub(crate) enum ContextRecord {}
+
+    impl DetachedDrop for ContextRecord {
+        type Implementor = u32;
+
+        fn drop(_: Self::Implementor) {
+            record("drop");
+        }
+
+        fn drop_during_panic(_: Self::Implementor, context: &PanicContext, _: HookToken) {
+            MESSAGES.with(|messages| messages.borrow_mut().push(context.message().map(ToString::to_string)));
+        }
+    }
+
+    /// Returns the messages recorded by `ContextRecord` since the last call.
+    pub(crate) fn messages() -> Vec<Option<String>> {
+        MESSAGES.with(RefCell::take)
+    }
+
+    /// Drops `value` while panicking with the message `unwinding`, returning the hooks called.
+    pub(crate) fn drop_while_panicking<T>(value: T) -> Vec<&'static str> {
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || {
+            let _value = value;
+            panic!("unwinding");
+        }));
+        assert!(result.is_err());
+        called()
+    }
+
+    #[test]
+    fn detached_drop() {
+        super::detached_drop::<Record>(42);
+        assert_eq!(called(), ["drop"]);
+        super::detached_drop::<SkipOnPanic>(42);
+        assert_eq!(called(), ["drop"]);
+
+        struct Run(u32);
+
+        impl Drop for Run {
+            fn drop(&mut self) {
+                super::detached_drop::<Record>(self.0);
+                super::detached_drop::<SkipOnPanic>(self.0);
+            }
+        }
+
+        assert_eq!(drop_while_panicking(Run(42)), ["drop_panicking"]);
+    }
+}
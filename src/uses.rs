@@ -0,0 +1,145 @@
+use crate::{EmptiableHelper, DetachedDrop};
+use crate::unwind::detached_drop;
+
+/// A helper running the detached drop early, after the inner value was used a fixed number of
+/// times.
+///
+/// Each call to `use_once` consumes one use. The detached drop runs right after the last use, or
+/// when the helper is dropped if some uses remain. This models resources valid for a limited
+/// number of operations, e.g. a token allowing a few retries.
+pub struct UsesGuard<T, D> where D: DetachedDrop<Implementor=T> {
+    inner: EmptiableHelper<T, D>,
+    remaining: usize,
+}
+
+impl<T, D> UsesGuard<T, D> where D: DetachedDrop<Implementor=T> {
+    /// Creates the helper allowing `uses` uses.
+    ///
+    /// If `uses` is zero the detached drop runs immediately.
+    pub fn new(inner: T, uses: usize) -> Self {
+        let mut guard = UsesGuard {
+            inner: EmptiableHelper::new(inner),
+            remaining: uses,
+        };
+        if uses == 0 {
+            guard.finalize_now();
+        }
+        guard
+    }
+
+    /// Uses the inner value once, returning `None` if no uses remain.
+    ///
+    /// If this was the last use, the detached drop runs after `f` returns.
+    pub fn use_once<R, F>(&mut self, f: F) -> Option<R> where F: FnOnce(&mut T) -> R {
+        let result = f(self.inner.inner_mut()?);
+        self.remaining -= 1;
+        if self.remaining == 0 {
+            self.finalize_now();
+        }
+        Some(result)
+    }
+
+    /// Returns the number of remaining uses.
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Returns `true` if all uses were consumed and the detached drop ran.
+    pub fn is_finalized(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Accesses the inner value, returning `None` if it was already finalized.
+    pub fn inner(&self) -> Option<&T> {
+        self.inner.inner()
+    }
+
+    /// Moves out the inner value, preventing the detached drop.
+    ///
+    /// Returns `None` if the value was already finalized.
+    pub fn into_inner(self) -> Option<T> {
+        if self.is_finalized() {
+            None
+        } else {
+            self.inner.into_inner()
+        }
+    }
+
+    fn finalize_now(&mut self) {
+        if let Some(inner) = self.inner.take() {
+            detached_drop::<D>(inner);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use super::UsesGuard;
+    use crate::DetachedDrop;
+
+    std::thread_local! {
+        static REVOKED: Cell<Option<u32>> = const { Cell::new(None) };
+    }
+
+    fn revoked() -> Option<u32> {
+        REVOKED.with(Cell::take)
+    }
+
+    enum Revoke {}
+
+    impl DetachedDrop for Revoke {
+        type Implementor = u32;
+
+        fn drop(attempts: Self::Implementor) {
+            REVOKED.with(|revoked| revoked.set(Some(attempts)));
+        }
+    }
+
+    #[test]
+    fn exhaustion_finalizes_early() {
+        let mut guard = <UsesGuard<_, Revoke>>::new(0, 3);
+        assert_eq!(guard.use_once(|attempts| { *attempts += 1; "first" }), Some("first"));
+        assert_eq!(guard.remaining(), 2);
+        guard.use_once(|attempts| *attempts += 1);
+        assert_eq!(revoked(), None);
+        guard.use_once(|attempts| *attempts += 1);
+        assert!(guard.is_finalized());
+        assert_eq!(revoked(), Some(3));
+        assert_eq!(guard.use_once(|_| panic!("no uses remain")), None);
+        assert_eq!(guard.into_inner(), None);
+        assert_eq!(revoked(), None);
+    }
+
+    #[test]
+    fn remaining_uses_finalize_on_drop() {
+        let mut guard = <UsesGuard<_, Revoke>>::new(0, 3);
+        guard.use_once(|attempts| *attempts += 1);
+        core::mem::drop(guard);
+        assert_eq!(revoked(), Some(1));
+    }
+
+    #[test]
+    fn into_inner_before_exhaustion() {
+        let mut guard = <UsesGuard<_, Revoke>>::new(0, 2);
+        guard.use_once(|attempts| *attempts += 1);
+        assert_eq!(guard.into_inner(), Some(1));
+        assert_eq!(revoked(), None);
+    }
+
+    #[test]
+    fn zero_uses() {
+        let guard = <UsesGuard<_, Revoke>>::new(7, 0);
+        assert!(guard.is_finalized());
+        assert_eq!(revoked(), Some(7));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unwinding() {
+        use crate::unwind::tests::{Record, SkipOnPanic, drop_while_panicking};
+
+        assert_eq!(drop_while_panicking(<UsesGuard<_, Record>>::new(42, 2)), ["drop_panicking"]);
+        assert!(drop_while_panicking(<UsesGuard<_, SkipOnPanic>>::new(42, 2)).is_empty());
+    }
+}
@@ -0,0 +1,251 @@
+use crate::{IntoInnerHelper, DetachedDrop, InPlace};
+use crate::unwind::{detached_drop, detached_drop_in_place};
+
+/// A helper keeping another guard, the scope, alive until the detached drop has finished.
+///
+/// This ties the lifetime of a scope guard to the lifetime of the resource. The intended use is
+/// an entered `tracing` span (`tracing::span::EnteredSpan`): the span is entered for as long as the
+/// resource lives, the detached drop runs inside the span and the span is exited right after. The
+/// scope is dropped by `into_inner` too, so the span is exited even if the cleanup is skipped.
+/// With the `tracing` feature `SpanHelper` and `in_span` provide exactly this.
+pub struct WithinHelper<T, S, D> where D: DetachedDrop<Implementor=T> {
+    inner: IntoInnerHelper<Within<T, S>, WithinDrop<D, S>>,
+}
+
+struct Within<T, S> {
+    value: T,
+    scope: S,
+}
+
+struct WithinDrop<D, S>(core::convert::Infallible, core::marker::PhantomData<(D, S)>);
+
+impl<D, S> DetachedDrop for WithinDrop<D, S> where D: DetachedDrop {
+    type Implementor = Within<D::Implementor, S>;
+
+    #[cfg(feature = "std")]
+    const RUN_ON_PANIC: bool = D::RUN_ON_PANIC;
+
+    fn drop(value: Self::Implementor) {
+        let Within { value, scope, } = value;
+        detached_drop::<D>(value);
+        core::mem::drop(scope);
+    }
+
+    #[cfg(feature = "std")]
+    fn drop_during_panic(value: Self::Implementor, context: &crate::PanicContext, token: crate::HookToken) {
+        let Within { value, scope, } = value;
+        crate::unwind::detached_drop_during_panic::<D>(value, context, token);
+        core::mem::drop(scope);
+    }
+
+    fn drop_in_place(value: InPlace<'_, Self::Implementor>) {
+        // SAFETY: the scope is moved out before projecting and never used through `value` again.
+        let scope = unsafe { core::ptr::read(&value.scope) };
+        detached_drop_in_place::<D>(unsafe { value.project(|value| &mut value.value) });
+        core::mem::drop(scope);
+    }
+}
+
+impl<T, S, D> WithinHelper<T, S, D> where D: DetachedDrop<Implementor=T> {
+    /// Creates the helper, `scope` is dropped after the detached drop.
+    pub fn new(inner: T, scope: S) -> Self {
+        WithinHelper {
+            inner: IntoInnerHelper::new(Within { value: inner, scope, }),
+        }
+    }
+
+    /// Accesses the inner value.
+    pub fn inner(&self) -> &T {
+        &self.inner.inner().value
+    }
+
+    /// Accesses the inner value mutably.
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner.inner_mut().value
+    }
+
+    /// Accesses the scope.
+    pub fn scope(&self) -> &S {
+        &self.inner.inner().scope
+    }
+
+    /// Moves out the inner value, dropping the scope without running the detached drop.
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner().value
+    }
+
+    /// Moves out both the inner value and the scope without running the detached drop.
+    pub fn into_parts(self) -> (T, S) {
+        let inner = self.inner.into_inner();
+        (inner.value, inner.scope)
+    }
+}
+
+/// A helper entered in a `tracing` span for as long as the resource lives.
+///
+/// The span is entered when the helper is created and exited right after the detached drop, or
+/// when the inner value is extracted using `into_inner`.
+#[cfg(feature = "tracing")]
+pub type SpanHelper<T, D> = WithinHelper<T, tracing::span::EnteredSpan, D>;
+
+#[cfg(feature = "tracing")]
+impl<T, D> WithinHelper<T, tracing::span::EnteredSpan, D> where D: DetachedDrop<Implementor=T> {
+    /// Creates the helper, entering `span`.
+    pub fn in_span(inner: T, span: tracing::Span) -> Self {
+        WithinHelper::new(inner, span.entered())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+    use super::WithinHelper;
+    use crate::DetachedDrop;
+
+    std::thread_local! {
+        static EVENTS: RefCell<std::vec::Vec<&'static str>> = const { RefCell::new(std::vec::Vec::new()) };
+    }
+
+    fn record(event: &'static str) {
+        EVENTS.with(|events| events.borrow_mut().push(event));
+    }
+
+    fn events() -> std::vec::Vec<&'static str> {
+        EVENTS.with(|events| events.take())
+    }
+
+    // Behaves like an entered span.
+    struct Entered;
+
+    impl Entered {
+        fn enter() -> Self {
+            record("enter");
+            Entered
+        }
+    }
+
+    impl Drop for Entered {
+        fn drop(&mut self) {
+            record("exit");
+        }
+    }
+
+    enum Close {}
+
+    impl DetachedDrop for Close {
+        type Implementor = u32;
+
+        fn drop(_: Self::Implementor) {
+            record("close");
+        }
+    }
+
+    #[test]
+    fn finalizer_runs_inside_scope() {
+        let helper = <WithinHelper<_, _, Close>>::new(42, Entered::enter());
+        assert_eq!(*helper.inner(), 42);
+        assert_eq!(events(), ["enter"]);
+        core::mem::drop(helper);
+        assert_eq!(events(), ["close", "exit"]);
+    }
+
+    #[test]
+    fn into_inner_exits_scope() {
+        let helper = <WithinHelper<_, _, Close>>::new(42, Entered::enter());
+        assert_eq!(helper.into_inner(), 42);
+        assert_eq!(events(), ["enter", "exit"]);
+
+        let (value, scope) = <WithinHelper<_, _, Close>>::new(42, Entered::enter()).into_parts();
+        assert_eq!(value, 42);
+        assert_eq!(events(), ["enter"]);
+        core::mem::drop(scope);
+        assert_eq!(events(), ["exit"]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unwinding() {
+        use crate::unwind::tests::{Record, SkipOnPanic, drop_while_panicking};
+
+        assert_eq!(drop_while_panicking(<WithinHelper<_, _, Record>>::new(42, ())), ["drop_panicking"]);
+        assert!(drop_while_panicking(<WithinHelper<_, _, SkipOnPanic>>::new(42, ())).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn drop_in_place() {
+        use crate::unwind::tests::{InPlaceRecord, called, in_place_address};
+
+        let address = {
+            let helper = <WithinHelper<_, _, InPlaceRecord>>::new(42, ());
+            helper.inner() as *const u32
+        };
+        assert_eq!(called(), ["drop_in_place"]);
+        assert_eq!(in_place_address(), Some(address));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unwinding_context() {
+        use crate::unwind::tests::{ContextRecord, drop_while_panicking, messages};
+
+        crate::install_panic_context_hook();
+        let helper = <WithinHelper<_, _, (ContextRecord, ContextRecord)>>::new((1, 2), ());
+        assert!(drop_while_panicking(helper).is_empty());
+        assert_eq!(messages(), std::vec![Some("unwinding".into()); 2]);
+    }
+
+    #[cfg(feature = "tracing")]
+    mod span {
+        use tracing::{Subscriber, Metadata, Event};
+        use tracing::span::{Id, Attributes, Record};
+        use crate::SpanHelper;
+        use super::{Close, record, events};
+
+        // Records entering and exiting spans.
+        struct Recorder;
+
+        impl Subscriber for Recorder {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, _: &Attributes<'_>) -> Id {
+                Id::from_u64(1)
+            }
+
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+            fn event(&self, _: &Event<'_>) {}
+
+            fn enter(&self, _: &Id) {
+                record("enter");
+            }
+
+            fn exit(&self, _: &Id) {
+                record("exit");
+            }
+        }
+
+        #[test]
+        fn finalizer_runs_inside_span() {
+            tracing::subscriber::with_default(Recorder, || {
+                let helper = <SpanHelper<_, Close>>::in_span(42, tracing::info_span!("connection"));
+                assert_eq!(events(), ["enter"]);
+                core::mem::drop(helper);
+                assert_eq!(events(), ["close", "exit"]);
+            });
+        }
+
+        #[test]
+        fn into_inner_exits_span() {
+            tracing::subscriber::with_default(Recorder, || {
+                let helper = <SpanHelper<_, Close>>::in_span(42, tracing::info_span!("connection"));
+                assert_eq!(helper.into_inner(), 42);
+                assert_eq!(events(), ["enter", "exit"]);
+            });
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Checks that the forwarding impls coexist with each other and with impls downstream crates
+//! are expected to write. Everything here is verified at compile time.
+
+use core::borrow::Borrow;
+use core::hash::{Hash, Hasher};
+use core::ops::{Deref, DerefMut};
+use std::collections::hash_map::DefaultHasher;
+use into_inner_drop::{IntoInnerHelper, DetachedDrop, Finalize};
+
+#[derive(Debug, Default, PartialEq, Eq, Hash)]
+struct Buffer(Vec<u8>);
+
+impl Borrow<[u8]> for Buffer {
+    fn borrow(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Iterator for Buffer {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.pop()
+    }
+}
+
+enum BufferDrop {}
+
+impl DetachedDrop for BufferDrop {
+    type Implementor = Buffer;
+
+    fn drop(_: Self::Implementor) {}
+}
+
+type Helper = IntoInnerHelper<Buffer, BufferDrop>;
+
+// Downstream crates can implement `From` for their own inner type...
+impl From<Helper> for Buffer {
+    fn from(helper: Helper) -> Self {
+        helper.into_inner()
+    }
+}
+
+// ...local traits for the helper...
+trait Describe {
+    fn describe(&self) -> &'static str;
+}
+
+impl Describe for Helper {
+    fn describe(&self) -> &'static str {
+        "helper"
+    }
+}
+
+// ...and foreign traits for the local type, parametrized by the helper.
+impl PartialEq<Helper> for Buffer {
+    fn eq(&self, other: &Helper) -> bool {
+        *self == **other
+    }
+}
+
+// A downstream blanket impl over all helpers doesn't conflict with anything either.
+trait IntoBoxed {
+    fn into_boxed(self) -> Box<Self>;
+}
+
+impl<T, D> IntoBoxed for IntoInnerHelper<T, D> where D: DetachedDrop<Implementor=T> {
+    fn into_boxed(self) -> Box<Self> {
+        Box::new(self)
+    }
+}
+
+// Fallible markers can still be implemented downstream despite the blanket impl for
+// `DetachedDrop` markers.
+enum TryBufferDrop {}
+
+impl into_inner_drop::TryDetachedDrop for TryBufferDrop {
+    type Implementor = Buffer;
+    type Error = ();
+
+    fn try_drop(_: Self::Implementor) -> Result<(), Self::Error> {
+        Err(())
+    }
+}
+
+// The sealed unwinding hooks can be overridden downstream, forwarding the token to another
+// marker.
+#[cfg(feature = "std")]
+enum QuietBufferDrop {}
+
+#[cfg(feature = "std")]
+impl DetachedDrop for QuietBufferDrop {
+    type Implementor = Buffer;
+
+    fn drop(buffer: Self::Implementor) {
+        BufferDrop::drop(buffer)
+    }
+
+    fn drop_panicking(buffer: Self::Implementor, token: into_inner_drop::HookToken) {
+        BufferDrop::drop_panicking(buffer, token)
+    }
+}
+
+fn assert_traits<H>() where H: core::fmt::Debug + Deref<Target=Buffer> + DerefMut + Borrow<[u8]> + PartialEq + Eq + Hash + Iterator<Item=u8> + Finalize<Output=()> + Describe + IntoBoxed {}
+
+#[test]
+fn forwarding_impls_coexist() {
+    assert_traits::<Helper>();
+    #[cfg(feature = "std")]
+    drop(IntoInnerHelper::<_, QuietBufferDrop>::new(Buffer::default()));
+    assert!(into_inner_drop::TryIntoInnerHelper::<_, TryBufferDrop>::new(Buffer::default()).try_finalize().is_err());
+
+    let mut helper = Helper::new(Buffer(vec![1, 2, 3]));
+    assert_eq!(helper.describe(), "helper");
+    assert!(Buffer(vec![1, 2, 3]) == helper);
+    assert_eq!(Borrow::<[u8]>::borrow(&helper), [1, 2, 3]);
+    assert_eq!(helper.next(), Some(3));
+    assert_eq!(helper.0, [1, 2]);
+
+    let mut hasher = DefaultHasher::new();
+    helper.hash(&mut hasher);
+    let mut expected = DefaultHasher::new();
+    Buffer(vec![1, 2]).hash(&mut expected);
+    assert_eq!(hasher.finish(), expected.finish());
+
+    let buffer = Buffer::from(*helper.into_boxed());
+    assert_eq!(buffer, Buffer(vec![1, 2]));
+}
@@ -0,0 +1,90 @@
+//! Tests of impls generated by `#[derive(GuardWrapper)]`.
+
+use std::cell::RefCell;
+use into_inner_drop::{IntoInnerHelper, DetachedDrop, GuardWrapper};
+
+thread_local! {
+    static FLUSHED: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+mod inner {
+    pub(super) enum Flush {}
+
+    impl into_inner_drop::DetachedDrop for Flush {
+        type Implementor = Vec<u8>;
+
+        fn drop(data: Self::Implementor) {
+            super::FLUSHED.with(|flushed| flushed.borrow_mut().extend_from_slice(&data));
+        }
+    }
+}
+
+#[derive(GuardWrapper)]
+pub struct Buffer(IntoInnerHelper<Vec<u8>, inner::Flush>);
+
+#[derive(GuardWrapper)]
+#[guard_wrapper(Deref, Debug)]
+pub struct ReadOnly {
+    buffer: IntoInnerHelper<Vec<u8>, inner::Flush>,
+}
+
+struct Close<T>(std::convert::Infallible, std::marker::PhantomData<T>);
+
+impl<T> DetachedDrop for Close<T> {
+    type Implementor = T;
+
+    fn drop(_: Self::Implementor) {}
+}
+
+#[derive(GuardWrapper)]
+#[guard_wrapper(AsRef)]
+#[guard_wrapper(Deref)]
+pub struct Named<T> where T: AsRef<str> {
+    name: IntoInnerHelper<T, Close<T>>,
+}
+
+fn flushed() -> Vec<u8> {
+    FLUSHED.with(|flushed| flushed.take())
+}
+
+fn assert_not_impl<T>() where T: NotDerefMut<()> {}
+
+// Resolving `T: NotDerefMut<_>` is ambiguous (and doesn't compile) if `T: DerefMut`.
+trait NotDerefMut<A> {}
+
+impl<T: ?Sized> NotDerefMut<()> for T {}
+impl<T: ?Sized + std::ops::DerefMut> NotDerefMut<u8> for T {}
+
+#[test]
+fn all_traits() {
+    let mut buffer = Buffer(IntoInnerHelper::new(vec![1, 2]));
+    buffer.push(3);
+    assert_eq!(buffer.len(), 3);
+    assert_eq!(AsRef::<Vec<u8>>::as_ref(&buffer), &[1, 2, 3]);
+    assert_eq!(format!("{:?}", buffer), "[1, 2, 3]");
+    assert!(flushed().is_empty());
+    drop(buffer);
+    assert_eq!(flushed(), [1, 2, 3]);
+}
+
+#[test]
+fn selected_traits() {
+    let buffer = ReadOnly { buffer: IntoInnerHelper::new(vec![4, 2]) };
+    assert_eq!(*buffer, [4, 2]);
+    assert_eq!(format!("{:?}", buffer), "[4, 2]");
+    assert_not_impl::<ReadOnly>();
+    drop(buffer);
+    assert_eq!(flushed(), [4, 2]);
+}
+
+#[test]
+fn generic() {
+    fn name<T: AsRef<str>>(named: &Named<T>) -> &str {
+        named.as_ref().as_ref()
+    }
+
+    let named = Named { name: IntoInnerHelper::new(String::from("foo")) };
+    assert_eq!(name(&named), "foo");
+    assert_eq!(named.len(), 3);
+    assert_eq!(name(&Named { name: IntoInnerHelper::new("bar") }), "bar");
+}